@@ -6,6 +6,25 @@ pub const MINT_NFT_REPLY_ID: u64 = 2;
 pub const REDELEGATE_REPLY_ID: u64 = 3;
 pub const UNBONDING_NFT_REPLY_ID: u64 = 4;
 pub const REDEEM_BOND_REPLY_ID: u64 = 5;
+pub const CLAIM_REWARDS_REPLY_ID: u64 = 6;
+pub const TOP_UP_NFT_REPLY_ID: u64 = 7;
+pub const SPLIT_NFT_REPLY_ID: u64 = 8;
+pub const MERGE_NFT_REPLY_ID: u64 = 9;
+pub const REFRESH_NFT_REPLY_ID: u64 = 10;
+pub const COMPLETION_TIME_REPLY_ID: u64 = 11;
+pub const COMPOUND_REPLY_ID: u64 = 12;
+pub const MIGRATE_COLLECTION_REPLY_ID: u64 = 13;
+pub const PAID_REWARDS_REPLY_ID: u64 = 14;
+
+// Maximum percentage of the rewards and deposits
+// that can be taken as protocol fee
+pub const MAX_PROTOCOL_FEE_PERCENT: u64 = 10;
+
+// Number of NFTs whose delegations are migrated from the NFT
+// metadata in each batch, the maximum is the one of the NFT
+// contract so a short batch means the last NFT was migrated
+pub const DEFAULT_MIGRATION_LIMIT: u32 = 30;
+pub const MAX_MIGRATION_LIMIT: u32 = 100;
 
 // This is the default contract delimiter when
 // having to parse structs to strings for the
@@ -2,22 +2,41 @@ use std::ops::Add;
 use std::str::FromStr;
 
 use crate::error::ContractError;
-use crate::msg::ExecuteMsg;
-use crate::state::{DisplayType, CFG};
+use crate::fees;
+use crate::msg::{
+    DelegationTarget, ExecuteMsg, RedelegationDestination, UpdateConfigMsg, ValidatorWeight,
+};
+use crate::rewards::{self, ShareRatios};
+use crate::state::{
+    ClaimRewardsTmp, CompletionTimeTmp, DelegationRecord, DisplayType, PaidRewardsTmp, SharesMove,
+    CFG, CLAIM_REWARDS_TMP, COMPLETION_TIMES_TMP, DELEGATIONS, DELEGATIONS_MIGRATION,
+    PAID_REWARDS_TMP, REWARD_INDEXES, UNBONDINGS,
+};
+use crate::state::{
+    CompoundDelegation, CompoundTmp, PauseFlags, RedeemedNft, AUTO_COMPOUND, COMPOUND_TMP,
+    PAUSE_FLAGS,
+};
+use crate::status;
+use crate::unbondings;
+use crate::validators::{self, ValidatorSelector};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::{
-    entry_point, to_binary, Binary, Coin, CosmosMsg, DepsMut, Env, MessageInfo, Response, SubMsg,
-    Validator, WasmMsg,
+    entry_point, to_binary, Binary, Coin, CosmosMsg, Deps, DepsMut, Env, MessageInfo, Response,
+    SubMsg, WasmMsg,
 };
-use cosmwasm_std::{BankMsg, Empty, Timestamp, Uint128};
+use cosmwasm_std::{to_vec, Addr, BankMsg, Empty, Event, Storage, Timestamp, Uint128};
+use cw_ownable::Action;
+use serde::Serialize;
 use terra_proto_rs::alliance::alliance::MsgRedelegate;
 
 use super::{
     constants::{
-        DEFAULT_DELIMITER, MINT_NFT_REPLY_ID, REDEEM_BOND_REPLY_ID, REDELEGATE_REPLY_ID,
-        UNBONDING_NFT_REPLY_ID,
+        CLAIM_REWARDS_REPLY_ID, COMPLETION_TIME_REPLY_ID, COMPOUND_REPLY_ID, DEFAULT_DELIMITER,
+        DEFAULT_MIGRATION_LIMIT, MERGE_NFT_REPLY_ID, MIGRATE_COLLECTION_REPLY_ID,
+        MINT_NFT_REPLY_ID, PAID_REWARDS_REPLY_ID, REDEEM_BOND_REPLY_ID, REDELEGATE_REPLY_ID,
+        REFRESH_NFT_REPLY_ID, SPLIT_NFT_REPLY_ID, TOP_UP_NFT_REPLY_ID, UNBONDING_NFT_REPLY_ID,
     },
-    query,
+    migrate, query,
 };
 use cw721_progressive_metadata::state::Extension;
 use cw721_progressive_metadata::{
@@ -39,52 +58,341 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
-    // TODO send claim_rewards to the user if he has anys
+    assert_not_paused(deps.storage, &msg)?;
+    assert_delegations_migrated(deps.storage, &msg)?;
+
     match msg {
-        ExecuteMsg::MsgDelegate {} => try_delegate(env, info, deps),
+        ExecuteMsg::MsgDelegate {
+            token_id,
+            validators,
+        } => match token_id {
+            Some(token_id) => try_top_up(env, info, deps, token_id, validators),
+            None => try_delegate(env, info, deps, validators),
+        },
         ExecuteMsg::MsgStartUnbonding { token_id } => {
-            try_start_unbonding(env, info, deps, token_id)
+            try_start_unbonding(env, info, deps, token_id, None)
         }
-        ExecuteMsg::MsgRedelegate { token_id } => try_redelegate(env, info, deps, token_id),
+        ExecuteMsg::MsgStartPartialUnbonding { token_id, targets } => {
+            try_start_unbonding(env, info, deps, token_id, Some(targets))
+        }
+        ExecuteMsg::MsgSplit { token_id, parts } => try_split(env, info, deps, token_id, parts),
+        ExecuteMsg::MsgMerge { token_ids } => try_merge(env, info, deps, token_ids),
+        ExecuteMsg::MsgRedelegate {
+            token_id,
+            validators,
+        } => try_redelegate(env, info, deps, token_id, validators),
         ExecuteMsg::MsgClaimRewards { token_id } => try_claim_rewards(env, info, deps, token_id),
         ExecuteMsg::MsgRedeemBond { token_id } => try_redeem_bond(env, info, deps, token_id),
+        ExecuteMsg::SetAutoCompound { token_id, enabled } => {
+            try_set_auto_compound(info, deps, token_id, enabled)
+        }
+        ExecuteMsg::Compound { token_ids } => try_compound(env, deps, token_ids),
+        ExecuteMsg::Refresh { token_ids } => try_refresh(env, deps, token_ids),
+        ExecuteMsg::UpdateConfig(msg) => try_update_config(info, deps, msg),
+        ExecuteMsg::UpdateOwnership(action) => try_update_ownership(env, info, deps, action),
+        ExecuteMsg::UpdatePauseFlags(flags) => try_update_pause_flags(info, deps, flags),
+        ExecuteMsg::MigrateCollection { new_code_id, msg } => {
+            try_migrate_collection(info, deps, new_code_id, msg)
+        }
+        ExecuteMsg::MigrateDelegations {
+            limit,
+            redelegations,
+        } => try_migrate_delegations(env, info, deps, limit, redelegations),
     }
 }
 
-fn try_delegate(env: Env, info: MessageInfo, deps: DepsMut) -> Result<Response, ContractError> {
-    let cfg = CFG.load(deps.storage)?;
-    let validators = query::all_validators(deps.querier)?;
-    if validators.is_empty() {
-        return Err(ContractError::NoValidatorsFound {});
+// Paused operations fail before doing anything, the rest of the
+// operations (e.g. MsgRedeemBond) are always allowed
+fn assert_not_paused(storage: &dyn Storage, msg: &ExecuteMsg) -> Result<(), ContractError> {
+    let flags = PAUSE_FLAGS.may_load(storage)?.unwrap_or_default();
+    let paused = match msg {
+        ExecuteMsg::MsgDelegate { .. } if flags.deposits => "deposits",
+        ExecuteMsg::MsgRedelegate { .. } if flags.redelegations => "redelegations",
+        ExecuteMsg::MsgStartUnbonding { .. } | ExecuteMsg::MsgStartPartialUnbonding { .. }
+            if flags.unbondings =>
+        {
+            "unbondings"
+        }
+        ExecuteMsg::MsgClaimRewards { .. } | ExecuteMsg::Compound { .. } if flags.claims => {
+            "claims"
+        }
+        ExecuteMsg::Compound { .. } if flags.deposits => "deposits",
+        _ => return Ok(()),
+    };
+
+    Err(ContractError::Paused(paused.to_string()))
+}
+
+// The shares of the NFTs not migrated yet are missing from the reward
+// indexes, so only the owner operations are allowed until every NFT
+// has been migrated from the versions before 0.2.0. Refreshing and
+// redeeming do not depend on the shares so they are also allowed for
+// the NFTs already migrated, the same as MsgRedeemBond while paused.
+fn assert_delegations_migrated(
+    storage: &dyn Storage,
+    msg: &ExecuteMsg,
+) -> Result<(), ContractError> {
+    if DELEGATIONS_MIGRATION.may_load(storage)?.is_none() {
+        return Ok(());
     }
-    if info.funds.is_empty() {
-        return Err(ContractError::NoFundsReceived {});
+    let allowed = match msg {
+        ExecuteMsg::UpdateConfig(_)
+        | ExecuteMsg::UpdateOwnership(_)
+        | ExecuteMsg::UpdatePauseFlags(_)
+        | ExecuteMsg::MigrateCollection { .. }
+        | ExecuteMsg::MigrateDelegations { .. } => true,
+        ExecuteMsg::MsgRedeemBond { token_id } => DELEGATIONS.has(storage, token_id),
+        ExecuteMsg::Refresh { token_ids } => token_ids
+            .iter()
+            .all(|token_id| DELEGATIONS.has(storage, token_id)),
+        _ => false,
+    };
+
+    match allowed {
+        true => Ok(()),
+        false => Err(ContractError::DelegationsMigrationPending {}),
     }
+}
+
+fn try_update_pause_flags(
+    info: MessageInfo,
+    deps: DepsMut,
+    flags: PauseFlags,
+) -> Result<Response, ContractError> {
+    cw_ownable::assert_owner(deps.storage, &info.sender)?;
+    let old = PAUSE_FLAGS.may_load(deps.storage)?.unwrap_or_default();
+    PAUSE_FLAGS.save(deps.storage, &flags)?;
 
-    let msg_delegate = match generate_delegate_msg(info.funds, env.clone(), validators) {
-        Ok(msg) => msg,
-        Err(e) => return Err(e),
+    Ok(Response::new()
+        .add_attribute("action", "update_pause_flags")
+        .add_attribute("sender", info.sender.to_string())
+        .add_event(
+            Event::new("update_pause_flags")
+                .add_attribute("old_value", String::from_utf8_lossy(&to_vec(&old)?))
+                .add_attribute("new_value", String::from_utf8_lossy(&to_vec(&flags)?)),
+        ))
+}
+
+// The hub is the admin of the NFT contract so it is
+// the only one that can roll out new NFT contract code
+fn try_migrate_collection(
+    info: MessageInfo,
+    deps: DepsMut,
+    new_code_id: u64,
+    msg: Binary,
+) -> Result<Response, ContractError> {
+    cw_ownable::assert_owner(deps.storage, &info.sender)?;
+    let cfg = CFG.load(deps.storage)?;
+    let nft_contract_addr = match cfg.nft_contract_addr {
+        Some(addr) => String::from(addr),
+        None => return Err(ContractError::NoNftContractAddress {}),
     };
-    let msg_mint = generate_mint_msg(
-        info.sender.clone().into(),
-        env.block.time,
-        cfg.minted_nfts.to_string(),
-        msg_delegate.clone(),
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate_collection")
+        .add_attribute("sender", info.sender.to_string())
+        .add_attribute("nft_contract_address", nft_contract_addr.clone())
+        .add_attribute("new_code_id", new_code_id.to_string())
+        .add_submessage(SubMsg::reply_always(
+            WasmMsg::Migrate {
+                contract_addr: nft_contract_addr,
+                new_code_id,
+                msg,
+            },
+            MIGRATE_COLLECTION_REPLY_ID,
+        )))
+}
+
+fn try_migrate_delegations(
+    env: Env,
+    info: MessageInfo,
+    mut deps: DepsMut,
+    limit: Option<u32>,
+    redelegations: Option<Vec<RedelegationDestination>>,
+) -> Result<Response, ContractError> {
+    cw_ownable::assert_owner(deps.storage, &info.sender)?;
+    let migrated = migrate::migrate_delegations_batch(
+        deps.branch(),
+        &env,
+        limit.unwrap_or(DEFAULT_MIGRATION_LIMIT),
+        &redelegations.unwrap_or_default(),
     )?;
+    let unresolved = DELEGATIONS_MIGRATION
+        .may_load(deps.storage)?
+        .map(|migration| migration.unresolved);
 
-    let msg: Vec<CosmosMsg> = msg_delegate
-        .iter()
-        .map(|msg| CosmosMsg::Stargate {
-            type_url: "/alliance.alliance.MsgDelegate".to_string(),
-            value: Binary::from(msg.encode_to_vec()),
-        })
-        .collect();
+    Ok(Response::new()
+        .add_attribute("action", "migrate_delegations")
+        .add_attribute("sender", info.sender.to_string())
+        .add_attribute("migrated_nfts", migrated.to_string())
+        .add_attribute(
+            "unresolved_nfts",
+            unresolved.clone().unwrap_or_default().join(","),
+        )
+        .add_attribute("completed", unresolved.is_none().to_string()))
+}
+
+fn try_update_config(
+    info: MessageInfo,
+    deps: DepsMut,
+    msg: UpdateConfigMsg,
+) -> Result<Response, ContractError> {
+    cw_ownable::assert_owner(deps.storage, &info.sender)?;
+    let mut cfg = CFG.load(deps.storage)?;
+
+    // Each updated field emits an event with its old and new values
+    let mut events = vec![];
+    if let Some(unbonding_seconds) = msg.unbonding_seconds {
+        events.push(config_event(
+            "unbonding_seconds",
+            &cfg.unbonding_seconds,
+            &unbonding_seconds,
+        )?);
+        cfg.unbonding_seconds = unbonding_seconds;
+    }
+    if let Some(addr) = msg.nft_contract_addr {
+        let addr = Some(deps.api.addr_validate(&addr)?);
+        events.push(config_event(
+            "nft_contract_addr",
+            &cfg.nft_contract_addr,
+            &addr,
+        )?);
+        cfg.nft_contract_addr = addr;
+    }
+    if let Some(validator_strategy) = msg.validator_strategy {
+        let validator_strategy = validators::validate_strategy(validator_strategy)?;
+        events.push(config_event(
+            "validator_strategy",
+            &cfg.validator_strategy,
+            &validator_strategy,
+        )?);
+        cfg.validator_strategy = validator_strategy;
+    }
+    if let Some(split_mode) = msg.split_mode {
+        events.push(config_event("split_mode", &cfg.split_mode, &split_mode)?);
+        cfg.split_mode = split_mode;
+    }
+    if let Some(max_validators) = msg.max_validators_per_denom {
+        let max_validators = max_validators.max(1);
+        events.push(config_event(
+            "max_validators_per_denom",
+            &cfg.max_validators_per_denom,
+            &max_validators,
+        )?);
+        cfg.max_validators_per_denom = max_validators;
+    }
+    if let Some(allowlist) = msg.validators_allowlist {
+        let allowlist = validators::validate_allowlist(Some(allowlist))?;
+        events.push(config_event(
+            "validators_allowlist",
+            &cfg.validators_allowlist,
+            &allowlist,
+        )?);
+        cfg.validators_allowlist = allowlist;
+    }
+    if let Some(refund) = msg.refund_unsupported_denoms {
+        events.push(config_event(
+            "refund_unsupported_denoms",
+            &cfg.refund_unsupported_denoms,
+            &refund,
+        )?);
+        cfg.refund_unsupported_denoms = refund;
+    }
+    if let Some(redeemed_nft) = msg.redeemed_nft {
+        events.push(config_event(
+            "redeemed_nft",
+            &cfg.redeemed_nft,
+            &redeemed_nft,
+        )?);
+        cfg.redeemed_nft = redeemed_nft;
+    }
+    if let Some(protocol_fee) = msg.protocol_fee {
+        let protocol_fee = fees::validate_protocol_fee(deps.api, Some(protocol_fee))?;
+        events.push(config_event(
+            "protocol_fee",
+            &cfg.protocol_fee,
+            &protocol_fee,
+        )?);
+        cfg.protocol_fee = protocol_fee;
+    }
+    CFG.save(deps.storage, &cfg)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_config")
+        .add_attribute("sender", info.sender.to_string())
+        .add_events(events))
+}
+
+// Event with the old and new values of the config field as JSON
+fn config_event<T: Serialize>(field: &str, old: &T, new: &T) -> Result<Event, ContractError> {
+    Ok(Event::new("update_config")
+        .add_attribute("field", field)
+        .add_attribute("old_value", String::from_utf8_lossy(&to_vec(old)?))
+        .add_attribute("new_value", String::from_utf8_lossy(&to_vec(new)?)))
+}
+
+fn try_update_ownership(
+    env: Env,
+    info: MessageInfo,
+    deps: DepsMut,
+    action: Action,
+) -> Result<Response, ContractError> {
+    let old = cw_ownable::get_ownership(deps.storage)?;
+    let new = cw_ownable::update_ownership(deps, &env.block, &info.sender, action)?;
+    let event = Event::new("update_ownership")
+        .add_attribute("old_owner", addr_or_none(&old.owner))
+        .add_attribute("new_owner", addr_or_none(&new.owner))
+        .add_attribute("old_pending_owner", addr_or_none(&old.pending_owner))
+        .add_attribute("new_pending_owner", addr_or_none(&new.pending_owner));
+
+    Ok(Response::new()
+        .add_attribute("action", "update_ownership")
+        .add_attribute("sender", info.sender.to_string())
+        .add_event(event))
+}
+
+fn addr_or_none(addr: &Option<Addr>) -> String {
+    match addr {
+        Some(addr) => addr.to_string(),
+        None => String::from("none"),
+    }
+}
+
+fn try_delegate(
+    env: Env,
+    info: MessageInfo,
+    deps: DepsMut,
+    validators: Option<Vec<ValidatorWeight>>,
+) -> Result<Response, ContractError> {
+    let cfg = CFG.load(deps.storage)?;
+    let DelegatePlan {
+        selector,
+        mut ratios,
+        msg_delegate,
+        records,
+        refunds,
+        deposit_fees,
+    } = plan_delegate(deps.as_ref(), &env, &info.funds, validators)?;
+    selector.save(deps.storage)?;
+
+    let token_id = cfg.minted_nfts.to_string();
+    DELEGATIONS.save(deps.storage, &token_id, &records)?;
+    let msg_mint = mint_nft_msg(
+        info.sender.to_string(),
+        token_id.clone(),
+        &ratios.to_amounts(deps.as_ref(), &records)?,
+    );
+
+    // The replies register the shares of the new delegations in the
+    // reward indexes so the NFT only earns the rewards from now on
+    let (msgs, moves) = delegate_submsgs(&token_id, &msg_delegate, &records)?;
+    save_paid_rewards_tmp(deps, &env, &info.sender, moves)?;
 
     let nft_contract_addr = match cfg.nft_contract_addr {
         Some(addr) => String::from(addr),
         None => return Err(ContractError::NoNftContractAddress {}),
     };
-    Ok(Response::new()
+    let res = Response::new()
         .add_attribute("action", "delegate")
         .add_attribute("sender", info.sender.to_string())
         .add_submessage(SubMsg::reply_always(
@@ -95,99 +403,229 @@ fn try_delegate(env: Env, info: MessageInfo, deps: DepsMut) -> Result<Response,
             },
             MINT_NFT_REPLY_ID,
         ))
-        .add_messages(msg))
+        .add_submessages(msgs);
+
+    let res = fees::add_fee_msg(res, "deposit_fee", cfg.protocol_fee.as_ref(), deposit_fees);
+    Ok(add_refund_msg(res, info.sender.to_string(), refunds))
+}
+
+// MsgDelegate submessages which replies add the shares of the
+// records, built from the same messages, to the reward indexes
+fn delegate_submsgs(
+    token_id: &str,
+    msg_delegate: &[MsgDelegate],
+    records: &[DelegationRecord],
+) -> Result<(Vec<SubMsg>, Vec<SharesMove>), ContractError> {
+    let mut msgs = vec![];
+    let mut moves = vec![];
+    for (msg, record) in msg_delegate.iter().zip(records.iter()) {
+        let coin = native_coin(msg.amount.as_ref())?;
+        msgs.push(SubMsg::reply_on_success(
+            CosmosMsg::Stargate {
+                type_url: "/alliance.alliance.MsgDelegate".to_string(),
+                value: Binary::from(msg.encode_to_vec()),
+            },
+            PAID_REWARDS_REPLY_ID,
+        ));
+        moves.push(SharesMove {
+            validator: record.validator.clone(),
+            denom: coin.denom.clone(),
+            spent: vec![coin],
+            removed: vec![],
+            added: vec![(
+                token_id.to_string(),
+                record.validator.clone(),
+                record.coin.clone(),
+            )],
+        });
+    }
+
+    Ok((msgs, moves))
+}
+
+// Store the balance of the hub before the delegation submessages
+// and the shares they move, so each reply credits the rewards paid
+// by x/alliance to the reward indexes before moving the shares
+fn save_paid_rewards_tmp(
+    deps: DepsMut,
+    env: &Env,
+    owner: &Addr,
+    moves: Vec<SharesMove>,
+) -> Result<(), ContractError> {
+    let balances = deps.querier.query_all_balances(&env.contract.address)?;
+    PAID_REWARDS_TMP.save(
+        deps.storage,
+        &PaidRewardsTmp {
+            owner: owner.clone(),
+            balances,
+            moves,
+        },
+    )?;
+
+    Ok(())
+}
+
+// Delegations of a new NFT computed from the deposited funds,
+// shared by MsgDelegate and the SimulateDelegate query
+pub struct DelegatePlan {
+    pub selector: ValidatorSelector,
+    pub ratios: ShareRatios,
+    pub msg_delegate: Vec<MsgDelegate>,
+    pub records: Vec<DelegationRecord>,
+    pub refunds: Vec<Coin>,
+    pub deposit_fees: Vec<Coin>,
+}
+
+pub fn plan_delegate(
+    deps: Deps,
+    env: &Env,
+    funds: &[Coin],
+    validators: Option<Vec<ValidatorWeight>>,
+) -> Result<DelegatePlan, ContractError> {
+    let cfg = CFG.load(deps.storage)?;
+    let mut selector = ValidatorSelector::load_with_user_validators(deps, env, validators)?;
+    if funds.is_empty() {
+        return Err(ContractError::NoFundsReceived {});
+    }
+    let (funds, refunds) = alliance_funds(deps, cfg.refund_unsupported_denoms, funds)?;
+    let (funds, deposit_fees) = fees::take_deposit_fee(cfg.protocol_fee.as_ref(), funds);
+
+    let msg_delegate = generate_delegate_msg(funds, env.clone(), &mut selector)?;
+    let mut ratios = ShareRatios::new(&env.contract.address);
+    let records = delegated_records(deps, &mut ratios, &msg_delegate, env.block.time)?;
+
+    Ok(DelegatePlan {
+        selector,
+        ratios,
+        msg_delegate,
+        records,
+        refunds,
+        deposit_fees,
+    })
+}
+
+// Split the funds between the denoms whitelisted by the x/alliance module
+// and the unsupported ones, which are refunded when the hub allows it and
+// the deposit still has some alliance asset to delegate
+fn alliance_funds(
+    deps: Deps,
+    refund_unsupported_denoms: bool,
+    funds: &[Coin],
+) -> Result<(Vec<Coin>, Vec<Coin>), ContractError> {
+    let denoms = query::alliance_denoms(deps.querier)?;
+    let (supported, unsupported): (Vec<Coin>, Vec<Coin>) = funds
+        .iter()
+        .cloned()
+        .partition(|coin| denoms.contains(&coin.denom));
+
+    match unsupported.first() {
+        Some(coin) if supported.is_empty() || !refund_unsupported_denoms => {
+            Err(ContractError::UnsupportedDenom(coin.denom.clone()))
+        }
+        _ => Ok((supported, unsupported)),
+    }
+}
+
+fn add_refund_msg(res: Response, sender: String, refunds: Vec<Coin>) -> Response {
+    if refunds.is_empty() {
+        return res;
+    }
+
+    res.add_message(BankMsg::Send {
+        to_address: sender,
+        amount: refunds,
+    })
 }
 
 fn generate_delegate_msg(
     funds: Vec<Coin>,
     env: Env,
-    validators: Vec<Validator>,
+    selector: &mut ValidatorSelector,
 ) -> Result<Vec<MsgDelegate>, ContractError> {
-    let mut vals_len = validators.len() as u64;
-    funds
+    let msgs = funds
         .iter()
         .map(|coin| {
-            let pseudorandom_index = get_pseudorandom(env.block.height, vals_len);
-            let val = &validators[pseudorandom_index as usize];
             if coin.amount == Uint128::new(0) {
                 return Err(ContractError::NoFundsReceived {});
             }
-            let msg_delegate = MsgDelegate {
-                delegator_address: env.contract.address.to_string(),
-                validator_address: val.address.to_string(),
-                amount: Some(CosmosNativeCoin {
-                    denom: coin.denom.to_string(),
-                    amount: coin.amount.to_string(),
-                }),
-            };
 
-            // Remove 1 of the index to generate a new
-            // pseudorandom index in the next iteration
-            if vals_len > 1 {
-                vals_len -= 1
-            }
+            // Each coin can be divided between several
+            // validators depending on the split mode
+            let msgs = selector
+                .split(coin)?
+                .into_iter()
+                .map(|(validator, coin)| MsgDelegate {
+                    delegator_address: env.contract.address.to_string(),
+                    validator_address: validator,
+                    amount: Some(CosmosNativeCoin {
+                        denom: coin.denom.to_string(),
+                        amount: coin.amount.to_string(),
+                    }),
+                })
+                .collect::<Vec<MsgDelegate>>();
 
-            Ok(msg_delegate)
+            Ok(msgs)
         })
-        .collect::<Result<Vec<MsgDelegate>, ContractError>>()
+        .collect::<Result<Vec<Vec<MsgDelegate>>, ContractError>>()?;
+
+    Ok(msgs.concat())
 }
 
-fn generate_mint_msg(
-    sender: String,
+// Record the shares of the delegations executed by the x/alliance
+// messages, which are worth the delegated amounts
+fn delegated_records(
+    deps: Deps,
+    ratios: &mut ShareRatios,
+    msg_delegate: &[MsgDelegate],
     block_time: Timestamp,
-    token_id: String,
-    msg_delegate: Vec<MsgDelegate>,
-) -> Result<Cw721ExecuteMsg, ContractError> {
-    let attributes = msg_delegate
+) -> Result<Vec<DelegationRecord>, ContractError> {
+    msg_delegate
         .iter()
         .map(|msg| {
-            let unwrapped_coin = match msg.amount.as_ref() {
-                Some(coin) => coin,
-                None => return Err(ContractError::NoFundsReceived {}),
-            };
-
-            let value = unwrapped_coin
-                .amount
-                .clone()
-                .add(DEFAULT_DELIMITER)
-                .add(&unwrapped_coin.denom);
-
-            Ok(CW721Trait {
-                display_type: DisplayType::Delegated.to_string(),
-                trait_type: msg.validator_address.to_string(),
-                timestamp: block_time,
-                value,
+            let coin = native_coin(msg.amount.as_ref())?;
+            Ok(DelegationRecord {
+                validator: msg.validator_address.clone(),
+                coin: ratios.to_shares(deps, &msg.validator_address, &coin)?,
+                status: DisplayType::Delegated,
+                completion_time: block_time,
             })
         })
-        .collect::<Result<Vec<CW721Trait>, ContractError>>()?;
+        .collect()
+}
 
-    let msg = Cw721ExecuteMsg::Mint {
+fn mint_nft_msg(owner: String, token_id: String, records: &[DelegationRecord]) -> Cw721ExecuteMsg {
+    Cw721ExecuteMsg::Mint {
         token_id: token_id.clone(),
-        owner: sender,
+        owner,
         token_uri: None,
-        extension: Some(CW721Metadata {
-            name: Some(String::from("Alliance NFT #").add(&token_id)),
-            attributes: Some(attributes),
-            ..Default::default()
-        }),
-    };
-
-    Ok(msg)
+        extension: Some(minted_nft_metadata(&token_id, records)),
+    }
 }
 
-fn get_pseudorandom(block_height: u64, max: u64) -> u64 {
-    let seed: u64 = block_height % (max + 1);
-
-    seed % max
+pub fn minted_nft_metadata(token_id: &str, records: &[DelegationRecord]) -> CW721Metadata {
+    CW721Metadata {
+        name: Some(String::from("Alliance NFT #").add(token_id)),
+        attributes: Some(delegation_traits(records)),
+        ..Default::default()
+    }
 }
 
-fn try_start_unbonding(
+fn try_top_up(
     env: Env,
     info: MessageInfo,
     deps: DepsMut,
     token_id: String,
+    validators: Option<Vec<ValidatorWeight>>,
 ) -> Result<Response, ContractError> {
     let cfg = CFG.load(deps.storage)?;
+    let mut selector =
+        ValidatorSelector::load_with_user_validators(deps.as_ref(), &env, validators)?;
+    if info.funds.is_empty() {
+        return Err(ContractError::NoFundsReceived {});
+    }
+    let (funds, refunds) =
+        alliance_funds(deps.as_ref(), cfg.refund_unsupported_denoms, &info.funds)?;
+    let (funds, deposit_fees) = fees::take_deposit_fee(cfg.protocol_fee.as_ref(), funds);
     let nft_contract_addr = match cfg.nft_contract_addr {
         Some(addr) => String::from(addr),
         None => return Err(ContractError::NoNftContractAddress {}),
@@ -199,46 +637,205 @@ fn try_start_unbonding(
             info.sender.to_string(),
         ));
     }
-    let attrs = query_res
-        .info
-        .extension
-        .attributes
-        .clone()
-        .unwrap_or_default();
-    if attrs.is_empty() {
-        return Err(ContractError::NoDelegationsFound(token_id));
+    let mut records = load_delegations(deps.storage, &token_id)?;
+    status::refresh(&mut records, env.block.time)?;
+    if records
+        .iter()
+        .any(|record| !is_undelegated(record) && record.status != DisplayType::Delegated)
+        || records.iter().all(is_undelegated)
+    {
+        return Err(ContractError::TopUpImpossible(token_id));
     }
 
-    let msgs = attrs
-        .iter()
-        .map(|attr| {
-            let coin = attr.value.split(DEFAULT_DELIMITER).collect::<Vec<&str>>();
-            let msg = MsgUndelegate {
+    // Delegate the denoms the NFT already holds to the same validator
+    // so the new funds are merged in the existing record, unless the
+    // user has chosen the validators to delegate to
+    let user_validators = selector.user_validators();
+    let mut msg_delegate = vec![];
+    for coin in funds.iter() {
+        match records
+            .iter()
+            .find(|record| !is_undelegated(record) && record.coin.denom == coin.denom)
+            .filter(|_| user_validators.is_none())
+        {
+            Some(record) if !coin.amount.is_zero() => msg_delegate.push(MsgDelegate {
                 delegator_address: env.contract.address.to_string(),
-                validator_address: attr.trait_type.clone(),
+                validator_address: record.validator.clone(),
                 amount: Some(CosmosNativeCoin {
-                    amount: coin[0].to_string(),
-                    denom: coin[1].to_string(),
+                    denom: coin.denom.to_string(),
+                    amount: coin.amount.to_string(),
                 }),
-            }
-            .encode_to_vec();
+            }),
+            _ => msg_delegate.extend(generate_delegate_msg(
+                vec![coin.clone()],
+                env.clone(),
+                &mut selector,
+            )?),
+        }
+    }
+    selector.save(deps.storage)?;
+    let mut ratios = ShareRatios::new(&env.contract.address);
+    let deposits = delegated_records(deps.as_ref(), &mut ratios, &msg_delegate, env.block.time)?;
+    for record in deposits.iter() {
+        merge_delegated_record(&mut records, record.clone());
+    }
+    DELEGATIONS.save(deps.storage, &token_id, &records)?;
+    let msg_update_nft = generate_update_nft_msg(
+        query_res.info.extension,
+        &ratios.to_amounts(deps.as_ref(), &records)?,
+        token_id.clone(),
+    );
+
+    // The replies register the shares of the new delegations in the
+    // reward indexes settling the rewards earned by the NFT until now
+    let (msgs, moves) = delegate_submsgs(&token_id, &msg_delegate, &deposits)?;
+    save_paid_rewards_tmp(deps, &env, &info.sender, moves)?;
+
+    let res = Response::new()
+        .add_attribute("action", "delegate")
+        .add_attribute("sender", info.sender.to_string())
+        .add_attribute("token_id", token_id)
+        .add_submessage(SubMsg::reply_always(
+            WasmMsg::Execute {
+                contract_addr: nft_contract_addr,
+                msg: to_binary(&msg_update_nft)?,
+                funds: vec![],
+            },
+            TOP_UP_NFT_REPLY_ID,
+        ))
+        .add_submessages(msgs);
+
+    let res = fees::add_fee_msg(res, "deposit_fee", cfg.protocol_fee.as_ref(), deposit_fees);
+    Ok(add_refund_msg(res, info.sender.to_string(), refunds))
+}
+
+// Sum the record to the delegated record of the same
+// validator and denom or append it when there is none
+pub fn merge_delegated_record(records: &mut Vec<DelegationRecord>, record: DelegationRecord) {
+    match records.iter_mut().find(|prev| {
+        prev.status == DisplayType::Delegated
+            && prev.validator == record.validator
+            && prev.coin.denom == record.coin.denom
+    }) {
+        Some(prev) => prev.coin.amount += record.coin.amount,
+        None => records.push(record),
+    }
+}
+
+fn try_start_unbonding(
+    env: Env,
+    info: MessageInfo,
+    mut deps: DepsMut,
+    token_id: String,
+    targets: Option<Vec<DelegationTarget>>,
+) -> Result<Response, ContractError> {
+    let cfg = CFG.load(deps.storage)?;
+    let nft_contract_addr = match cfg.nft_contract_addr {
+        Some(addr) => String::from(addr),
+        None => return Err(ContractError::NoNftContractAddress {}),
+    };
+    let query_res = query::all_nft_info(deps.querier, token_id.clone(), nft_contract_addr.clone())?;
+    if query_res.access.owner != info.sender {
+        return Err(ContractError::UnauthorizedNFTOwnere(
+            query_res.access.owner,
+            info.sender.to_string(),
+        ));
+    }
+    let mut records = load_delegations(deps.storage, &token_id)?;
+    status::refresh(&mut records, env.block.time)?;
+    for record in records.iter().filter(|record| !is_undelegated(record)) {
+        status::transition(
+            &record.status,
+            &DisplayType::Unbonding,
+            record.completion_time,
+            env.block.time,
+        )?;
+    }
+
+    // Without targets all the delegated records are unbonded
+    let mut ratios = ShareRatios::new(&env.contract.address);
+    let targets = match targets {
+        Some(targets) => targets,
+        None => ratios
+            .to_amounts(deps.as_ref(), &records)?
+            .into_iter()
+            .filter(|record| !is_undelegated(record))
+            .map(|record| DelegationTarget {
+                validator: Some(record.validator),
+                amount: record.coin,
+            })
+            .collect(),
+    };
+    if targets.is_empty() {
+        return Err(ContractError::UnbondingImpossible(token_id));
+    }
+    let unbondings =
+        take_delegated_shares(deps.as_ref(), &mut ratios, &mut records, targets, &token_id)?;
 
+    // Undelegate the amount the shares are worth so the
+    // hub never undelegates more than it holds
+    let (unbonding_timestamp, fallback_seconds) = estimate_completion_time(deps.as_ref(), &env)?;
+    let mut msgs = vec![];
+    let mut completion_times = vec![];
+    let mut moves = vec![];
+    for (validator, shares, slashed_coin) in unbondings.into_iter() {
+        let msg = MsgUndelegate {
+            delegator_address: env.contract.address.to_string(),
+            validator_address: validator.clone(),
+            amount: Some(CosmosNativeCoin {
+                amount: slashed_coin.amount.to_string(),
+                denom: slashed_coin.denom.to_string(),
+            }),
+        }
+        .encode_to_vec();
+        msgs.push(SubMsg::reply_on_success(
             CosmosMsg::Stargate {
                 type_url: "/alliance.alliance.MsgUndelegate".to_string(),
                 value: Binary::from(msg),
-            }
-        })
-        .collect::<Vec<CosmosMsg>>();
-    let msg_update_nft = generate_unbonding_nft_msg(
+            },
+            COMPLETION_TIME_REPLY_ID,
+        ));
+        completion_times.push(CompletionTimeTmp {
+            token_id: token_id.clone(),
+            validator: validator.clone(),
+            denom: slashed_coin.denom.clone(),
+            status: DisplayType::Unbonding,
+            completion_time: unbonding_timestamp,
+        });
+        unbondings::add_unbonding(deps.storage, &slashed_coin, unbonding_timestamp)?;
+
+        // The reply removes the shares from the reward index
+        // settling the rewards earned by the NFT until now
+        moves.push(SharesMove {
+            validator: validator.clone(),
+            denom: slashed_coin.denom.clone(),
+            spent: vec![],
+            removed: vec![(token_id.clone(), validator.clone(), shares)],
+            added: vec![],
+        });
+
+        records.push(DelegationRecord {
+            validator,
+            coin: slashed_coin,
+            status: DisplayType::Unbonding,
+            completion_time: unbonding_timestamp,
+        });
+    }
+    DELEGATIONS.save(deps.storage, &token_id, &records)?;
+    COMPLETION_TIMES_TMP.save(deps.storage, &completion_times)?;
+    save_paid_rewards_tmp(deps.branch(), &env, &info.sender, moves)?;
+    let msg_update_nft = generate_update_nft_msg(
         query_res.info.extension,
-        cfg.unbonding_seconds,
-        env.block.time,
-        token_id,
-    )?;
+        &ratios.to_amounts(deps.as_ref(), &records)?,
+        token_id.clone(),
+    );
 
-    Ok(Response::new()
+    let res = Response::new()
         .add_attribute("action", "start_unbonding")
         .add_attribute("sender", info.sender.to_string())
+        .add_attributes(
+            fallback_seconds.map(|seconds| ("unbonding_seconds_fallback", seconds.to_string())),
+        )
         .add_submessage(SubMsg::reply_always(
             WasmMsg::Execute {
                 contract_addr: nft_contract_addr,
@@ -247,61 +844,236 @@ fn try_start_unbonding(
             },
             UNBONDING_NFT_REPLY_ID,
         ))
-        .add_messages(msgs))
+        .add_submessages(msgs);
+
+    Ok(res)
 }
 
-fn generate_unbonding_nft_msg(
-    query_res: CW721Metadata,
-    unbonding_seconds: u64,
-    block_time: Timestamp,
-    token_id: String,
-) -> Result<Cw721ExecuteMsg, ContractError> {
-    let unbonding_timestamp = block_time.plus_seconds(unbonding_seconds);
-    let attrs = match query_res.attributes {
-        Some(attrs) => attrs,
-        None => return Err(ContractError::NoDelegationsFound(token_id)),
-    };
+// Completion time of the undelegations and redelegations executed
+// in this block using the current unbonding time of the chain, the
+// replies replace it with the completion time reported by x/alliance.
+// The unbonding seconds of the config are used, and returned so they
+// are reported in the attributes, when the chain does not support the
+// staking params query or they cannot be parsed.
+fn estimate_completion_time(
+    deps: Deps,
+    env: &Env,
+) -> Result<(Timestamp, Option<u64>), ContractError> {
+    match query::unbonding_seconds(deps.querier)? {
+        Some(unbonding_seconds) => Ok((env.block.time.plus_seconds(unbonding_seconds), None)),
+        None => {
+            let unbonding_seconds = CFG.load(deps.storage)?.unbonding_seconds;
+            Ok((
+                env.block.time.plus_seconds(unbonding_seconds),
+                Some(unbonding_seconds),
+            ))
+        }
+    }
+}
 
-    let parsed_attrs = attrs
-        .iter()
-        .map(|attr| {
-            if (attr.display_type == DisplayType::Redelegating.to_string()
-                && attr.timestamp < block_time)
-                || attr.display_type != DisplayType::Delegated.to_string()
-            {
-                return Err(ContractError::UnbondingImpossible(token_id.clone()));
+// Take the targets from the delegated records returning
+// the recorded coins taken from each validator
+fn take_delegated_records(
+    records: &mut Vec<DelegationRecord>,
+    targets: Vec<DelegationTarget>,
+    token_id: &str,
+) -> Result<Vec<(String, Coin)>, ContractError> {
+    let mut taken_coins: Vec<(String, Coin)> = vec![];
+
+    for target in targets {
+        if target.amount.amount.is_zero() {
+            return Err(ContractError::NoFundsReceived {});
+        }
+        let mut remaining = target.amount.amount;
+
+        for record in records.iter_mut() {
+            if remaining.is_zero() {
+                break;
+            }
+            if record.status != DisplayType::Delegated || record.coin.denom != target.amount.denom {
+                continue;
+            }
+            if let Some(validator) = &target.validator {
+                if &record.validator != validator {
+                    continue;
+                }
             }
 
-            Ok(CW721Trait {
-                display_type: DisplayType::Unbonding.to_string(),
-                timestamp: unbonding_timestamp,
-                ..attr.clone()
-            })
+            let taken = record.coin.amount.min(remaining);
+            remaining -= taken;
+            record.coin.amount -= taken;
+
+            match taken_coins.iter_mut().find(|(validator, taken_coin)| {
+                validator == &record.validator && taken_coin.denom == record.coin.denom
+            }) {
+                Some((_, taken_coin)) => taken_coin.amount += taken,
+                None => taken_coins.push((
+                    record.validator.clone(),
+                    Coin {
+                        amount: taken,
+                        denom: record.coin.denom.clone(),
+                    },
+                )),
+            }
+        }
+
+        if !remaining.is_zero() {
+            return Err(ContractError::InsufficientDelegation(
+                target.amount.to_string(),
+                token_id.to_string(),
+            ));
+        }
+    }
+
+    // Drop the delegated records that have been fully taken
+    records
+        .retain(|record| record.status != DisplayType::Delegated || !record.coin.amount.is_zero());
+
+    Ok(taken_coins)
+}
+
+// Take the targets, which are amounts as displayed in the NFT
+// metadata, from the shares of the delegated records returning
+// the shares taken from each validator and the amount they are
+// worth. Taking the whole amount of a record takes all its shares.
+fn take_delegated_shares(
+    deps: Deps,
+    ratios: &mut ShareRatios,
+    records: &mut Vec<DelegationRecord>,
+    targets: Vec<DelegationTarget>,
+    token_id: &str,
+) -> Result<Vec<(String, Coin, Coin)>, ContractError> {
+    let mut amounts = ratios.to_amounts(deps, records)?;
+    let taken_amounts = take_delegated_records(&mut amounts, targets, token_id)?;
+
+    let mut taken = vec![];
+    for (validator, amount) in taken_amounts {
+        let is_record = |record: &DelegationRecord| {
+            record.status == DisplayType::Delegated
+                && record.validator == validator
+                && record.coin.denom == amount.denom
+        };
+        let record = match records.iter_mut().find(|record| is_record(record)) {
+            Some(record) => record,
+            None => {
+                return Err(ContractError::InsufficientDelegation(
+                    amount.to_string(),
+                    token_id.to_string(),
+                ))
+            }
+        };
+        let shares = match amounts.iter().any(is_record) {
+            true => ratios
+                .to_shares(deps, &validator, &amount)?
+                .amount
+                .min(record.coin.amount),
+            false => record.coin.amount,
+        };
+        record.coin.amount -= shares;
+
+        let shares = Coin::new(shares.u128(), &amount.denom);
+        let amount = ratios.to_amount(deps, &validator, &shares)?;
+        taken.push((validator, shares, amount));
+    }
+    records
+        .retain(|record| record.status != DisplayType::Delegated || !record.coin.amount.is_zero());
+
+    Ok(taken)
+}
+
+// Parse the coin of a x/alliance message
+fn native_coin(coin: Option<&CosmosNativeCoin>) -> Result<Coin, ContractError> {
+    match coin {
+        Some(coin) => Ok(Coin::new(
+            Uint128::from_str(&coin.amount)?.into(),
+            &coin.denom,
+        )),
+        None => Err(ContractError::NoFundsReceived {}),
+    }
+}
+
+// Format the coin to be displayed in the NFT trait value
+fn trait_value(coin: &Coin) -> String {
+    coin.amount
+        .to_string()
+        .add(DEFAULT_DELIMITER)
+        .add(&coin.denom)
+}
+
+// Load the delegations recorded by the hub for the NFT,
+// the NFT metadata is only used to display them
+fn load_delegations(
+    storage: &dyn Storage,
+    token_id: &str,
+) -> Result<Vec<DelegationRecord>, ContractError> {
+    let records = DELEGATIONS.may_load(storage, token_id)?.unwrap_or_default();
+    if records.is_empty() {
+        return Err(ContractError::NoDelegationsFound(token_id.to_string()));
+    }
+
+    Ok(records)
+}
+
+// Build the NFT traits that display the delegation records
+fn delegation_traits(records: &[DelegationRecord]) -> Vec<CW721Trait> {
+    records
+        .iter()
+        .map(|record| CW721Trait {
+            display_type: record.status.to_string(),
+            trait_type: record.validator.clone(),
+            timestamp: record.completion_time,
+            value: trait_value(&record.coin),
         })
-        .collect::<Result<Vec<CW721Trait>, ContractError>>()?;
+        .collect()
+}
+
+// Records left as Unbonding, Claimable or Unbonded by a partial unbonding,
+// which are skipped by the operations on the delegated records
+fn is_undelegated(record: &DelegationRecord) -> bool {
+    record.status == DisplayType::Unbonding
+        || record.status == DisplayType::Claimable
+        || record.status == DisplayType::Unbonded
+}
 
-    let msg = Cw721ExecuteMsg::UpdateExtension {
+pub fn generate_update_nft_msg(
+    query_res: CW721Metadata,
+    records: &[DelegationRecord],
+    token_id: String,
+) -> Cw721ExecuteMsg {
+    Cw721ExecuteMsg::UpdateExtension {
         token_id,
-        extension: Some(CW721Metadata {
-            attributes: Some(parsed_attrs),
-            ..query_res
-        }),
-    };
+        extension: Some(updated_nft_metadata(query_res, records)),
+    }
+}
+
+pub fn updated_nft_metadata(
+    query_res: CW721Metadata,
+    records: &[DelegationRecord],
+) -> CW721Metadata {
+    CW721Metadata {
+        attributes: Some(delegation_traits(records)),
+        ..query_res
+    }
+}
 
-    Ok(msg)
+// Records with the shares converted to the amounts they are worth,
+// for the executions that do not change the reward indexes
+pub fn displayed_records(
+    deps: Deps,
+    env: &Env,
+    records: &[DelegationRecord],
+) -> Result<Vec<DelegationRecord>, ContractError> {
+    ShareRatios::new(&env.contract.address).to_amounts(deps, records)
 }
 
 fn try_redelegate(
     env: Env,
     info: MessageInfo,
-    deps: DepsMut,
+    mut deps: DepsMut,
     token_id: String,
+    validators: Option<Vec<ValidatorWeight>>,
 ) -> Result<Response, ContractError> {
     let cfg = CFG.load(deps.storage)?;
-    let validators = query::all_validators(deps.querier)?;
-    if validators.is_empty() {
-        return Err(ContractError::NoValidatorsFound {});
-    }
     let nft_contract_addr = match cfg.nft_contract_addr {
         Some(addr) => String::from(addr),
         None => return Err(ContractError::NoNftContractAddress {}),
@@ -313,26 +1085,97 @@ fn try_redelegate(
             info.sender.to_string(),
         ));
     }
-    let attrs = query_res
-        .info
-        .extension
-        .attributes
-        .clone()
-        .unwrap_or_default();
-    if attrs.is_empty() {
-        return Err(ContractError::NoDelegationsFound(token_id));
-    }
-    let msgs = generate_redelegate_msg(validators, attrs, env.clone(), token_id.clone())?;
-    let msg_update_nft = generate_redelegate_nft_msg(
+    let RedelegatePlan {
+        selector,
+        mut ratios,
+        delegated,
+        msg_redelegate,
+        completion_time,
+        fallback_seconds,
+        records,
+    } = plan_redelegate(deps.as_ref(), &env, &token_id, validators)?;
+    selector.save(deps.storage)?;
+    DELEGATIONS.save(deps.storage, &token_id, &records)?;
+    let msg_update_nft = generate_update_nft_msg(
         query_res.info.extension,
-        cfg.unbonding_seconds,
-        env.block.time,
-        token_id,
-    )?;
+        &ratios.to_amounts(deps.as_ref(), &records)?,
+        token_id.clone(),
+    );
 
-    Ok(Response::new()
+    // The replies move the shares to the reward indexes of the
+    // destination validators, where the redelegated amounts are worth
+    // other shares, settling the rewards earned by the NFT until now.
+    // x/alliance pays the rewards of the source and the destination
+    // delegations at once, so the destination ones are claimed first
+    // to credit each reward index with its own rewards
+    let mut completion_times = vec![];
+    let mut moves = vec![];
+    let mut claimed = vec![];
+    let mut msgs = vec![];
+    for (record, record_msgs) in delegated.iter().zip(msg_redelegate.iter()) {
+        let mut removed = vec![(
+            token_id.clone(),
+            record.validator.clone(),
+            record.coin.clone(),
+        )];
+        for msg in record_msgs.iter() {
+            let coin = native_coin(msg.amount.as_ref())?;
+            let dst = msg.validator_dst_address.clone();
+            let dst_shares = REWARD_INDEXES
+                .may_load(deps.storage, (&dst, &coin.denom))?
+                .unwrap_or_default()
+                .total_shares;
+            if !dst_shares.is_zero() && !claimed.contains(&(dst.clone(), coin.denom.clone())) {
+                msgs.push(claim_rewards_msg(
+                    &env,
+                    &dst,
+                    &coin.denom,
+                    PAID_REWARDS_REPLY_ID,
+                ));
+                moves.push(SharesMove {
+                    validator: dst.clone(),
+                    denom: coin.denom.clone(),
+                    spent: vec![],
+                    removed: vec![],
+                    added: vec![],
+                });
+            }
+            claimed.push((record.validator.clone(), coin.denom.clone()));
+            claimed.push((dst.clone(), coin.denom.clone()));
+
+            let shares = ratios.to_shares(deps.as_ref(), &dst, &coin)?;
+            moves.push(SharesMove {
+                validator: record.validator.clone(),
+                denom: coin.denom.clone(),
+                spent: vec![],
+                removed: std::mem::take(&mut removed),
+                added: vec![(token_id.clone(), dst.clone(), shares)],
+            });
+            completion_times.push(CompletionTimeTmp {
+                token_id: token_id.clone(),
+                validator: dst,
+                denom: coin.denom,
+                status: DisplayType::Redelegating,
+                completion_time,
+            });
+            msgs.push(SubMsg::reply_on_success(
+                CosmosMsg::Stargate {
+                    type_url: "/alliance.alliance.MsgRedelegate".to_string(),
+                    value: Binary::from(msg.encode_to_vec()),
+                },
+                COMPLETION_TIME_REPLY_ID,
+            ));
+        }
+    }
+    COMPLETION_TIMES_TMP.save(deps.storage, &completion_times)?;
+    save_paid_rewards_tmp(deps.branch(), &env, &info.sender, moves)?;
+
+    let res = Response::new()
         .add_attribute("action", "redelegate")
         .add_attribute("sender", info.sender.to_string())
+        .add_attributes(
+            fallback_seconds.map(|seconds| ("unbonding_seconds_fallback", seconds.to_string())),
+        )
         .add_submessage(SubMsg::reply_always(
             WasmMsg::Execute {
                 contract_addr: nft_contract_addr,
@@ -341,96 +1184,402 @@ fn try_redelegate(
             },
             REDELEGATE_REPLY_ID,
         ))
-        .add_messages(msgs))
+        .add_submessages(msgs);
+
+    Ok(res)
+}
+
+// Redelegations of the NFT delegated records, shared
+// by MsgRedelegate and the SimulateRedelegate query
+pub struct RedelegatePlan {
+    pub selector: ValidatorSelector,
+    pub ratios: ShareRatios,
+    pub delegated: Vec<DelegationRecord>,
+    pub msg_redelegate: Vec<Vec<MsgRedelegate>>,
+    pub completion_time: Timestamp,
+    pub fallback_seconds: Option<u64>,
+    pub records: Vec<DelegationRecord>,
+}
+
+pub fn plan_redelegate(
+    deps: Deps,
+    env: &Env,
+    token_id: &str,
+    validators: Option<Vec<ValidatorWeight>>,
+) -> Result<RedelegatePlan, ContractError> {
+    let mut selector = ValidatorSelector::load_with_user_validators(deps, env, validators)?;
+    let mut records = load_delegations(deps.storage, token_id)?;
+    status::refresh(&mut records, env.block.time)?;
+    let delegated = records
+        .iter()
+        .filter(|record| !is_undelegated(record))
+        .cloned()
+        .collect::<Vec<DelegationRecord>>();
+    if delegated.is_empty() {
+        return Err(ContractError::RedelegatingImpossible(token_id.to_string()));
+    }
+    for record in delegated.iter() {
+        status::transition(
+            &record.status,
+            &DisplayType::Redelegating,
+            record.completion_time,
+            env.block.time,
+        )?;
+    }
+
+    // Redelegate the amount the shares are worth so the
+    // hub never redelegates more than it holds
+    let mut ratios = ShareRatios::new(&env.contract.address);
+    let mut coins = vec![];
+    for record in delegated.iter() {
+        coins.push(ratios.to_amount(deps, &record.validator, &record.coin)?);
+    }
+    let msg_redelegate = generate_redelegate_msg(
+        &mut selector,
+        &delegated,
+        coins,
+        env.clone(),
+        token_id.to_string(),
+    )?;
+    let (completion_time, fallback_seconds) = estimate_completion_time(deps, env)?;
+    let records = redelegated_records(
+        deps,
+        &mut ratios,
+        records,
+        &msg_redelegate,
+        completion_time,
+        token_id,
+    )?;
+
+    Ok(RedelegatePlan {
+        selector,
+        ratios,
+        delegated,
+        msg_redelegate,
+        completion_time,
+        fallback_seconds,
+        records,
+    })
 }
 
 fn generate_redelegate_msg(
-    validators: Vec<Validator>,
-    attrs: Vec<CW721Trait>,
+    selector: &mut ValidatorSelector,
+    records: &[DelegationRecord],
+    coins: Vec<Coin>,
     env: Env,
     token_id: String,
-) -> Result<Vec<CosmosMsg>, ContractError> {
-    let mut vals_len = validators.len() as u64;
-
-    let msgs = attrs
+) -> Result<Vec<Vec<MsgRedelegate>>, ContractError> {
+    let user_validators = selector.user_validators();
+    let msgs = records
         .iter()
-        .map(|attr| {
-            if (attr.display_type == DisplayType::Redelegating.to_string()
-                && attr.timestamp < env.block.time)
-                || attr.display_type != DisplayType::Delegated.to_string()
-            {
-                return Err(ContractError::RedelegatingImpossible(token_id.clone()));
-            }
-            let coin = attr.value.split(DEFAULT_DELIMITER).collect::<Vec<&str>>();
+        .zip(coins.iter())
+        .map(|(record, coin)| {
+            // The validators chosen by the user divide the coin between
+            // them so the delegation cannot be redelegated to itself
+            let dsts = match &user_validators {
+                Some(validators) if validators.contains(&record.validator) => {
+                    return Err(ContractError::RedelegatingImpossible(token_id.clone()))
+                }
+                Some(_) => selector.split(coin)?,
+                None => vec![(selector.next(Some(&record.validator))?, coin.clone())],
+            };
 
-            let pseudorandom_index = get_pseudorandom(env.block.height, vals_len);
-            let val = &validators[pseudorandom_index as usize];
+            let msgs = dsts
+                .into_iter()
+                .map(|(validator, coin)| MsgRedelegate {
+                    delegator_address: env.contract.address.to_string(),
+                    validator_src_address: record.validator.clone(),
+                    validator_dst_address: validator,
+                    amount: Some(CosmosNativeCoin {
+                        denom: coin.denom.to_string(),
+                        amount: coin.amount.to_string(),
+                    }),
+                })
+                .collect::<Vec<MsgRedelegate>>();
 
-            let msg = MsgRedelegate {
-                delegator_address: env.contract.address.to_string(),
-                validator_src_address: attr.trait_type.clone(),
-                validator_dst_address: val.address.to_string(),
-                amount: Some(CosmosNativeCoin {
-                    denom: coin[1].to_string(),
-                    amount: coin[0].to_string(),
-                }),
-            }
-            .encode_to_vec();
+            Ok(msgs)
+        })
+        .collect::<Result<Vec<Vec<MsgRedelegate>>, ContractError>>()?;
+
+    Ok(msgs)
+}
 
-            // Remove 1 of the index to generate a new
-            // pseudorandom index in the next iteration
-            if vals_len > 1 {
-                vals_len -= 1
+// Replace each delegated record with the Redelegating records of
+// the messages that redelegate it, which keep the shares of the
+// destination validators the redelegated amounts are worth
+fn redelegated_records(
+    deps: Deps,
+    ratios: &mut ShareRatios,
+    records: Vec<DelegationRecord>,
+    msg_redelegate: &[Vec<MsgRedelegate>],
+    completion_time: Timestamp,
+    token_id: &str,
+) -> Result<Vec<DelegationRecord>, ContractError> {
+    let mut msgs = msg_redelegate.iter();
+    let records = records
+        .into_iter()
+        .map(|record| {
+            if is_undelegated(&record) {
+                return Ok(vec![record]);
             }
+            let record_msgs = match msgs.next() {
+                Some(record_msgs) => record_msgs,
+                None => return Err(ContractError::RedelegatingImpossible(token_id.to_string())),
+            };
 
-            Ok(CosmosMsg::Stargate {
-                type_url: "/alliance.alliance.MsgRedelegate".to_string(),
-                value: Binary::from(msg),
-            })
+            record_msgs
+                .iter()
+                .map(|msg| {
+                    let coin = native_coin(msg.amount.as_ref())?;
+                    Ok(DelegationRecord {
+                        validator: msg.validator_dst_address.clone(),
+                        coin: ratios.to_shares(deps, &msg.validator_dst_address, &coin)?,
+                        status: DisplayType::Redelegating,
+                        completion_time,
+                    })
+                })
+                .collect::<Result<Vec<DelegationRecord>, ContractError>>()
         })
-        .collect::<Result<Vec<CosmosMsg>, ContractError>>()?;
+        .collect::<Result<Vec<Vec<DelegationRecord>>, ContractError>>()?;
 
-    Ok(msgs)
+    Ok(records.concat())
 }
 
-fn generate_redelegate_nft_msg(
-    query_res: CW721Metadata,
-    unbonding_seconds: u64,
-    block_time: Timestamp,
+fn try_split(
+    env: Env,
+    info: MessageInfo,
+    mut deps: DepsMut,
     token_id: String,
-) -> Result<Cw721ExecuteMsg, ContractError> {
-    let unbonding_timestamp = block_time.plus_seconds(unbonding_seconds);
-    let attrs = match query_res.attributes {
-        Some(attrs) => attrs,
-        None => return Err(ContractError::NoDelegationsFound(token_id)),
+    parts: Vec<Vec<DelegationTarget>>,
+) -> Result<Response, ContractError> {
+    let cfg = CFG.load(deps.storage)?;
+    let nft_contract_addr = match cfg.nft_contract_addr {
+        Some(addr) => String::from(addr),
+        None => return Err(ContractError::NoNftContractAddress {}),
     };
-    let parsed_attrs = attrs
+    let query_res = query::all_nft_info(deps.querier, token_id.clone(), nft_contract_addr.clone())?;
+    if query_res.access.owner != info.sender {
+        return Err(ContractError::UnauthorizedNFTOwnere(
+            query_res.access.owner,
+            info.sender.to_string(),
+        ));
+    }
+    let mut records = load_delegations(deps.storage, &token_id)?;
+    status::refresh(&mut records, env.block.time)?;
+    if records
         .iter()
-        .map(|attr| {
-            if (attr.display_type == DisplayType::Redelegating.to_string()
-                && attr.timestamp < block_time)
-                || attr.display_type != DisplayType::Delegated.to_string()
-            {
-                return Err(ContractError::RedelegatingImpossible(token_id.clone()));
-            }
+        .any(|record| record.status != DisplayType::Delegated)
+        || parts.is_empty()
+        || parts.iter().any(|part| part.is_empty())
+    {
+        return Err(ContractError::SplitImpossible(token_id));
+    }
 
-            Ok(CW721Trait {
-                display_type: DisplayType::Redelegating.to_string(),
-                timestamp: unbonding_timestamp,
-                ..attr.clone()
-            })
-        })
-        .collect::<Result<Vec<CW721Trait>, ContractError>>()?;
+    // Take each part from the NFT delegations minting a new NFT with
+    // them, the replies of the claims move their shares of the reward
+    // indexes once the rewards earned until now are credited
+    let mut ratios = ShareRatios::new(&env.contract.address);
+    let mut moves = vec![];
+    let mut msgs = vec![];
+    for (index, part) in parts.into_iter().enumerate() {
+        let part_token_id = (cfg.minted_nfts + index as u64).to_string();
+        let taken_shares =
+            take_delegated_shares(deps.as_ref(), &mut ratios, &mut records, part, &token_id)?;
 
-    let msg = Cw721ExecuteMsg::UpdateExtension {
-        token_id,
-        extension: Some(CW721Metadata {
-            attributes: Some(parsed_attrs),
-            ..query_res
-        }),
+        let mut part_records = vec![];
+        for (validator, shares, _) in taken_shares.into_iter() {
+            move_claimed_shares(&mut moves, &token_id, &part_token_id, &validator, &shares);
+
+            part_records.push(DelegationRecord {
+                validator,
+                coin: shares,
+                status: DisplayType::Delegated,
+                completion_time: env.block.time,
+            });
+        }
+        DELEGATIONS.save(deps.storage, &part_token_id, &part_records)?;
+
+        msgs.push(SubMsg::reply_always(
+            WasmMsg::Execute {
+                contract_addr: nft_contract_addr.clone(),
+                msg: to_binary(&mint_nft_msg(
+                    info.sender.to_string(),
+                    part_token_id,
+                    &ratios.to_amounts(deps.as_ref(), &part_records)?,
+                ))?,
+                funds: vec![],
+            },
+            MINT_NFT_REPLY_ID,
+        ));
+    }
+
+    // The original NFT must keep part of its delegations
+    if records.is_empty() {
+        return Err(ContractError::SplitImpossible(token_id));
+    }
+    DELEGATIONS.save(deps.storage, &token_id, &records)?;
+    let msg_update_nft = generate_update_nft_msg(
+        query_res.info.extension,
+        &ratios.to_amounts(deps.as_ref(), &records)?,
+        token_id.clone(),
+    );
+
+    let claims = claim_moved_shares_msgs(&env, &moves);
+    save_paid_rewards_tmp(deps.branch(), &env, &info.sender, moves)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "split")
+        .add_attribute("sender", info.sender.to_string())
+        .add_attribute("token_id", token_id)
+        .add_submessage(SubMsg::reply_always(
+            WasmMsg::Execute {
+                contract_addr: nft_contract_addr,
+                msg: to_binary(&msg_update_nft)?,
+                funds: vec![],
+            },
+            SPLIT_NFT_REPLY_ID,
+        ))
+        .add_submessages(msgs)
+        .add_submessages(claims))
+}
+
+fn try_merge(
+    env: Env,
+    info: MessageInfo,
+    mut deps: DepsMut,
+    token_ids: Vec<String>,
+) -> Result<Response, ContractError> {
+    let cfg = CFG.load(deps.storage)?;
+    let nft_contract_addr = match cfg.nft_contract_addr {
+        Some(addr) => String::from(addr),
+        None => return Err(ContractError::NoNftContractAddress {}),
+    };
+    let token_id = match token_ids.first() {
+        Some(token_id) => token_id.clone(),
+        None => return Err(ContractError::MergeImpossible(String::new())),
     };
+    if token_ids.len() < 2
+        || token_ids
+            .iter()
+            .enumerate()
+            .any(|(index, id)| token_ids[..index].contains(id))
+    {
+        return Err(ContractError::MergeImpossible(token_id));
+    }
+
+    // Check the sender owns all the NFTs and that
+    // all their delegations are Delegated
+    let mut nfts = vec![];
+    for id in token_ids.iter() {
+        let query_res = query::all_nft_info(deps.querier, id.clone(), nft_contract_addr.clone())?;
+        if query_res.access.owner != info.sender {
+            return Err(ContractError::UnauthorizedNFTOwnere(
+                query_res.access.owner,
+                info.sender.to_string(),
+            ));
+        }
+        let mut records = DELEGATIONS.may_load(deps.storage, id)?.unwrap_or_default();
+        status::refresh(&mut records, env.block.time)?;
+        if records.is_empty()
+            || records
+                .iter()
+                .any(|record| record.status != DisplayType::Delegated)
+        {
+            return Err(ContractError::MergeImpossible(id.clone()));
+        }
+        nfts.push((id.clone(), query_res.info.extension, records));
+    }
+
+    // Fold the delegations of the rest of the NFTs into the first one
+    // before burning them, the replies of the claims move their shares
+    // of the reward indexes once the rewards earned until now are credited
+    let (_, extension, mut merged_records) = nfts.remove(0);
+    let mut moves = vec![];
+    let mut msgs = vec![];
+    for (id, _, records) in nfts.iter() {
+        for record in records.iter() {
+            move_claimed_shares(&mut moves, id, &token_id, &record.validator, &record.coin);
 
-    Ok(msg)
+            merge_delegated_record(&mut merged_records, record.clone());
+        }
+        DELEGATIONS.remove(deps.storage, id);
+        AUTO_COMPOUND.remove(deps.storage, id);
+
+        msgs.push(WasmMsg::Execute {
+            contract_addr: nft_contract_addr.clone(),
+            msg: to_binary(&Cw721ExecuteMsg::MinterBurn {
+                token_id: id.clone(),
+            })?,
+            funds: vec![],
+        });
+    }
+    DELEGATIONS.save(deps.storage, &token_id, &merged_records)?;
+    let msg_update_nft = generate_update_nft_msg(
+        extension,
+        &displayed_records(deps.as_ref(), &env, &merged_records)?,
+        token_id.clone(),
+    );
+
+    let claims = claim_moved_shares_msgs(&env, &moves);
+    save_paid_rewards_tmp(deps.branch(), &env, &info.sender, moves)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "merge")
+        .add_attribute("sender", info.sender.to_string())
+        .add_attribute("token_id", token_id)
+        .add_submessage(SubMsg::reply_always(
+            WasmMsg::Execute {
+                contract_addr: nft_contract_addr,
+                msg: to_binary(&msg_update_nft)?,
+                funds: vec![],
+            },
+            MERGE_NFT_REPLY_ID,
+        ))
+        .add_messages(msgs)
+        .add_submessages(claims))
+}
+
+// Move the shares from one NFT to another once the rewards of the
+// (validator, denom) are claimed, grouping the moves of each reward
+// index so its rewards are claimed only once
+fn move_claimed_shares(
+    moves: &mut Vec<SharesMove>,
+    from: &str,
+    to: &str,
+    validator: &str,
+    shares: &Coin,
+) {
+    let shares_move = match moves
+        .iter_mut()
+        .position(|m| m.validator == validator && m.denom == shares.denom)
+    {
+        Some(index) => &mut moves[index],
+        None => {
+            moves.push(SharesMove {
+                validator: validator.to_string(),
+                denom: shares.denom.clone(),
+                spent: vec![],
+                removed: vec![],
+                added: vec![],
+            });
+            moves.last_mut().unwrap()
+        }
+    };
+    shares_move
+        .removed
+        .push((from.to_string(), validator.to_string(), shares.clone()));
+    shares_move
+        .added
+        .push((to.to_string(), validator.to_string(), shares.clone()));
+}
+
+// The rewards earned by the moved shares until now are claimed
+// first so the replies credit them to the NFTs that earned them
+fn claim_moved_shares_msgs(env: &Env, moves: &[SharesMove]) -> Vec<SubMsg> {
+    moves
+        .iter()
+        .map(|m| claim_rewards_msg(env, &m.validator, &m.denom, PAID_REWARDS_REPLY_ID))
+        .collect()
 }
 
 fn try_claim_rewards(
@@ -444,143 +1593,372 @@ fn try_claim_rewards(
         Some(addr) => String::from(addr),
         None => return Err(ContractError::NoNftContractAddress {}),
     };
-    let query_res = query::all_nft_info(deps.querier, token_id.clone(), nft_contract_addr)?;
+    let query_res = query::all_nft_info(deps.querier, token_id.clone(), nft_contract_addr.clone())?;
     if query_res.access.owner != info.sender {
         return Err(ContractError::UnauthorizedNFTOwnere(
             query_res.access.owner,
             info.sender.to_string(),
         ));
     }
-    let attrs = query_res.info.extension.attributes.unwrap_or_default();
-    if attrs.is_empty() {
-        return Err(ContractError::NoDelegationsFound(token_id));
+    let mut records = load_delegations(deps.storage, &token_id)?;
+    let mut msgs = vec![];
+    if status::refresh(&mut records, env.block.time)? {
+        DELEGATIONS.save(deps.storage, &token_id, &records)?;
+        msgs.push(refresh_nft_msg(
+            query_res.info.extension,
+            &displayed_records(deps.as_ref(), &env, &records)?,
+            token_id.clone(),
+            nft_contract_addr,
+        )?);
+    }
+    let records = records
+        .into_iter()
+        .filter(|record| !is_undelegated(record))
+        .collect::<Vec<DelegationRecord>>();
+    if records.is_empty() {
+        return Err(ContractError::ClaimRewardsImpossible(token_id));
     }
 
-    let msgs = attrs
+    let claim_msgs = records
         .iter()
-        .map(|attr| {
-            if (attr.display_type == DisplayType::Redelegating.to_string()
-                && attr.timestamp < env.block.time)
-                || attr.display_type != DisplayType::Delegated.to_string()
-            {
+        .map(|record| {
+            if record.status != DisplayType::Delegated {
                 return Err(ContractError::ClaimRewardsImpossible(token_id.clone()));
             }
 
-            let coin = attr.value.split(DEFAULT_DELIMITER).collect::<Vec<&str>>();
-            let msg = MsgClaimDelegationRewards {
-                delegator_address: env.contract.address.to_string(),
-                validator_address: attr.trait_type.clone(),
-                denom: coin[1].to_string(),
-            }
-            .encode_to_vec();
-
-            Ok(CosmosMsg::Stargate {
-                type_url: "/alliance.alliance.MsgClaimDelegationRewards".to_string(),
-                value: Binary::from(msg),
-            })
+            Ok(claim_rewards_msg(
+                &env,
+                &record.validator,
+                &record.coin.denom,
+                CLAIM_REWARDS_REPLY_ID,
+            ))
         })
-        .collect::<Result<Vec<CosmosMsg>, ContractError>>()?;
+        .collect::<Result<Vec<SubMsg>, ContractError>>()?;
+
+    // Store the balance of the hub before claiming the rewards and
+    // the claimed delegations so each reply can split the claimed
+    // rewards between the NFTs and send its share to the NFT owner
+    let balances = deps.querier.query_all_balances(&env.contract.address)?;
+    let delegations = records
+        .iter()
+        .map(|record| (record.validator.clone(), record.coin.clone()))
+        .collect::<Vec<(String, Coin)>>();
+    CLAIM_REWARDS_TMP.save(
+        deps.storage,
+        &ClaimRewardsTmp {
+            owner: info.sender.clone(),
+            token_id,
+            balances,
+            delegations,
+        },
+    )?;
 
     Ok(Response::new()
         .add_attribute("action", "claim_rewards")
         .add_attribute("sender", info.sender.to_string())
-        .add_messages(msgs))
+        .add_submessages(msgs)
+        .add_submessages(claim_msgs))
 }
 
-fn try_redeem_bond(
-    env: Env,
+fn claim_rewards_msg(env: &Env, validator: &str, denom: &str, reply_id: u64) -> SubMsg {
+    let msg = MsgClaimDelegationRewards {
+        delegator_address: env.contract.address.to_string(),
+        validator_address: validator.to_string(),
+        denom: denom.to_string(),
+    }
+    .encode_to_vec();
+
+    SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: "/alliance.alliance.MsgClaimDelegationRewards".to_string(),
+            value: Binary::from(msg),
+        },
+        reply_id,
+    )
+}
+
+fn try_set_auto_compound(
     info: MessageInfo,
     deps: DepsMut,
     token_id: String,
+    enabled: bool,
 ) -> Result<Response, ContractError> {
     let cfg = CFG.load(deps.storage)?;
     let nft_contract_addr = match cfg.nft_contract_addr {
         Some(addr) => String::from(addr),
         None => return Err(ContractError::NoNftContractAddress {}),
     };
-    let query_res = query::all_nft_info(deps.querier, token_id.clone(), nft_contract_addr.clone())?;
+    let query_res = query::all_nft_info(deps.querier, token_id.clone(), nft_contract_addr)?;
     if query_res.access.owner != info.sender {
         return Err(ContractError::UnauthorizedNFTOwnere(
             query_res.access.owner,
             info.sender.to_string(),
         ));
     }
-    let attrs = query_res
-        .info
-        .extension
-        .attributes
-        .clone()
-        .unwrap_or_default();
-    if attrs.is_empty() {
-        return Err(ContractError::NoDelegationsFound(token_id));
+
+    if enabled {
+        AUTO_COMPOUND.save(deps.storage, &token_id, &Empty {})?;
+    } else {
+        AUTO_COMPOUND.remove(deps.storage, &token_id);
     }
 
-    let msgs = attrs
-        .iter()
-        .map(|attr| {
-            let coin_vec = attr.value.split(DEFAULT_DELIMITER).collect::<Vec<&str>>();
-            let amount_uint = Uint128::from_str(coin_vec[0]).unwrap();
+    Ok(Response::new()
+        .add_attribute("action", "set_auto_compound")
+        .add_attribute("sender", info.sender.to_string())
+        .add_attribute("token_id", token_id)
+        .add_attribute("enabled", enabled.to_string()))
+}
+
+fn try_compound(
+    env: Env,
+    deps: DepsMut,
+    token_ids: Vec<String>,
+) -> Result<Response, ContractError> {
+    let cfg = CFG.load(deps.storage)?;
+    let nft_contract_addr = match cfg.nft_contract_addr {
+        Some(addr) => String::from(addr),
+        None => return Err(ContractError::NoNftContractAddress {}),
+    };
 
-            let amount = Coin::new(amount_uint.into(), coin_vec[1].to_string());
+    // Claim the rewards of the delegated records, the ones
+    // waiting for the chain are compounded once they complete
+    let mut refresh_msgs = vec![];
+    let mut msgs = vec![];
+    let mut delegations = vec![];
+    for token_id in token_ids.iter() {
+        if !AUTO_COMPOUND.has(deps.storage, token_id) {
+            return Err(ContractError::AutoCompoundDisabled(token_id.clone()));
+        }
+        let query_res =
+            query::all_nft_info(deps.querier, token_id.clone(), nft_contract_addr.clone())?;
+        let mut records = load_delegations(deps.storage, token_id)?;
+        if status::refresh(&mut records, env.block.time)? {
+            DELEGATIONS.save(deps.storage, token_id, &records)?;
+            refresh_msgs.push(refresh_nft_msg(
+                query_res.info.extension,
+                &displayed_records(deps.as_ref(), &env, &records)?,
+                token_id.clone(),
+                nft_contract_addr.clone(),
+            )?);
+        }
+        for record in records
+            .iter()
+            .filter(|record| record.status == DisplayType::Delegated)
+        {
+            msgs.push(claim_rewards_msg(
+                &env,
+                &record.validator,
+                &record.coin.denom,
+                COMPOUND_REPLY_ID,
+            ));
+            delegations.push(CompoundDelegation {
+                owner: query_res.access.owner.clone(),
+                token_id: token_id.clone(),
+                validator: record.validator.clone(),
+                coin: record.coin.clone(),
+            });
+        }
+    }
 
-            // generate msg send to the user address
-            BankMsg::Send {
-                to_address: info.sender.to_string(),
-                amount: vec![amount],
-            }
-        })
-        .collect::<Vec<BankMsg>>();
-    let msg_update_nft =
-        generate_redeem_bond_nft_msg(query_res.info.extension, env.block.time, token_id)?;
+    let balances = deps.querier.query_all_balances(&env.contract.address)?;
+    COMPOUND_TMP.save(
+        deps.storage,
+        &CompoundTmp {
+            balances,
+            delegations,
+        },
+    )?;
 
     Ok(Response::new()
-        .add_attribute("action", "redeem_bond")
-        .add_attribute("sender", info.sender.to_string())
-        .add_submessage(SubMsg::reply_always(
-            WasmMsg::Execute {
-                contract_addr: nft_contract_addr,
-                msg: to_binary(&msg_update_nft)?,
-                funds: vec![],
-            },
-            REDEEM_BOND_REPLY_ID,
-        ))
-        .add_messages(msgs))
+        .add_attribute("action", "compound")
+        .add_attribute("token_ids", token_ids.join(","))
+        .add_submessages(refresh_msgs)
+        .add_submessages(msgs))
 }
 
-fn generate_redeem_bond_nft_msg(
-    query_res: CW721Metadata,
-    block_time: Timestamp,
+fn try_redeem_bond(
+    env: Env,
+    info: MessageInfo,
+    mut deps: DepsMut,
     token_id: String,
-) -> Result<Cw721ExecuteMsg, ContractError> {
-    let attrs = match query_res.attributes {
-        Some(attrs) => attrs,
-        None => return Err(ContractError::NoDelegationsFound(token_id)),
+) -> Result<Response, ContractError> {
+    let cfg = CFG.load(deps.storage)?;
+    let nft_contract_addr = match cfg.nft_contract_addr {
+        Some(addr) => String::from(addr),
+        None => return Err(ContractError::NoNftContractAddress {}),
     };
-    let parsed_attrs = attrs
+
+    // The NFT may have been burned once all its records
+    // were redeemed so the records are checked first
+    let mut records = load_delegations(deps.storage, &token_id)?;
+    if is_redeemed(&records) {
+        return Err(ContractError::AlreadyRedeemed(token_id));
+    }
+    let query_res = query::all_nft_info(deps.querier, token_id.clone(), nft_contract_addr.clone())?;
+    if query_res.access.owner != info.sender {
+        return Err(ContractError::UnauthorizedNFTOwnere(
+            query_res.access.owner,
+            info.sender.to_string(),
+        ));
+    }
+    status::refresh(&mut records, env.block.time)?;
+
+    // Only the records which unbonding has matured are paid, the
+    // rest of the records are kept until they can be redeemed too
+    let mut claimable = vec![];
+    for record in records
         .iter()
-        .map(|attr| {
-            if attr.display_type != DisplayType::Unbonding.to_string()
-                || (attr.display_type == DisplayType::Unbonding.to_string()
-                    && attr.timestamp > block_time)
-            {
-                return Err(ContractError::RedeeemBondImpossibel(token_id.clone()));
-            }
+        .filter(|record| record.status == DisplayType::Claimable)
+    {
+        rewards::merge_coins(&mut claimable, vec![record.coin.clone()]);
+    }
+    if claimable.is_empty() {
+        return Err(ContractError::RedeeemBondImpossibel(token_id));
+    }
 
-            Ok(CW721Trait {
-                display_type: DisplayType::Unbonded.to_string(),
-                timestamp: block_time,
-                ..attr.clone()
-            })
+    // The records unbonded before the unbondings were tracked are
+    // tracked now so they are paid from the funds not owed yet
+    let untracked = records
+        .iter()
+        .filter(|record| {
+            record.status == DisplayType::Claimable
+                && !UNBONDINGS.has(
+                    deps.storage,
+                    (&record.coin.denom, record.completion_time.nanos()),
+                )
         })
-        .collect::<Result<Vec<CW721Trait>, ContractError>>()?;
+        .collect::<Vec<&DelegationRecord>>();
+    for record in untracked.into_iter() {
+        unbondings::add_unbonding(deps.storage, &record.coin, record.completion_time)?;
+    }
+    for coin in claimable.iter() {
+        unbondings::settle_unbondings(deps.branch(), &env, &coin.denom)?;
+    }
 
-    let msg = Cw721ExecuteMsg::UpdateExtension {
-        token_id,
-        extension: Some(CW721Metadata {
-            attributes: Some(parsed_attrs),
-            ..query_res
-        }),
+    // Pay the records from the principal returned by the chain for
+    // their own unbonding, pro rata when the hub received less than
+    // the amounts undelegated
+    let mut msgs = vec![];
+    for record in records
+        .iter_mut()
+        .filter(|record| record.status == DisplayType::Claimable)
+    {
+        record.coin =
+            unbondings::redeem_unbonding(deps.storage, &record.coin, record.completion_time)?;
+        record.transition(DisplayType::Unbonded, env.block.time, env.block.time)?;
+        if !record.coin.amount.is_zero() {
+            msgs.push(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount: vec![record.coin.clone()],
+            });
+        }
+    }
+    DELEGATIONS.save(deps.storage, &token_id, &records)?;
+    let res = Response::new()
+        .add_attribute("action", "redeem_bond")
+        .add_attribute("sender", info.sender.to_string());
+
+    // Once all the records are redeemed the NFT is worthless so
+    // it is burned or locked to avoid it being sold as valuable
+    let res = match (is_redeemed(&records), cfg.redeemed_nft) {
+        (true, RedeemedNft::Burn) => {
+            res.add_attribute("redeemed_nft", "burn")
+                .add_submessage(SubMsg::reply_always(
+                    WasmMsg::Execute {
+                        contract_addr: nft_contract_addr,
+                        msg: to_binary(&Cw721ExecuteMsg::MinterBurn { token_id })?,
+                        funds: vec![],
+                    },
+                    REDEEM_BOND_REPLY_ID,
+                ))
+        }
+        (redeemed, _) => {
+            let msg_update_nft = generate_update_nft_msg(
+                query_res.info.extension,
+                &displayed_records(deps.as_ref(), &env, &records)?,
+                token_id.clone(),
+            );
+            let res = res.add_submessage(SubMsg::reply_always(
+                WasmMsg::Execute {
+                    contract_addr: nft_contract_addr.clone(),
+                    msg: to_binary(&msg_update_nft)?,
+                    funds: vec![],
+                },
+                REDEEM_BOND_REPLY_ID,
+            ));
+            if !redeemed {
+                res
+            } else {
+                res.add_attribute("redeemed_nft", "lock")
+                    .add_message(WasmMsg::Execute {
+                        contract_addr: nft_contract_addr,
+                        msg: to_binary(&Cw721ExecuteMsg::Lock { token_id })?,
+                        funds: vec![],
+                    })
+            }
+        }
+    };
+
+    Ok(res.add_messages(msgs))
+}
+
+// The NFT is redeemed when all its records are Unbonded
+fn is_redeemed(records: &[DelegationRecord]) -> bool {
+    records
+        .iter()
+        .all(|record| record.status == DisplayType::Unbonded)
+}
+
+fn try_refresh(env: Env, deps: DepsMut, token_ids: Vec<String>) -> Result<Response, ContractError> {
+    let cfg = CFG.load(deps.storage)?;
+    let nft_contract_addr = match cfg.nft_contract_addr {
+        Some(addr) => String::from(addr),
+        None => return Err(ContractError::NoNftContractAddress {}),
     };
 
-    Ok(msg)
+    // Anyone can refresh the NFTs because the promotions
+    // only depend on the completion time of the records
+    let mut msgs = vec![];
+    let mut refreshed = vec![];
+    for token_id in token_ids {
+        let mut records = load_delegations(deps.storage, &token_id)?;
+        if !status::refresh(&mut records, env.block.time)? {
+            continue;
+        }
+        DELEGATIONS.save(deps.storage, &token_id, &records)?;
+
+        let query_res =
+            query::all_nft_info(deps.querier, token_id.clone(), nft_contract_addr.clone())?;
+        msgs.push(refresh_nft_msg(
+            query_res.info.extension,
+            &displayed_records(deps.as_ref(), &env, &records)?,
+            token_id.clone(),
+            nft_contract_addr.clone(),
+        )?);
+        refreshed.push(token_id);
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "refresh")
+        .add_attribute("token_ids", refreshed.join(","))
+        .add_submessages(msgs))
+}
+
+// Update the NFT metadata with the records promoted by the refresh
+fn refresh_nft_msg(
+    query_res: CW721Metadata,
+    records: &[DelegationRecord],
+    token_id: String,
+    nft_contract_addr: String,
+) -> Result<SubMsg, ContractError> {
+    let msg = generate_update_nft_msg(query_res, records, token_id);
+
+    Ok(SubMsg::reply_always(
+        WasmMsg::Execute {
+            contract_addr: nft_contract_addr,
+            msg: to_binary(&msg)?,
+            funds: vec![],
+        },
+        REFRESH_NFT_REPLY_ID,
+    ))
 }
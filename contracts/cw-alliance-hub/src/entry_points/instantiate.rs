@@ -1,5 +1,7 @@
+use crate::fees;
 use crate::msg::InstantiateMsg;
 use crate::state::Cfg;
+use crate::validators;
 use crate::{error::ContractError, state::CFG};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::{entry_point, to_binary, DepsMut, Env, MessageInfo, Response};
@@ -20,6 +22,10 @@ pub fn instantiate(
     // Set contract version
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
+    // The sender owns the hub unless another owner is set
+    let owner = msg.owner.unwrap_or_else(|| info.sender.to_string());
+    cw_ownable::initialize_owner(deps.storage, deps.api, Some(&owner))?;
+
     let cw721_instantiate_msg = Cw721InstantiateMsg {
         name: msg.cw721_collection.name.clone(),
         symbol: msg.cw721_collection.symbol,
@@ -38,7 +44,19 @@ pub fn instantiate(
         INSTANTIATE_REPLY_ID,
     );
 
-    CFG.save(deps.storage, &Cfg::new(msg.cw721_unbonding_seconds))?;
+    CFG.save(
+        deps.storage,
+        &Cfg::new(
+            msg.cw721_unbonding_seconds,
+            validators::validate_strategy(msg.validator_strategy.unwrap_or_default())?,
+            msg.split_mode.unwrap_or_default(),
+            msg.max_validators_per_denom.unwrap_or(1).max(1),
+            validators::validate_allowlist(msg.validators_allowlist)?,
+            msg.refund_unsupported_denoms.unwrap_or_default(),
+            msg.redeemed_nft.unwrap_or_default(),
+            fees::validate_protocol_fee(deps.api, msg.protocol_fee)?,
+        ),
+    )?;
 
     Ok(Response::new()
         .add_submessage(cw721_instantiate_submsg)
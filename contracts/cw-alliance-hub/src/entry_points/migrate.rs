@@ -1,8 +1,313 @@
-use crate::msg::MigrateMsg;
+use std::str::FromStr;
+
+use crate::msg::{MigrateMsg, RedelegationDestination};
+use crate::rewards;
+use crate::state::{
+    Cfg, DelegationRecord, DelegationsMigration, DisplayType, RedeemedNft, CFG, DELEGATIONS,
+    DELEGATIONS_MIGRATION, ORPHANED_REWARDS,
+};
+use crate::unbondings;
+use crate::ContractError;
+use cosmwasm_schema::cw_serde;
 #[cfg(not(feature = "library"))]
-use cosmwasm_std::{entry_point, DepsMut, Env, Response, StdResult};
+use cosmwasm_std::{entry_point, DepsMut, Env, Response};
+use cosmwasm_std::{Addr, Coin, StdResult, Uint128};
+use cw2::{get_contract_version, set_contract_version};
+use cw721_progressive_metadata::state::Trait as CW721Trait;
+use cw_storage_plus::Item;
+use semver::Version;
+
+use super::constants::{
+    CONTRACT_NAME, CONTRACT_VERSION, DEFAULT_DELIMITER, DEFAULT_MIGRATION_LIMIT,
+    MAX_MIGRATION_LIMIT,
+};
+use super::query;
+
+// Config stored by the versions before 0.2.0
+pub const CFG_V1: Item<CfgV1> = Item::new("config");
+
+#[cw_serde]
+pub struct CfgV1 {
+    pub minted_nfts: u64,
+    pub unbonding_seconds: u64,
+    pub nft_contract_addr: Option<Addr>,
+}
+
+type MigrationStep = fn(DepsMut, &Env, &MigrateMsg) -> Result<(), ContractError>;
+
+// State upgrades in the order they must run, each step
+// runs when migrating from a version lower than its own
+const MIGRATION_STEPS: [(&str, MigrationStep); 2] =
+    [("0.2.0", migrate_cfg_v1), ("0.2.0", migrate_delegations_v1)];
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
-    Ok(Response::default())
+pub fn migrate(mut deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::InvalidContractName(stored.contract));
+    }
+    let from = parse_version(&stored.version)?;
+    if from > parse_version(CONTRACT_VERSION)? {
+        return Err(ContractError::MigrationDowngrade(
+            stored.version,
+            CONTRACT_VERSION.to_string(),
+        ));
+    }
+
+    for (version, step) in MIGRATION_STEPS.iter() {
+        if from < parse_version(version)? {
+            step(deps.branch(), &env, &msg)?;
+        }
+    }
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("migrated_from", stored.version)
+        .add_attribute("migrated_to", CONTRACT_VERSION))
+}
+
+fn parse_version(version: &str) -> Result<Version, ContractError> {
+    Version::parse(version).map_err(|_| ContractError::InvalidContractVersion(version.to_string()))
+}
+
+// The config v1 only had the NFTs counter, the unbonding seconds and
+// the NFT contract, the new fields take the instantiate defaults and
+// the owner of the hub is taken from the migrate message
+pub fn migrate_cfg_v1(deps: DepsMut, _env: &Env, msg: &MigrateMsg) -> Result<(), ContractError> {
+    let MigrateMsg::Migrate { owner, .. } = msg;
+    let owner = match owner {
+        Some(owner) => owner,
+        None => return Err(ContractError::MigrationOwnerRequired {}),
+    };
+    cw_ownable::initialize_owner(deps.storage, deps.api, Some(owner))?;
+
+    let cfg_v1 = CFG_V1.load(deps.storage)?;
+    let mut cfg = Cfg::new(
+        cfg_v1.unbonding_seconds,
+        Default::default(),
+        Default::default(),
+        1,
+        None,
+        false,
+        RedeemedNft::default(),
+        None,
+    );
+    cfg.minted_nfts = cfg_v1.minted_nfts;
+    cfg.nft_contract_addr = cfg_v1.nft_contract_addr;
+    CFG.save(deps.storage, &cfg)?;
+
+    Ok(())
+}
+
+// The versions before 0.2.0 only kept the delegations in the NFT
+// metadata, so the delegation records of each NFT are rebuilt from
+// its metadata. The collection may be too large for a single message
+// so the migration starts with the first batch of NFTs and the owner
+// migrates the rest with ExecuteMsg::MigrateDelegations.
+// The rewards the versions before 0.2.0 left in the hub balance are
+// not owed to any unbonding, so the whole balance is kept apart as
+// orphaned rewards and the principal of the legacy unbondings that
+// were already returned is released while migrating them.
+pub fn migrate_delegations_v1(
+    deps: DepsMut,
+    env: &Env,
+    msg: &MigrateMsg,
+) -> Result<(), ContractError> {
+    if CFG.load(deps.storage)?.nft_contract_addr.is_none() {
+        return Ok(());
+    }
+    let balances = deps.querier.query_all_balances(&env.contract.address)?;
+    for balance in balances.iter() {
+        ORPHANED_REWARDS.update(deps.storage, &balance.denom, |orphaned| -> StdResult<_> {
+            Ok(orphaned.unwrap_or_default().checked_add(balance.amount)?)
+        })?;
+    }
+    DELEGATIONS_MIGRATION.save(
+        deps.storage,
+        &DelegationsMigration {
+            start_after: None,
+            unresolved: vec![],
+            started_at: env.block.time,
+        },
+    )?;
+    let MigrateMsg::Migrate { redelegations, .. } = msg;
+    migrate_delegations_batch(
+        deps,
+        env,
+        DEFAULT_MIGRATION_LIMIT,
+        redelegations.as_deref().unwrap_or_default(),
+    )?;
+
+    Ok(())
+}
+
+// Rebuild the delegation records of the next NFTs registering the
+// delegations in the reward indexes and the unbondings in the
+// unbondings ledger. The NFTs minted after the contract migration
+// already have their records so they are skipped. The NFTs which
+// redelegation destination cannot be recovered are kept unresolved
+// until the owner sets it, the migration completes after the last
+// NFT once there are no unresolved NFTs. It returns the number of
+// NFTs migrated.
+pub fn migrate_delegations_batch(
+    mut deps: DepsMut,
+    env: &Env,
+    limit: u32,
+    redelegations: &[RedelegationDestination],
+) -> Result<usize, ContractError> {
+    let mut migration = match DELEGATIONS_MIGRATION.may_load(deps.storage)? {
+        Some(migration) => migration,
+        None => return Err(ContractError::NoDelegationsMigration {}),
+    };
+    let nft_contract_addr = match CFG.load(deps.storage)?.nft_contract_addr {
+        Some(addr) => String::from(addr),
+        None => return Err(ContractError::NoNftContractAddress {}),
+    };
+    let limit = limit.clamp(1, MAX_MIGRATION_LIMIT);
+    let token_ids = query::all_tokens(
+        deps.querier,
+        nft_contract_addr.clone(),
+        migration.start_after.clone(),
+        limit,
+    )?;
+
+    let mut alliance_delegations = None;
+    let mut migrated = 0;
+    let unresolved = std::mem::take(&mut migration.unresolved);
+    for token_id in unresolved.iter().chain(token_ids.iter()) {
+        if DELEGATIONS.has(deps.storage, token_id) {
+            continue;
+        }
+        let query_res =
+            query::all_nft_info(deps.querier, token_id.clone(), nft_contract_addr.clone())?;
+        let mut records = query_res
+            .info
+            .extension
+            .attributes
+            .unwrap_or_default()
+            .iter()
+            .map(record_v1)
+            .collect::<Result<Vec<DelegationRecord>, ContractError>>()?;
+
+        // The versions before 0.2.0 wrote the source validator in the
+        // traits of the redelegating NFTs, the destination is the one
+        // set by the owner or the only other validator the hub delegates
+        // at least the redelegated coin to
+        let mut resolved = true;
+        for record in records
+            .iter_mut()
+            .filter(|record| record.status == DisplayType::Redelegating)
+        {
+            let destination = match redelegations.iter().find(|dst| &dst.token_id == token_id) {
+                Some(dst) => Some(dst.validator.clone()),
+                None => {
+                    if alliance_delegations.is_none() {
+                        alliance_delegations = Some(query::alliance_delegations(
+                            deps.querier,
+                            &env.contract.address,
+                        )?);
+                    }
+                    redelegation_destination(
+                        alliance_delegations.as_deref().unwrap_or_default(),
+                        record,
+                    )
+                }
+            };
+            match destination {
+                Some(validator) => record.validator = validator,
+                None => resolved = false,
+            }
+        }
+        if !resolved {
+            migration.unresolved.push(token_id.clone());
+            continue;
+        }
+
+        migrate_records(deps.branch(), &migration, token_id, &records)?;
+        DELEGATIONS.save(deps.storage, token_id, &records)?;
+        migrated += 1;
+    }
+
+    if let Some(last) = token_ids.last() {
+        migration.start_after = Some(last.clone());
+    }
+    if (token_ids.len() as u32) < limit && migration.unresolved.is_empty() {
+        DELEGATIONS_MIGRATION.remove(deps.storage);
+    } else {
+        DELEGATIONS_MIGRATION.save(deps.storage, &migration)?;
+    }
+
+    Ok(migrated)
+}
+
+// The legacy amounts become the shares of the delegations, each one
+// worth the balance of the hub over the total shares. The principal of
+// the legacy unbondings completed before the contract migration is part
+// of the balance kept apart as orphaned rewards so it is released.
+fn migrate_records(
+    deps: DepsMut,
+    migration: &DelegationsMigration,
+    token_id: &str,
+    records: &[DelegationRecord],
+) -> Result<(), ContractError> {
+    for record in records.iter() {
+        match record.status {
+            DisplayType::Delegated | DisplayType::Redelegating => {
+                rewards::add_delegation(deps.storage, token_id, &record.validator, &record.coin)?;
+            }
+            DisplayType::Unbonding => {
+                unbondings::add_unbonding(deps.storage, &record.coin, record.completion_time)?;
+                if record.completion_time < migration.started_at {
+                    ORPHANED_REWARDS.update(
+                        deps.storage,
+                        &record.coin.denom,
+                        |orphaned| -> StdResult<_> {
+                            Ok(orphaned
+                                .unwrap_or_default()
+                                .saturating_sub(record.coin.amount))
+                        },
+                    )?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+// Only other validator the hub delegates at least the redelegated
+// coin to, None when there are none or more than one candidates
+fn redelegation_destination(
+    alliance_delegations: &[(String, Coin)],
+    record: &DelegationRecord,
+) -> Option<String> {
+    let mut candidates = alliance_delegations.iter().filter(|(validator, balance)| {
+        validator != &record.validator
+            && balance.denom == record.coin.denom
+            && balance.amount >= record.coin.amount
+    });
+
+    match (candidates.next(), candidates.next()) {
+        (Some((validator, _)), None) => Some(validator.clone()),
+        _ => None,
+    }
+}
+
+// Parse the NFT trait written by the versions before 0.2.0
+fn record_v1(attr: &CW721Trait) -> Result<DelegationRecord, ContractError> {
+    let invalid = || ContractError::InvalidNftTrait(attr.value.clone());
+    let (amount, denom) = attr
+        .value
+        .split_once(DEFAULT_DELIMITER)
+        .ok_or_else(invalid)?;
+    let amount = Uint128::from_str(amount).map_err(|_| invalid())?;
+
+    Ok(DelegationRecord {
+        validator: attr.trait_type.clone(),
+        coin: Coin::new(amount.u128(), denom),
+        status: DisplayType::from_str(&attr.display_type).map_err(|_| invalid())?,
+        completion_time: attr.timestamp,
+    })
 }
@@ -1,23 +1,233 @@
-use crate::state::CFG;
-use crate::{msg::QueryMsg, ContractError};
+use crate::msg::{
+    PendingRewardsResponse, PositionResponse, PositionsResponse, QueryMsg,
+    SimulateDelegateResponse, SimulateRedelegateResponse, TotalDelegatedResponse,
+    ValidatorDelegation,
+};
+use crate::rewards::{self, ShareRatios};
+use crate::state::{
+    AUTO_COMPOUND, CFG, DELEGATIONS, PAUSE_FLAGS, REWARD_CHECKPOINTS, REWARD_INDEXES,
+};
+use crate::{fees, status, ContractError};
+
+use super::execute;
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::{
     entry_point, to_binary, AllValidatorsResponse, Binary, Deps, Env, QueryRequest, StdResult,
 };
-use cosmwasm_std::{QuerierWrapper, StakingQuery, Validator, WasmQuery};
-use cw721::AllNftInfoResponse;
+use cosmwasm_std::{
+    from_binary, to_vec, Addr, Coin, ContractResult, Empty, Order, QuerierWrapper, StakingQuery,
+    StdError, SystemError, SystemResult, Uint128, Validator, WasmQuery,
+};
+use cw721::{AllNftInfoResponse, TokensResponse};
+use serde::Deserialize;
+use terra_proto_rs::{
+    alliance::alliance::{
+        QueryAllianceDelegationRequest, QueryAllianceDelegationRewardsRequest,
+        QueryAlliancesDelegationsRequest, QueryAlliancesRequest,
+    },
+    cosmos::base::query::v1beta1::PageRequest,
+    cosmos::staking::v1beta1::{QueryParamsRequest, QueryValidatorsRequest},
+    traits::Message,
+};
 
 use cw721_progressive_metadata::{state::Metadata as CW721Metadata, QueryMsg as CW721QueryEmpty};
 
 type CW721Query = CW721QueryEmpty<CW721Metadata>;
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     Ok(match msg {
         QueryMsg::GetConfig {} => to_binary(&CFG.load(deps.storage)?)?,
+        QueryMsg::GetPauseFlags {} => {
+            to_binary(&PAUSE_FLAGS.may_load(deps.storage)?.unwrap_or_default())?
+        }
+        QueryMsg::GetAutoCompound { token_id } => {
+            to_binary(&AUTO_COMPOUND.has(deps.storage, &token_id))?
+        }
+        QueryMsg::Position { token_id } => to_binary(&query_position(deps, &env, token_id)?)?,
+        QueryMsg::PositionsByOwner {
+            owner,
+            start_after,
+            limit,
+        } => to_binary(&query_positions_by_owner(
+            deps,
+            &env,
+            owner,
+            start_after,
+            limit,
+        )?)?,
+        QueryMsg::PendingRewards { token_id } => {
+            to_binary(&query_pending_rewards(deps, &env, token_id)?)?
+        }
+        QueryMsg::TotalDelegated {} => to_binary(&query_total_delegated(deps, &env)?)?,
+        QueryMsg::SimulateDelegate { funds } => to_binary(
+            &query_simulate_delegate(deps, &env, funds)
+                .map_err(|err| StdError::generic_err(err.to_string()))?,
+        )?,
+        QueryMsg::SimulateRedelegate { token_id } => to_binary(
+            &query_simulate_redelegate(deps, &env, token_id)
+                .map_err(|err| StdError::generic_err(err.to_string()))?,
+        )?,
+        QueryMsg::Ownership {} => to_binary(&cw_ownable::get_ownership(deps.storage)?)?,
+    })
+}
+
+fn query_position(deps: Deps, env: &Env, token_id: String) -> StdResult<PositionResponse> {
+    let nft_contract_addr = nft_contract_addr(deps)?;
+    let query_res = all_nft_info(deps.querier, token_id.clone(), nft_contract_addr)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+
+    position(deps, env, token_id, query_res.access.owner)
+}
+
+fn query_positions_by_owner(
+    deps: Deps,
+    env: &Env,
+    owner: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<PositionsResponse> {
+    let nft_contract_addr = nft_contract_addr(deps)?;
+    let msg = to_binary(&CW721Query::Tokens {
+        owner: owner.clone(),
+        start_after,
+        limit,
+    })?;
+    let res: TokensResponse = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: nft_contract_addr,
+        msg,
+    }))?;
+
+    let positions = res
+        .tokens
+        .into_iter()
+        .map(|token_id| position(deps, env, token_id, owner.clone()))
+        .collect::<StdResult<Vec<PositionResponse>>>()?;
+
+    Ok(PositionsResponse { positions })
+}
+
+// The matured records are displayed with the status they will
+// be promoted to by the next execution and the shares with the
+// amounts they are worth, as in the NFT metadata
+fn position(deps: Deps, env: &Env, token_id: String, owner: String) -> StdResult<PositionResponse> {
+    let mut records = DELEGATIONS
+        .may_load(deps.storage, &token_id)?
+        .unwrap_or_default();
+    status::refresh(&mut records, env.block.time)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    let delegations = ShareRatios::new(&env.contract.address)
+        .to_amounts(deps, &records)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+
+    Ok(PositionResponse {
+        token_id,
+        owner,
+        delegations,
+    })
+}
+
+// The rewards accounted to the NFT plus its share of the rewards
+// the hub has not claimed yet, without the reward fee
+fn query_pending_rewards(
+    deps: Deps,
+    env: &Env,
+    token_id: String,
+) -> StdResult<PendingRewardsResponse> {
+    let cfg = CFG.load(deps.storage)?;
+    let records = DELEGATIONS
+        .may_load(deps.storage, &token_id)?
+        .unwrap_or_default();
+
+    let mut rewards = vec![];
+    let mut queried: Vec<(&str, &str)> = vec![];
+    for record in records.iter() {
+        let key = (record.validator.as_str(), record.coin.denom.as_str());
+        if queried.contains(&key)
+            || !REWARD_CHECKPOINTS.has(deps.storage, (&token_id, key.0, key.1))
+        {
+            continue;
+        }
+        queried.push(key);
+
+        let unclaimed = alliance_rewards(deps.querier, &env.contract.address, key.0, key.1)
+            .map_err(|err| StdError::generic_err(err.to_string()))?;
+        let (unclaimed, _) = fees::take_reward_fee(cfg.protocol_fee.as_ref(), unclaimed);
+        let pending = rewards::pending_rewards(deps.storage, &token_id, key.0, key.1, &unclaimed)?;
+        rewards::merge_coins(&mut rewards, pending);
+    }
+
+    Ok(PendingRewardsResponse { rewards })
+}
+
+// The balance delegated by the hub to each validator
+// and denom which reward index has shares
+fn query_total_delegated(deps: Deps, env: &Env) -> StdResult<TotalDelegatedResponse> {
+    let indexes = REWARD_INDEXES
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+
+    let mut delegations = vec![];
+    for ((validator, denom), index) in indexes {
+        if index.total_shares.is_zero() {
+            continue;
+        }
+        let coin = alliance_delegation(deps.querier, &env.contract.address, &validator, &denom)
+            .map_err(|err| StdError::generic_err(err.to_string()))?;
+        delegations.push(ValidatorDelegation { validator, coin });
+    }
+
+    Ok(TotalDelegatedResponse { delegations })
+}
+
+// Run the MsgDelegate logic without saving anything, the validators
+// selected by the pseudo random strategies depend on the block so
+// the execution may select different validators
+fn query_simulate_delegate(
+    deps: Deps,
+    env: &Env,
+    funds: Vec<Coin>,
+) -> Result<SimulateDelegateResponse, ContractError> {
+    let cfg = CFG.load(deps.storage)?;
+    let mut plan = execute::plan_delegate(deps, env, &funds, None)?;
+    let token_id = cfg.minted_nfts.to_string();
+    let delegations = plan.ratios.to_amounts(deps, &plan.records)?;
+
+    Ok(SimulateDelegateResponse {
+        metadata: execute::minted_nft_metadata(&token_id, &delegations),
+        token_id,
+        delegations,
+        refunds: plan.refunds,
+        deposit_fees: plan.deposit_fees,
     })
 }
 
+// Run the MsgRedelegate logic without saving anything
+fn query_simulate_redelegate(
+    deps: Deps,
+    env: &Env,
+    token_id: String,
+) -> Result<SimulateRedelegateResponse, ContractError> {
+    let nft_contract_addr = nft_contract_addr(deps)?;
+    let query_res = all_nft_info(deps.querier, token_id.clone(), nft_contract_addr)?;
+    let mut plan = execute::plan_redelegate(deps, env, &token_id, None)?;
+    let delegations = plan.ratios.to_amounts(deps, &plan.records)?;
+
+    Ok(SimulateRedelegateResponse {
+        metadata: execute::updated_nft_metadata(query_res.info.extension, &delegations),
+        delegations,
+    })
+}
+
+fn nft_contract_addr(deps: Deps) -> StdResult<String> {
+    match CFG.load(deps.storage)?.nft_contract_addr {
+        Some(addr) => Ok(String::from(addr)),
+        None => Err(StdError::generic_err(
+            ContractError::NoNftContractAddress {}.to_string(),
+        )),
+    }
+}
+
 pub fn all_nft_info(
     querier: QuerierWrapper,
     token_id: String,
@@ -34,6 +244,24 @@ pub fn all_nft_info(
     Ok(res)
 }
 
+// Query the NFT contract for the NFTs minted and not burned yet
+pub fn all_tokens(
+    querier: QuerierWrapper,
+    contract_addr: String,
+    start_after: Option<String>,
+    limit: u32,
+) -> Result<Vec<String>, ContractError> {
+    let msg = to_binary(&CW721Query::AllTokens {
+        start_after,
+        limit: Some(limit),
+    })?;
+
+    let res: TokensResponse =
+        querier.query(&QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }))?;
+
+    Ok(res.tokens)
+}
+
 pub fn all_validators(querier: QuerierWrapper) -> Result<Vec<Validator>, ContractError> {
     let res = querier.query(&QueryRequest::Staking(StakingQuery::AllValidators {}));
 
@@ -42,3 +270,256 @@ pub fn all_validators(querier: QuerierWrapper) -> Result<Vec<Validator>, Contrac
         Err(err) => Err(ContractError::Std(err)),
     }
 }
+
+#[derive(Deserialize)]
+struct PageResponse {
+    next_key: Option<Binary>,
+}
+
+// Key of the next page, none once the last page has been read
+fn next_page_key(pagination: Option<PageResponse>) -> Option<Vec<u8>> {
+    match pagination.and_then(|page| page.next_key) {
+        Some(next_key) if !next_key.is_empty() => Some(next_key.to_vec()),
+        _ => None,
+    }
+}
+
+#[derive(Deserialize)]
+struct StakingValidatorsResponse {
+    validators: Vec<StakingValidator>,
+    pagination: Option<PageResponse>,
+}
+
+#[derive(Deserialize)]
+pub struct StakingValidator {
+    pub operator_address: String,
+    #[serde(default)]
+    pub jailed: bool,
+    pub tokens: Uint128,
+}
+
+// Query the x/staking module for the bonded validators which contain
+// the voting power and jailing status, following the pages until the last
+pub fn bonded_validators(querier: QuerierWrapper) -> Result<Vec<StakingValidator>, ContractError> {
+    let mut validators = vec![];
+    let mut key = vec![];
+    loop {
+        let data = QueryValidatorsRequest {
+            status: "BOND_STATUS_BONDED".to_string(),
+            pagination: Some(PageRequest {
+                key,
+                ..Default::default()
+            }),
+        }
+        .encode_to_vec();
+
+        let res: StakingValidatorsResponse = querier.query(&QueryRequest::Stargate {
+            path: "/cosmos.staking.v1beta1.Query/Validators".to_string(),
+            data: Binary::from(data),
+        })?;
+        validators.extend(res.validators);
+
+        match next_page_key(res.pagination) {
+            Some(next_key) => key = next_key,
+            None => return Ok(validators),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AllianceDelegationResponse {
+    delegation: AllianceDelegation,
+}
+
+#[derive(Deserialize)]
+struct AllianceDelegation {
+    balance: Coin,
+}
+
+// Query the x/alliance module for the balance
+// delegated by the delegator to the validator
+pub fn alliance_delegation(
+    querier: QuerierWrapper,
+    delegator: &Addr,
+    validator: &str,
+    denom: &str,
+) -> Result<Coin, ContractError> {
+    let data = QueryAllianceDelegationRequest {
+        delegator_addr: delegator.to_string(),
+        validator_addr: validator.to_string(),
+        denom: denom.to_string(),
+        pagination: None,
+    }
+    .encode_to_vec();
+
+    let res: AllianceDelegationResponse = querier.query(&QueryRequest::Stargate {
+        path: "/alliance.alliance.Query/AllianceDelegation".to_string(),
+        data: Binary::from(data),
+    })?;
+
+    Ok(res.delegation.balance)
+}
+
+#[derive(Deserialize)]
+struct AlliancesDelegationsResponse {
+    delegations: Vec<AlliancesDelegation>,
+    pagination: Option<PageResponse>,
+}
+
+#[derive(Deserialize)]
+struct AlliancesDelegation {
+    delegation: AlliancesDelegationInfo,
+    balance: Coin,
+}
+
+#[derive(Deserialize)]
+struct AlliancesDelegationInfo {
+    validator_address: String,
+}
+
+// Query the x/alliance module for the balances delegated by the
+// delegator to every validator, following the pages until the last
+pub fn alliance_delegations(
+    querier: QuerierWrapper,
+    delegator: &Addr,
+) -> Result<Vec<(String, Coin)>, ContractError> {
+    let mut delegations = vec![];
+    let mut key = vec![];
+    loop {
+        let data = QueryAlliancesDelegationsRequest {
+            delegator_addr: delegator.to_string(),
+            pagination: Some(PageRequest {
+                key,
+                ..Default::default()
+            }),
+        }
+        .encode_to_vec();
+
+        let res: AlliancesDelegationsResponse = querier.query(&QueryRequest::Stargate {
+            path: "/alliance.alliance.Query/AlliancesDelegation".to_string(),
+            data: Binary::from(data),
+        })?;
+        delegations.extend(
+            res.delegations
+                .into_iter()
+                .map(|res| (res.delegation.validator_address, res.balance)),
+        );
+
+        match next_page_key(res.pagination) {
+            Some(next_key) => key = next_key,
+            None => return Ok(delegations),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AlliancesResponse {
+    alliances: Vec<AllianceAsset>,
+    pagination: Option<PageResponse>,
+}
+
+#[derive(Deserialize)]
+struct AllianceAsset {
+    denom: String,
+}
+
+// Query the x/alliance module for the denoms whitelisted
+// as alliance assets, following the pages until the last
+pub fn alliance_denoms(querier: QuerierWrapper) -> Result<Vec<String>, ContractError> {
+    let mut denoms = vec![];
+    let mut key = vec![];
+    loop {
+        let data = QueryAlliancesRequest {
+            pagination: Some(PageRequest {
+                key,
+                ..Default::default()
+            }),
+        }
+        .encode_to_vec();
+
+        let res: AlliancesResponse = querier.query(&QueryRequest::Stargate {
+            path: "/alliance.alliance.Query/Alliances".to_string(),
+            data: Binary::from(data),
+        })?;
+        denoms.extend(res.alliances.into_iter().map(|asset| asset.denom));
+
+        match next_page_key(res.pagination) {
+            Some(next_key) => key = next_key,
+            None => return Ok(denoms),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct StakingParamsResponse {
+    params: StakingParams,
+}
+
+#[derive(Deserialize)]
+struct StakingParams {
+    unbonding_time: String,
+}
+
+#[derive(Deserialize)]
+struct AllianceRewardsResponse {
+    rewards: Vec<Coin>,
+}
+
+// Query the x/alliance module for the rewards the
+// delegator has not claimed from the validator yet
+pub fn alliance_rewards(
+    querier: QuerierWrapper,
+    delegator: &Addr,
+    validator: &str,
+    denom: &str,
+) -> Result<Vec<Coin>, ContractError> {
+    let data = QueryAllianceDelegationRewardsRequest {
+        delegator_addr: delegator.to_string(),
+        validator_addr: validator.to_string(),
+        denom: denom.to_string(),
+        pagination: None,
+    }
+    .encode_to_vec();
+
+    let res: AllianceRewardsResponse = querier.query(&QueryRequest::Stargate {
+        path: "/alliance.alliance.Query/AllianceDelegationRewards".to_string(),
+        data: Binary::from(data),
+    })?;
+
+    Ok(res.rewards)
+}
+
+// Query the x/staking module for the unbonding time in seconds,
+// which can be changed by governance at any moment. None is returned
+// when the chain does not support the query or its response cannot
+// be parsed, any other error of the querier is returned as it is
+pub fn unbonding_seconds(querier: QuerierWrapper) -> Result<Option<u64>, ContractError> {
+    let data = QueryParamsRequest {}.encode_to_vec();
+    let request = to_vec(&QueryRequest::<Empty>::Stargate {
+        path: "/cosmos.staking.v1beta1.Query/Params".to_string(),
+        data: Binary::from(data),
+    })?;
+
+    let res: StakingParamsResponse = match querier.raw_query(&request) {
+        SystemResult::Ok(ContractResult::Ok(value)) => match from_binary(&value) {
+            Ok(res) => res,
+            Err(_) => return Ok(None),
+        },
+        SystemResult::Err(SystemError::UnsupportedRequest { .. }) => return Ok(None),
+        SystemResult::Err(err) => {
+            return Err(StdError::generic_err(format!("Querier system error: {err}")).into())
+        }
+        SystemResult::Ok(ContractResult::Err(err)) => {
+            return Err(StdError::generic_err(format!("Querier contract error: {err}")).into())
+        }
+    };
+
+    // The duration is encoded as seconds with an optional
+    // fraction followed by the "s" suffix (e.g. "1814400s")
+    Ok(res
+        .params
+        .unbonding_time
+        .strip_suffix('s')
+        .and_then(|seconds| seconds.split('.').next())
+        .and_then(|seconds| seconds.parse::<u64>().ok()))
+}
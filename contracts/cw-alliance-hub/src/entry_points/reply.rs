@@ -1,22 +1,53 @@
 use std::ops::Add;
 
-use crate::state::CFG;
+use crate::fees;
+use crate::rewards::{self, ShareRatios};
+use crate::state::{
+    DelegationRecord, DisplayType, CFG, CLAIM_REWARDS_TMP, COMPLETION_TIMES_TMP, COMPOUND_TMP,
+    DELEGATIONS, PAID_REWARDS_TMP,
+};
+use crate::unbondings;
+use crate::ContractError;
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::{entry_point, Addr, DepsMut, Env, Response, StdResult};
-use cosmwasm_std::{Reply, StdError};
+use cosmwasm_std::{
+    to_binary, BankMsg, Binary, Coin, CosmosMsg, Reply, StdError, SubMsg, Timestamp, WasmMsg,
+};
+use terra_proto_rs::{
+    alliance::alliance::MsgDelegate,
+    cosmos::base::v1beta1::Coin as CosmosNativeCoin,
+    cosmos::staking::v1beta1::{MsgBeginRedelegateResponse, MsgUndelegateResponse},
+    traits::Message,
+};
 
 use super::constants::{
-    INSTANTIATE_REPLY_ID, MINT_NFT_REPLY_ID, REDEEM_BOND_REPLY_ID, REDELEGATE_REPLY_ID,
-    UNBONDING_NFT_REPLY_ID,
+    CLAIM_REWARDS_REPLY_ID, COMPLETION_TIME_REPLY_ID, COMPOUND_REPLY_ID, INSTANTIATE_REPLY_ID,
+    MERGE_NFT_REPLY_ID, MIGRATE_COLLECTION_REPLY_ID, MINT_NFT_REPLY_ID, PAID_REWARDS_REPLY_ID,
+    REDEEM_BOND_REPLY_ID, REDELEGATE_REPLY_ID, REFRESH_NFT_REPLY_ID, SPLIT_NFT_REPLY_ID,
+    TOP_UP_NFT_REPLY_ID, UNBONDING_NFT_REPLY_ID,
 };
+use super::{
+    execute::{displayed_records, generate_update_nft_msg, merge_delegated_record},
+    query,
+};
+
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> StdResult<Response> {
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
     match msg.id {
         INSTANTIATE_REPLY_ID => handle_instantiate_reply(deps, msg),
         MINT_NFT_REPLY_ID => handle_mint_nft_reply_id(deps, msg),
-        REDELEGATE_REPLY_ID => handle_redelegate_reply_id(msg),
-        UNBONDING_NFT_REPLY_ID => handle_unbonding_reply_id(msg),
+        REDELEGATE_REPLY_ID => handle_update_nft_reply("redelegate_reply", msg),
+        UNBONDING_NFT_REPLY_ID => handle_update_nft_reply("start_unbonding_reply", msg),
         REDEEM_BOND_REPLY_ID => handle_redeem_bond(msg),
+        CLAIM_REWARDS_REPLY_ID => handle_claim_rewards_reply(deps, env),
+        TOP_UP_NFT_REPLY_ID => handle_update_nft_reply("top_up_reply", msg),
+        SPLIT_NFT_REPLY_ID => handle_update_nft_reply("split_reply", msg),
+        MERGE_NFT_REPLY_ID => handle_update_nft_reply("merge_reply", msg),
+        REFRESH_NFT_REPLY_ID => handle_update_nft_reply("refresh_reply", msg),
+        COMPLETION_TIME_REPLY_ID => handle_completion_time_reply(deps, env, msg),
+        COMPOUND_REPLY_ID => handle_compound_reply(deps, env),
+        MIGRATE_COLLECTION_REPLY_ID => handle_migrate_collection_reply(msg),
+        PAID_REWARDS_REPLY_ID => handle_paid_rewards_reply(deps, env),
         id => Err(StdError::generic_err(format!("Unknown reply id: {}", id))),
     }
 }
@@ -83,28 +114,43 @@ fn handle_mint_nft_reply_id(deps: DepsMut, msg: Reply) -> StdResult<Response> {
         .add_attribute("minted_nfts", cfg.minted_nfts.to_string()))
 }
 
-fn handle_unbonding_reply_id(msg: Reply) -> StdResult<Response> {
+fn handle_migrate_collection_reply(msg: Reply) -> StdResult<Response> {
     // Unwrap the result, if it is an error, respond with the error
     if msg.result.is_err() {
-        let msg = "Error update nft: "
+        let msg = "Error migrating nft contract: "
             .to_string()
             .add(&msg.result.unwrap_err());
         return Err(StdError::generic_err(msg));
     }
 
-    Ok(Response::new().add_attribute("method", "start_unbonding_reply"))
+    // Report the version the NFT contract migrated from when it is
+    // in the events of the migration (cw721 migrate attribute)
+    let result = msg.result.into_result().map_err(StdError::generic_err)?;
+    let res = Response::new().add_attribute("method", "migrate_collection_reply");
+    let migrated_from = result
+        .events
+        .iter()
+        .filter(|event| event.ty == "wasm")
+        .flat_map(|event| event.attributes.iter())
+        .find(|attr| attr.key == "migrated_from_version");
+    Ok(match migrated_from {
+        Some(attr) => res.add_attribute("migrated_from_version", attr.value.clone()),
+        None => res,
+    })
 }
 
-fn handle_redelegate_reply_id(msg: Reply) -> StdResult<Response> {
+// The NFT metadata updates only fail or succeed, the method
+// reports which operation updated the NFT metadata
+fn handle_update_nft_reply(method: &str, msg: Reply) -> StdResult<Response> {
     // Unwrap the result, if it is an error, respond with the error
     if msg.result.is_err() {
-        let msg = "Error update nft:"
+        let msg = "Error update nft: "
             .to_string()
             .add(&msg.result.unwrap_err());
         return Err(StdError::generic_err(msg));
     }
 
-    Ok(Response::new().add_attribute("method", "redelegate_reply"))
+    Ok(Response::new().add_attribute("method", method))
 }
 
 fn handle_redeem_bond(msg: Reply) -> StdResult<Response> {
@@ -118,3 +164,441 @@ fn handle_redeem_bond(msg: Reply) -> StdResult<Response> {
 
     Ok(Response::new().add_attribute("method", "redeem_bond_reply"))
 }
+
+fn handle_claim_rewards_reply(deps: DepsMut, env: Env) -> StdResult<Response> {
+    let mut tmp = CLAIM_REWARDS_TMP.load(deps.storage)?;
+    let balances = deps.querier.query_all_balances(&env.contract.address)?;
+    if tmp.delegations.is_empty() {
+        return Err(StdError::generic_err("No pending claim rewards found"));
+    }
+    let (validator, coin) = tmp.delegations.remove(0);
+    let claimed_rewards = claimed_since(&tmp.balances, &balances);
+
+    // The reward fee is taken from the claimed rewards and the claimed
+    // delegations share the rest of the rewards with the other NFTs
+    // delegating to the same validator and denom, so only the NFT
+    // share is sent
+    let cfg = CFG.load(deps.storage)?;
+    let protocol_fee = cfg.protocol_fee.as_ref();
+    let (reward_fees, orphaned) = rewards::add_rewards(
+        deps.storage,
+        protocol_fee,
+        &validator,
+        &coin.denom,
+        claimed_rewards,
+    )?;
+    let rewards = rewards::settle_rewards(deps.storage, &tmp.token_id, &validator, &coin.denom)?;
+
+    tmp.balances = balances_after(balances, &[&reward_fees, &orphaned, &rewards]);
+    CLAIM_REWARDS_TMP.save(deps.storage, &tmp)?;
+
+    let res = Response::new()
+        .add_attribute("method", "claim_rewards_reply")
+        .add_attribute("recipient", tmp.owner.to_string());
+    let res = fees::add_fee_msg(res, "reward_fee", protocol_fee, reward_fees);
+    let res = fees::add_fee_msg(res, "orphaned_rewards", protocol_fee, orphaned);
+    if rewards.is_empty() {
+        return Ok(res);
+    }
+
+    Ok(res.add_message(BankMsg::Send {
+        to_address: tmp.owner.to_string(),
+        amount: rewards,
+    }))
+}
+
+fn handle_paid_rewards_reply(deps: DepsMut, env: Env) -> StdResult<Response> {
+    let res = Response::new().add_attribute("method", "paid_rewards_reply");
+    credit_paid_rewards(deps, &env, res)
+}
+
+// Credit the rewards paid by x/alliance when it executed the delegation
+// submessage to the reward index of the delegation, then move the shares
+// of the NFTs so they only earn the rewards from now on
+fn credit_paid_rewards(deps: DepsMut, env: &Env, res: Response) -> StdResult<Response> {
+    let mut tmp = PAID_REWARDS_TMP.load(deps.storage)?;
+    let balances = deps.querier.query_all_balances(&env.contract.address)?;
+    if tmp.moves.is_empty() {
+        return Err(StdError::generic_err("No pending paid rewards found"));
+    }
+    let shares_move = tmp.moves.remove(0);
+
+    // The coins delegated by the submessage left the hub
+    // balance so they are not mistaken for rewards
+    let prev_balances = balances_after(tmp.balances, &[&shares_move.spent]);
+    let cfg = CFG.load(deps.storage)?;
+    let protocol_fee = cfg.protocol_fee.as_ref();
+    let (reward_fees, orphaned) = rewards::add_rewards(
+        deps.storage,
+        protocol_fee,
+        &shares_move.validator,
+        &shares_move.denom,
+        claimed_since(&prev_balances, &balances),
+    )?;
+
+    let mut settled_rewards = vec![];
+    for (token_id, validator, shares) in shares_move.removed.iter() {
+        let rewards = rewards::remove_delegation(deps.storage, token_id, validator, shares)?;
+        rewards::merge_coins(&mut settled_rewards, rewards);
+    }
+    for (token_id, validator, shares) in shares_move.added.iter() {
+        let rewards = rewards::add_delegation(deps.storage, token_id, validator, shares)?;
+        rewards::merge_coins(&mut settled_rewards, rewards);
+    }
+
+    tmp.balances = balances_after(balances, &[&reward_fees, &orphaned, &settled_rewards]);
+    PAID_REWARDS_TMP.save(deps.storage, &tmp)?;
+
+    let res = fees::add_fee_msg(res, "reward_fee", protocol_fee, reward_fees);
+    let res = fees::add_fee_msg(res, "orphaned_rewards", protocol_fee, orphaned);
+    if settled_rewards.is_empty() {
+        return Ok(res);
+    }
+
+    Ok(res.add_message(BankMsg::Send {
+        to_address: tmp.owner.to_string(),
+        amount: settled_rewards,
+    }))
+}
+
+fn handle_compound_reply(deps: DepsMut, env: Env) -> StdResult<Response> {
+    let mut tmp = COMPOUND_TMP.load(deps.storage)?;
+    let balances = deps.querier.query_all_balances(&env.contract.address)?;
+    if tmp.delegations.is_empty() {
+        return Err(StdError::generic_err("No pending compound found"));
+    }
+    let delegation = tmp.delegations.remove(0);
+    let token_id = delegation.token_id;
+    let validator = delegation.validator;
+    let denom = delegation.coin.denom;
+
+    // Split the claimed rewards as when claiming them
+    let cfg = CFG.load(deps.storage)?;
+    let protocol_fee = cfg.protocol_fee.as_ref();
+    let (reward_fees, orphaned) = rewards::add_rewards(
+        deps.storage,
+        protocol_fee,
+        &validator,
+        &denom,
+        claimed_since(&tmp.balances, &balances),
+    )?;
+    let rewards = rewards::settle_rewards(deps.storage, &token_id, &validator, &denom)?;
+
+    // Only the alliance assets can be delegated,
+    // the rest of the rewards are sent to the owner
+    let denoms = query::alliance_denoms(deps.querier)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    let (compounded, rewards): (Vec<Coin>, Vec<Coin>) = rewards
+        .into_iter()
+        .partition(|coin| denoms.contains(&coin.denom));
+    tmp.balances = balances_after(balances, &[&reward_fees, &orphaned, &compounded, &rewards]);
+    COMPOUND_TMP.save(deps.storage, &tmp)?;
+
+    let res = Response::new()
+        .add_attribute("method", "compound_reply")
+        .add_attribute("token_id", token_id.clone());
+    let res = fees::add_fee_msg(res, "reward_fee", protocol_fee, reward_fees);
+    let mut res = fees::add_fee_msg(res, "orphaned_rewards", protocol_fee, orphaned);
+    if !compounded.is_empty() {
+        // Delegate the rewards to the same validator growing the
+        // delegated records of the NFT and its reward checkpoint
+        // with the shares the rewards are worth. The rewards of this
+        // delegation were just claimed so MsgDelegate pays none
+        let mut ratios = ShareRatios::new(&env.contract.address);
+        let mut records = DELEGATIONS.load(deps.storage, &token_id)?;
+        for coin in compounded.iter() {
+            let shares = ratios
+                .to_shares(deps.as_ref(), &validator, coin)
+                .map_err(|err| StdError::generic_err(err.to_string()))?;
+            rewards::add_delegation(deps.storage, &token_id, &validator, &shares)?;
+            merge_delegated_record(
+                &mut records,
+                DelegationRecord {
+                    validator: validator.clone(),
+                    coin: shares,
+                    status: DisplayType::Delegated,
+                    completion_time: env.block.time,
+                },
+            );
+            let msg = MsgDelegate {
+                delegator_address: env.contract.address.to_string(),
+                validator_address: validator.clone(),
+                amount: Some(CosmosNativeCoin {
+                    denom: coin.denom.clone(),
+                    amount: coin.amount.to_string(),
+                }),
+            };
+            res = res.add_message(CosmosMsg::Stargate {
+                type_url: "/alliance.alliance.MsgDelegate".to_string(),
+                value: Binary::from(msg.encode_to_vec()),
+            });
+        }
+        DELEGATIONS.save(deps.storage, &token_id, &records)?;
+
+        let nft_contract_addr = match cfg.nft_contract_addr {
+            Some(addr) => String::from(addr),
+            None => {
+                return Err(StdError::generic_err(
+                    ContractError::NoNftContractAddress {}.to_string(),
+                ))
+            }
+        };
+        let query_res =
+            query::all_nft_info(deps.querier, token_id.clone(), nft_contract_addr.clone())
+                .map_err(|err| StdError::generic_err(err.to_string()))?;
+        let records = ratios
+            .to_amounts(deps.as_ref(), &records)
+            .map_err(|err| StdError::generic_err(err.to_string()))?;
+        let msg = generate_update_nft_msg(query_res.info.extension, &records, token_id);
+        let value = compounded
+            .iter()
+            .map(|coin| coin.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        res = res
+            .add_attribute("compounded", value)
+            .add_submessage(SubMsg::reply_always(
+                WasmMsg::Execute {
+                    contract_addr: nft_contract_addr,
+                    msg: to_binary(&msg)?,
+                    funds: vec![],
+                },
+                REFRESH_NFT_REPLY_ID,
+            ));
+    }
+    if rewards.is_empty() {
+        return Ok(res);
+    }
+
+    Ok(res.add_message(BankMsg::Send {
+        to_address: delegation.owner,
+        amount: rewards,
+    }))
+}
+
+// Rewards received by the hub since the previous balance snapshot
+fn claimed_since(prev_balances: &[Coin], balances: &[Coin]) -> Vec<Coin> {
+    balances
+        .iter()
+        .filter_map(|balance| {
+            let prev_amount = prev_balances
+                .iter()
+                .find(|prev| prev.denom == balance.denom)
+                .map(|prev| prev.amount)
+                .unwrap_or_default();
+
+            if balance.amount > prev_amount {
+                Some(Coin {
+                    denom: balance.denom.clone(),
+                    amount: balance.amount - prev_amount,
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+// Balance snapshot for the next reply of the same execution, the
+// coins sent by the reply leave the hub before the next claim so
+// the next reply only accounts its own rewards
+fn balances_after(balances: Vec<Coin>, sent: &[&Vec<Coin>]) -> Vec<Coin> {
+    balances
+        .into_iter()
+        .map(|mut balance| {
+            for coin in sent.iter().flat_map(|coins| coins.iter()) {
+                if coin.denom == balance.denom {
+                    balance.amount = balance.amount.saturating_sub(coin.amount);
+                }
+            }
+            balance
+        })
+        .collect()
+}
+
+fn handle_completion_time_reply(mut deps: DepsMut, env: Env, msg: Reply) -> StdResult<Response> {
+    let res = Response::new().add_attribute("method", "completion_time_reply");
+    let res = credit_paid_rewards(deps.branch(), &env, res)?;
+    let mut tmp = COMPLETION_TIMES_TMP.load(deps.storage)?;
+    if tmp.is_empty() {
+        return Err(StdError::generic_err("No pending completion time found"));
+    }
+    let pending = tmp.remove(0);
+    COMPLETION_TIMES_TMP.save(deps.storage, &tmp)?;
+
+    // Decode the completion time from the message response, or find it
+    // in the event emitted by x/alliance when the response is empty,
+    // the estimate from the staking params is kept when both miss it
+    let result = msg.result.into_result().map_err(StdError::generic_err)?;
+    let (response_time, event_type) = match pending.status {
+        DisplayType::Redelegating => (
+            result
+                .data
+                .and_then(|data| MsgBeginRedelegateResponse::decode(data.as_slice()).ok())
+                .and_then(|res| res.completion_time),
+            "alliance.alliance.RedelegateAllianceEvent",
+        ),
+        _ => (
+            result
+                .data
+                .and_then(|data| MsgUndelegateResponse::decode(data.as_slice()).ok())
+                .and_then(|res| res.completion_time),
+            "alliance.alliance.UndelegateAllianceEvent",
+        ),
+    };
+    let completion_time = match response_time {
+        Some(time) => u64::try_from(time.seconds)
+            .ok()
+            .zip(u64::try_from(time.nanos).ok())
+            .map(|(seconds, nanos)| Timestamp::from_seconds(seconds).plus_nanos(nanos)),
+        None => result
+            .events
+            .iter()
+            .filter(|event| event.ty == event_type)
+            .flat_map(|event| event.attributes.iter())
+            .find(|attr| attr.key == "completion_time")
+            .and_then(|attr| parse_timestamp(&attr.value)),
+    };
+
+    let completion_time = match completion_time {
+        Some(time) => time,
+        None => return Ok(res),
+    };
+    let mut records = DELEGATIONS.load(deps.storage, &pending.token_id)?;
+    let record = records.iter_mut().find(|record| {
+        record.validator == pending.validator
+            && record.coin.denom == pending.denom
+            && record.status == pending.status
+            && record.completion_time == pending.completion_time
+    });
+    match record {
+        Some(record) => {
+            if record.status == DisplayType::Unbonding {
+                unbondings::confirm_unbonding(
+                    deps.storage,
+                    &record.coin,
+                    record.completion_time,
+                    completion_time,
+                )?;
+            }
+            if record.completion_time == completion_time {
+                return Ok(res);
+            }
+            record.completion_time = completion_time;
+        }
+        None => return Ok(res),
+    }
+    DELEGATIONS.save(deps.storage, &pending.token_id, &records)?;
+
+    // Update the NFT metadata with the completion time of the chain
+    let nft_contract_addr = match CFG.load(deps.storage)?.nft_contract_addr {
+        Some(addr) => String::from(addr),
+        None => {
+            return Err(StdError::generic_err(
+                ContractError::NoNftContractAddress {}.to_string(),
+            ))
+        }
+    };
+    let query_res = query::all_nft_info(
+        deps.querier,
+        pending.token_id.clone(),
+        nft_contract_addr.clone(),
+    )
+    .map_err(|err| StdError::generic_err(err.to_string()))?;
+    let records = displayed_records(deps.as_ref(), &env, &records)
+        .map_err(|err| StdError::generic_err(err.to_string()))?;
+    let msg = generate_update_nft_msg(query_res.info.extension, &records, pending.token_id);
+
+    Ok(res
+        .add_attribute("completion_time", completion_time.to_string())
+        .add_submessage(SubMsg::reply_always(
+            WasmMsg::Execute {
+                contract_addr: nft_contract_addr,
+                msg: to_binary(&msg)?,
+                funds: vec![],
+            },
+            REFRESH_NFT_REPLY_ID,
+        )))
+}
+
+// Parse the RFC 3339 timestamps of the typed events, which are JSON
+// encoded (e.g. "\"2023-05-01T10:00:00.5Z\"") and can have a UTC offset
+pub fn parse_timestamp(value: &str) -> Option<Timestamp> {
+    let value = value.trim_matches('"');
+    let (date, time) = value.split_once(['T', 't'])?;
+    let (time, offset) = match time.strip_suffix(['Z', 'z']) {
+        Some(time) => (time, 0),
+        None => {
+            let index = time.rfind(['+', '-'])?;
+            let sign = if time[index..].starts_with('-') {
+                -1
+            } else {
+                1
+            };
+            let (hours, minutes) = time[index + 1..].split_once(':')?;
+            let (hours, minutes) = (parse_part(hours, 2)?, parse_part(minutes, 2)?);
+            if hours > 23 || minutes > 59 {
+                return None;
+            }
+            (&time[..index], sign * (hours * 3_600 + minutes * 60))
+        }
+    };
+    let (time, nanos) = match time.split_once('.') {
+        Some((time, fraction)) if !fraction.is_empty() && fraction.len() <= 9 => {
+            (time, parse_part(&format!("{:0<9}", fraction), 9)? as u64)
+        }
+        Some(_) => return None,
+        None => (time, 0),
+    };
+    let (year, month, day) = match &date.split('-').collect::<Vec<&str>>()[..] {
+        [year, month, day] => (
+            parse_part(year, 4)?,
+            parse_part(month, 2)?,
+            parse_part(day, 2)?,
+        ),
+        _ => return None,
+    };
+    let (hour, minute, second) = match &time.split(':').collect::<Vec<&str>>()[..] {
+        [hour, minute, second] => (
+            parse_part(hour, 2)?,
+            parse_part(minute, 2)?,
+            parse_part(second, 2)?,
+        ),
+        _ => return None,
+    };
+    let leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days_in_month = match month {
+        2 if leap_year => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+
+    // Days since the unix epoch of the civil date
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86_400 + hour * 3_600 + minute * 60 + second - offset;
+    Some(Timestamp::from_seconds(u64::try_from(seconds).ok()?).plus_nanos(nanos))
+}
+
+// Parse a part of the timestamp with the exact number of digits
+fn parse_part(value: &str, digits: usize) -> Option<i64> {
+    if value.len() != digits || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+
+    value.parse().ok()
+}
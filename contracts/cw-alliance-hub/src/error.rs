@@ -1,4 +1,5 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Decimal, StdError};
+use cw_ownable::OwnershipError;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -6,6 +7,9 @@ pub enum ContractError {
     #[error("{0}")]
     Std(#[from] StdError),
 
+    #[error("{0}")]
+    Ownership(#[from] OwnershipError),
+
     #[error("Unauthorized NFT owner, expected '{0}', received '{1}'")]
     UnauthorizedNFTOwnere(String, String),
 
@@ -18,12 +22,90 @@ pub enum ContractError {
     #[error("Cannot unbond the '{0}' NFT")]
     UnbondingImpossible(String),
 
+    #[error("Not enough delegated to take '{0}' from the '{1}' NFT")]
+    InsufficientDelegation(String, String),
+
     #[error("Cannot redelegate the '{0}' NFT")]
     RedelegatingImpossible(String),
 
+    #[error("Cannot top up the '{0}' NFT")]
+    TopUpImpossible(String),
+
+    #[error("Cannot split the '{0}' NFT")]
+    SplitImpossible(String),
+
+    #[error("Cannot merge the '{0}' NFT")]
+    MergeImpossible(String),
+
+    #[error("Cannot move a delegation from '{0}' to '{1}'")]
+    IllegalTransition(String, String),
+
+    #[error("The '{0}' delegation does not complete until {1}")]
+    StatusNotCompleted(String, u64),
+
+    #[error("Auto compound is not enabled for the '{0}' NFT")]
+    AutoCompoundDisabled(String),
+
     #[error("Cannot claim rewards for the '{0}' NFT")]
     ClaimRewardsImpossible(String),
 
+    #[error("Denom '{0}' is not whitelisted as an alliance asset")]
+    UnsupportedDenom(String),
+
+    #[error("Validator '{0}' is not active or not allowed by the hub")]
+    ValidatorNotAllowed(String),
+
+    #[error("Validators weights must be greater than zero and not repeated")]
+    InvalidValidatorWeights {},
+
+    #[error("The sum of the validators weights overflows")]
+    ValidatorWeightsOverflow {},
+
+    #[error("Invalid validator operator address '{0}'")]
+    InvalidValidatorAddress(String),
+
+    #[error("Validator '{0}' is repeated")]
+    RepeatedValidator(String),
+
+    #[error("The curated validators strategy needs at least one validator")]
+    NoCuratedValidators {},
+
+    #[error("Cannot migrate from the '{0}' contract")]
+    InvalidContractName(String),
+
+    #[error("Invalid contract version '{0}'")]
+    InvalidContractVersion(String),
+
+    #[error("Cannot migrate from version {0} to the lower version {1}")]
+    MigrationDowngrade(String, String),
+
+    #[error("The owner of the hub is required to migrate from a hub without owner")]
+    MigrationOwnerRequired {},
+
+    #[error("Cannot migrate the NFT trait with value '{0}'")]
+    InvalidNftTrait(String),
+
+    #[error("The delegations are being migrated from the previous version")]
+    DelegationsMigrationPending {},
+
+    #[error("There are no delegations to migrate")]
+    NoDelegationsMigration {},
+
+    #[error("The {0} protocol fee cannot exceed {1}")]
+    ProtocolFeeTooHigh(String, Decimal),
+
+    #[error("The {0} are paused")]
+    Paused(String),
+
+    #[error("The NFT '{0}' has already been redeemed")]
+    AlreadyRedeemed(String),
+
+    #[error("The delegation of the hub to '{0}' has been slashed completely")]
+    DelegationSlashed(String),
+
+    #[error("The unbonded '{0}' has not been returned to the hub yet")]
+    UnbondedFundsNotReceived(String),
+
     #[error("Something went wrong quering the validatos of the network")]
     NoValidatorsFound {},
 
@@ -0,0 +1,99 @@
+use cosmwasm_std::{Api, BankMsg, Coin, Decimal, Response};
+
+use crate::{entry_points::constants::MAX_PROTOCOL_FEE_PERCENT, state::ProtocolFee, ContractError};
+
+// Validate the treasury and the fees of the protocol fee,
+// the protocol fee is removed when both fees are zero
+pub fn validate_protocol_fee(
+    api: &dyn Api,
+    protocol_fee: Option<ProtocolFee>,
+) -> Result<Option<ProtocolFee>, ContractError> {
+    let protocol_fee = match protocol_fee {
+        Some(fee) if !fee.reward_fee.is_zero() || !fee.deposit_fee.is_zero() => fee,
+        _ => return Ok(None),
+    };
+
+    let max_fee = Decimal::percent(MAX_PROTOCOL_FEE_PERCENT);
+    if protocol_fee.reward_fee > max_fee {
+        return Err(ContractError::ProtocolFeeTooHigh(
+            String::from("reward"),
+            max_fee,
+        ));
+    }
+    if protocol_fee.deposit_fee > max_fee {
+        return Err(ContractError::ProtocolFeeTooHigh(
+            String::from("deposit"),
+            max_fee,
+        ));
+    }
+    api.addr_validate(protocol_fee.treasury.as_str())?;
+
+    Ok(Some(protocol_fee))
+}
+
+// Take the deposit fee from the funds before delegating them
+pub fn take_deposit_fee(
+    protocol_fee: Option<&ProtocolFee>,
+    funds: Vec<Coin>,
+) -> (Vec<Coin>, Vec<Coin>) {
+    match protocol_fee {
+        Some(fee) => take_fee(&funds, fee.deposit_fee),
+        None => (funds, vec![]),
+    }
+}
+
+// Take the reward fee from the rewards claimed by the hub
+// before splitting them between the NFTs
+pub fn take_reward_fee(
+    protocol_fee: Option<&ProtocolFee>,
+    rewards: Vec<Coin>,
+) -> (Vec<Coin>, Vec<Coin>) {
+    match protocol_fee {
+        Some(fee) => take_fee(&rewards, fee.reward_fee),
+        None => (rewards, vec![]),
+    }
+}
+
+// Split the coins between the rest of the coins and the
+// fee, the fee is rounded down in favor of the users
+fn take_fee(coins: &[Coin], fee: Decimal) -> (Vec<Coin>, Vec<Coin>) {
+    let mut rest = vec![];
+    let mut fees = vec![];
+    for coin in coins.iter() {
+        let fee_amount = coin.amount * fee;
+        if !fee_amount.is_zero() {
+            fees.push(Coin::new(fee_amount.u128(), coin.denom.clone()));
+        }
+        rest.push(Coin::new(
+            (coin.amount - fee_amount).u128(),
+            coin.denom.clone(),
+        ));
+    }
+
+    (rest, fees)
+}
+
+// Send the fees, or the orphaned rewards, to the treasury
+// and report them in the attribute of the response
+pub fn add_fee_msg(
+    res: Response,
+    attribute: &str,
+    protocol_fee: Option<&ProtocolFee>,
+    fees: Vec<Coin>,
+) -> Response {
+    let protocol_fee = match protocol_fee {
+        Some(fee) if !fees.is_empty() => fee,
+        _ => return res,
+    };
+
+    let value = fees
+        .iter()
+        .map(|coin| coin.to_string())
+        .collect::<Vec<String>>()
+        .join(",");
+    res.add_attribute(attribute, value)
+        .add_message(BankMsg::Send {
+            to_address: protocol_fee.treasury.to_string(),
+            amount: fees,
+        })
+}
@@ -7,8 +7,13 @@ pub mod entry_points {
     pub mod reply;
 }
 mod error;
+pub mod fees;
 pub mod msg;
+pub mod rewards;
 pub mod state;
+pub mod status;
+pub mod unbondings;
+pub mod validators;
 pub use crate::error::ContractError;
 
 #[cfg(test)]
@@ -1,11 +1,39 @@
-use crate::state::Cfg;
+use crate::state::{
+    Cfg, DelegationRecord, PauseFlags, ProtocolFee, RedeemedNft, SplitMode, ValidatorStrategy,
+};
 use cosmwasm_schema::{cw_serde, QueryResponses};
+use cosmwasm_std::{Binary, Coin, Uint128};
+use cw721_progressive_metadata::state::Metadata as CW721Metadata;
+use cw_ownable::{cw_ownable_execute, cw_ownable_query};
 
 #[cw_serde]
 pub struct InstantiateMsg {
+    /// Owner of the hub allowed to update the config,
+    /// the sender of the instantiate by default
+    pub owner: Option<String>,
     pub cw721_code_id: u64,
+    /// Used to estimate the completion times only when the
+    /// unbonding time cannot be read from the staking params
     pub cw721_unbonding_seconds: u64,
     pub cw721_collection: CW721Collection,
+    /// Strategy to select the validators, pseudo random by default
+    pub validator_strategy: Option<ValidatorStrategy>,
+    /// How each deposited coin is divided, single validator by default
+    pub split_mode: Option<SplitMode>,
+    /// Maximum validators each deposited coin is divided between, 1 by default
+    pub max_validators_per_denom: Option<u64>,
+    /// Operator addresses of the validators the users can choose,
+    /// any active validator when not set or empty
+    pub validators_allowlist: Option<Vec<String>>,
+    /// Refund the denoms that are not alliance assets instead of
+    /// failing when the deposit has other denoms to delegate
+    pub refund_unsupported_denoms: Option<bool>,
+    /// What happens to the NFT once all its delegations
+    /// have been redeemed, locked by default
+    pub redeemed_nft: Option<RedeemedNft>,
+    /// Fee taken from the claimed rewards and the
+    /// deposits and sent to the treasury, none by default
+    pub protocol_fee: Option<ProtocolFee>,
 }
 
 #[cw_serde]
@@ -16,23 +44,217 @@ pub struct CW721Collection {
     pub symbol: String,
 }
 
+#[cw_ownable_execute]
 #[cw_serde]
 pub enum ExecuteMsg {
-    MsgDelegate {},
-    MsgStartUnbonding { token_id: String },
-    MsgRedelegate { token_id: String },
-    MsgClaimRewards { token_id: String },
-    MsgRedeemBond { token_id: String },
+    /// Delegate the funds minting a new NFT or, when the
+    /// token_id is set, adding them to the existing NFT.
+    /// The validators are selected by the hub strategy
+    /// unless the user chooses them.
+    MsgDelegate {
+        token_id: Option<String>,
+        validators: Option<Vec<ValidatorWeight>>,
+    },
+    MsgStartUnbonding {
+        token_id: String,
+    },
+    /// Unbond only the targets from the NFT delegations
+    /// keeping the rest of the delegations as they are
+    MsgStartPartialUnbonding {
+        token_id: String,
+        targets: Vec<DelegationTarget>,
+    },
+    /// Mint a new NFT for each part taking the part
+    /// delegations from the original NFT delegations
+    MsgSplit {
+        token_id: String,
+        parts: Vec<Vec<DelegationTarget>>,
+    },
+    /// Fold the delegations of all the NFTs into
+    /// the first one burning the rest of the NFTs
+    MsgMerge {
+        token_ids: Vec<String>,
+    },
+    MsgRedelegate {
+        token_id: String,
+        validators: Option<Vec<ValidatorWeight>>,
+    },
+    MsgClaimRewards {
+        token_id: String,
+    },
+    MsgRedeemBond {
+        token_id: String,
+    },
+    /// Enable or disable the auto compound of the NFT
+    /// rewards, can only be executed by the NFT owner
+    SetAutoCompound {
+        token_id: String,
+        enabled: bool,
+    },
+    /// Claim the rewards of the NFTs with auto compound enabled
+    /// and delegate the alliance assets to the same validators,
+    /// anyone can execute it
+    Compound {
+        token_ids: Vec<String>,
+    },
+    /// Update the NFTs moving the completed redelegations back
+    /// to Delegated and the matured unbondings to Claimable.
+    /// It can be executed by anyone.
+    Refresh {
+        token_ids: Vec<String>,
+    },
+    /// Update the mutable fields of the config,
+    /// can only be executed by the owner of the hub
+    UpdateConfig(UpdateConfigMsg),
+    /// Pause or resume the operations of the hub,
+    /// can only be executed by the owner of the hub
+    UpdatePauseFlags(PauseFlags),
+    /// Migrate the NFT contract to the new code id with the
+    /// hub as admin, can only be executed by the owner of the hub
+    MigrateCollection {
+        new_code_id: u64,
+        msg: Binary,
+    },
+    /// Migrate the delegations of the next batch of NFTs from
+    /// the metadata written by the versions before 0.2.0, which
+    /// the contract migration starts with the first batch. The
+    /// NFTs which redelegation destination cannot be recovered
+    /// wait for the owner to set it in `redelegations`. The other
+    /// operations, except refreshing and redeeming the migrated
+    /// NFTs, are rejected until every NFT is migrated, can only
+    /// be executed by the owner of the hub
+    MigrateDelegations {
+        limit: Option<u32>,
+        redelegations: Option<Vec<RedelegationDestination>>,
+    },
 }
 
+/// Validator an NFT migrated from the versions before 0.2.0
+/// is redelegating to, which those versions did not store
+#[cw_serde]
+pub struct RedelegationDestination {
+    pub token_id: String,
+    pub validator: String,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct UpdateConfigMsg {
+    pub unbonding_seconds: Option<u64>,
+    pub nft_contract_addr: Option<String>,
+    pub validator_strategy: Option<ValidatorStrategy>,
+    pub split_mode: Option<SplitMode>,
+    pub max_validators_per_denom: Option<u64>,
+    /// Validators the users can choose, an
+    /// empty list allows any active validator
+    pub validators_allowlist: Option<Vec<String>>,
+    pub refund_unsupported_denoms: Option<bool>,
+    pub redeemed_nft: Option<RedeemedNft>,
+    /// Fee sent to the treasury, setting both
+    /// fees to zero removes the protocol fee
+    pub protocol_fee: Option<ProtocolFee>,
+}
+
+#[cw_serde]
+pub struct DelegationTarget {
+    /// Validator to take the amount from, when not set the
+    /// amount is taken from any of the validators of the denom
+    pub validator: Option<String>,
+    pub amount: Coin,
+}
+
+#[cw_serde]
+pub struct ValidatorWeight {
+    pub address: String,
+    /// Share of each coin delegated to the validator
+    /// relative to the weights of the other validators
+    pub weight: Uint128,
+}
+
+#[cw_ownable_query]
 #[cw_serde]
 #[derive(QueryResponses)]
 pub enum QueryMsg {
     #[returns(Cfg)]
     GetConfig {},
+    #[returns(PauseFlags)]
+    GetPauseFlags {},
+    #[returns(bool)]
+    GetAutoCompound { token_id: String },
+    /// Delegations of the NFT with the statuses at the current block
+    #[returns(PositionResponse)]
+    Position { token_id: String },
+    #[returns(PositionsResponse)]
+    PositionsByOwner {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Rewards the NFT owner would receive claiming the rewards now
+    #[returns(PendingRewardsResponse)]
+    PendingRewards { token_id: String },
+    /// Amounts delegated by the hub to each validator and denom
+    #[returns(TotalDelegatedResponse)]
+    TotalDelegated {},
+    /// Delegations and metadata of the NFT MsgDelegate would mint
+    #[returns(SimulateDelegateResponse)]
+    SimulateDelegate { funds: Vec<Coin> },
+    /// Delegations and metadata of the NFT after MsgRedelegate
+    #[returns(SimulateRedelegateResponse)]
+    SimulateRedelegate { token_id: String },
+}
+
+#[cw_serde]
+pub struct PositionResponse {
+    pub token_id: String,
+    pub owner: String,
+    pub delegations: Vec<DelegationRecord>,
+}
+
+#[cw_serde]
+pub struct PositionsResponse {
+    pub positions: Vec<PositionResponse>,
+}
+
+#[cw_serde]
+pub struct PendingRewardsResponse {
+    pub rewards: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct TotalDelegatedResponse {
+    pub delegations: Vec<ValidatorDelegation>,
+}
+
+#[cw_serde]
+pub struct SimulateDelegateResponse {
+    pub token_id: String,
+    pub delegations: Vec<DelegationRecord>,
+    pub metadata: CW721Metadata,
+    pub refunds: Vec<Coin>,
+    pub deposit_fees: Vec<Coin>,
+}
+
+#[cw_serde]
+pub struct SimulateRedelegateResponse {
+    pub delegations: Vec<DelegationRecord>,
+    pub metadata: CW721Metadata,
+}
+
+#[cw_serde]
+pub struct ValidatorDelegation {
+    pub validator: String,
+    pub coin: Coin,
 }
 
 #[cw_serde]
 pub enum MigrateMsg {
-    Migrate {},
+    Migrate {
+        /// Owner of the hub, required when migrating
+        /// from the versions without an owner
+        owner: Option<String>,
+        /// Validators the NFTs are redelegating to when migrating
+        /// from the versions before 0.2.0 (see MigrateDelegations)
+        redelegations: Option<Vec<RedelegationDestination>>,
+    },
 }
@@ -0,0 +1,303 @@
+use cosmwasm_std::{Addr, Coin, Deps, StdResult, Storage, Uint128};
+
+use crate::entry_points::query;
+use crate::fees;
+use crate::state::{
+    DelegationRecord, DisplayType, ProtocolFee, RewardCheckpoint, ORPHANED_REWARDS,
+    REWARD_CHECKPOINTS, REWARD_INDEXES, REWARD_RESERVES,
+};
+use crate::ContractError;
+
+// Add the shares of the NFT to the reward index of the
+// (validator, denom). If the NFT already had shares of the
+// same validator and denom the rewards earned until now are
+// settled and returned so they can be sent to the NFT owner.
+pub fn add_delegation(
+    storage: &mut dyn Storage,
+    token_id: &str,
+    validator: &str,
+    shares: &Coin,
+) -> StdResult<Vec<Coin>> {
+    let mut index = REWARD_INDEXES
+        .may_load(storage, (validator, &shares.denom))?
+        .unwrap_or_default();
+    let checkpoint = REWARD_CHECKPOINTS.may_load(storage, (token_id, validator, &shares.denom))?;
+    let (rewards, prev_shares) = match checkpoint {
+        Some(checkpoint) => (
+            index.rewards_since(&checkpoint.rewards_per_share, checkpoint.shares),
+            checkpoint.shares,
+        ),
+        None => (vec![], Uint128::zero()),
+    };
+
+    index.total_shares = index.total_shares.checked_add(shares.amount)?;
+    REWARD_CHECKPOINTS.save(
+        storage,
+        (token_id, validator, &shares.denom),
+        &RewardCheckpoint {
+            shares: prev_shares.checked_add(shares.amount)?,
+            rewards_per_share: index.rewards_per_share.clone(),
+        },
+    )?;
+    REWARD_INDEXES.save(storage, (validator, &shares.denom), &index)?;
+    reserve_rewards(storage, &rewards, false)?;
+
+    Ok(rewards)
+}
+
+// Remove the shares of the NFT from the reward index of the
+// (validator, denom) settling the rewards earned by the NFT
+// until now, which are returned to be sent to the NFT owner.
+pub fn remove_delegation(
+    storage: &mut dyn Storage,
+    token_id: &str,
+    validator: &str,
+    shares: &Coin,
+) -> StdResult<Vec<Coin>> {
+    let checkpoint =
+        match REWARD_CHECKPOINTS.may_load(storage, (token_id, validator, &shares.denom))? {
+            Some(checkpoint) => checkpoint,
+            None => return Ok(vec![]),
+        };
+    let mut index = REWARD_INDEXES
+        .may_load(storage, (validator, &shares.denom))?
+        .unwrap_or_default();
+    let rewards = index.rewards_since(&checkpoint.rewards_per_share, checkpoint.shares);
+
+    index.total_shares = index.total_shares.checked_sub(shares.amount)?;
+    let remaining = checkpoint.shares.checked_sub(shares.amount)?;
+    if remaining.is_zero() {
+        REWARD_CHECKPOINTS.remove(storage, (token_id, validator, &shares.denom));
+    } else {
+        REWARD_CHECKPOINTS.save(
+            storage,
+            (token_id, validator, &shares.denom),
+            &RewardCheckpoint {
+                shares: remaining,
+                rewards_per_share: index.rewards_per_share.clone(),
+            },
+        )?;
+    }
+    REWARD_INDEXES.save(storage, (validator, &shares.denom), &index)?;
+    reserve_rewards(storage, &rewards, false)?;
+
+    Ok(rewards)
+}
+
+// Balance delegated by the hub to each (validator, denom) and the
+// shares of its reward index, used to convert the amounts delegated
+// to shares and back. The chain only updates the balances once the
+// execution ends, so each ratio is loaded before the execution changes
+// the reward index and cached for the rest of the execution.
+pub struct ShareRatios {
+    delegator: Addr,
+    ratios: Vec<(String, String, Uint128, Uint128)>,
+}
+
+impl ShareRatios {
+    pub fn new(delegator: &Addr) -> Self {
+        ShareRatios {
+            delegator: delegator.clone(),
+            ratios: vec![],
+        }
+    }
+
+    // Balance and shares of the (validator, denom), the balance is only
+    // queried when there are shares because the chain does not find the
+    // delegations without balance
+    fn load(
+        &mut self,
+        deps: Deps,
+        validator: &str,
+        denom: &str,
+    ) -> Result<(Uint128, Uint128), ContractError> {
+        if let Some((_, _, balance, shares)) = self
+            .ratios
+            .iter()
+            .find(|(val, den, _, _)| val == validator && den == denom)
+        {
+            return Ok((*balance, *shares));
+        }
+
+        let shares = REWARD_INDEXES
+            .may_load(deps.storage, (validator, denom))?
+            .unwrap_or_default()
+            .total_shares;
+        let balance = match shares.is_zero() {
+            true => Uint128::zero(),
+            false => {
+                query::alliance_delegation(deps.querier, &self.delegator, validator, denom)?.amount
+            }
+        };
+        self.ratios
+            .push((validator.to_string(), denom.to_string(), balance, shares));
+
+        Ok((balance, shares))
+    }
+
+    // Shares the delegated amount is worth, which are
+    // the same as the amount while there are no shares
+    pub fn to_shares(
+        &mut self,
+        deps: Deps,
+        validator: &str,
+        coin: &Coin,
+    ) -> Result<Coin, ContractError> {
+        let (balance, shares) = self.load(deps, validator, &coin.denom)?;
+        if shares.is_zero() {
+            return Ok(coin.clone());
+        }
+        let amount = coin
+            .amount
+            .checked_multiply_ratio(shares, balance)
+            .map_err(|_| ContractError::DelegationSlashed(validator.to_string()))?;
+
+        Ok(Coin::new(amount.u128(), &coin.denom))
+    }
+
+    // Amount the shares are worth, lower than the
+    // shares when the validator has been slashed
+    pub fn to_amount(
+        &mut self,
+        deps: Deps,
+        validator: &str,
+        shares: &Coin,
+    ) -> Result<Coin, ContractError> {
+        let (balance, total_shares) = self.load(deps, validator, &shares.denom)?;
+        if total_shares.is_zero() {
+            return Ok(shares.clone());
+        }
+        let amount = shares
+            .amount
+            .checked_multiply_ratio(balance, total_shares)
+            .map_err(|_| ContractError::DelegationSlashed(validator.to_string()))?;
+
+        Ok(Coin::new(amount.u128(), &shares.denom))
+    }
+
+    // Records with the shares of the Delegated and Redelegating
+    // records converted to the amounts they are worth, which are
+    // the amounts displayed in the NFT metadata
+    pub fn to_amounts(
+        &mut self,
+        deps: Deps,
+        records: &[DelegationRecord],
+    ) -> Result<Vec<DelegationRecord>, ContractError> {
+        records
+            .iter()
+            .map(|record| match record.status {
+                DisplayType::Delegated | DisplayType::Redelegating => Ok(DelegationRecord {
+                    coin: self.to_amount(deps, &record.validator, &record.coin)?,
+                    ..record.clone()
+                }),
+                _ => Ok(record.clone()),
+            })
+            .collect()
+    }
+}
+
+// Split the rewards claimed from the (validator, denom) between all the
+// NFTs delegating to it after taking the reward fee, which is returned.
+// The rewards claimed when no NFT delegates to it cannot be credited so
+// no fee is taken from them and they are returned as orphaned to be sent
+// to the treasury, or kept reserved in the hub when there is none.
+pub fn add_rewards(
+    storage: &mut dyn Storage,
+    protocol_fee: Option<&ProtocolFee>,
+    validator: &str,
+    denom: &str,
+    rewards: Vec<Coin>,
+) -> StdResult<(Vec<Coin>, Vec<Coin>)> {
+    let mut index = REWARD_INDEXES
+        .may_load(storage, (validator, denom))?
+        .unwrap_or_default();
+    if index.total_shares.is_zero() {
+        if protocol_fee.is_some() {
+            return Ok((vec![], rewards));
+        }
+        for reward in rewards.iter() {
+            ORPHANED_REWARDS.update(storage, &reward.denom, |orphaned| -> StdResult<_> {
+                Ok(orphaned.unwrap_or_default().checked_add(reward.amount)?)
+            })?;
+        }
+        return Ok((vec![], vec![]));
+    }
+
+    let (rewards, reward_fees) = fees::take_reward_fee(protocol_fee, rewards);
+    reserve_rewards(storage, &rewards, true)?;
+    index.add_rewards(&rewards);
+    REWARD_INDEXES.save(storage, (validator, denom), &index)?;
+
+    Ok((reward_fees, vec![]))
+}
+
+// Track the rewards held by the hub for the NFTs, adding the credited
+// rewards and removing the settled ones, which leave the hub balance
+fn reserve_rewards(storage: &mut dyn Storage, rewards: &[Coin], credited: bool) -> StdResult<()> {
+    for reward in rewards.iter() {
+        REWARD_RESERVES.update(storage, &reward.denom, |reserve| -> StdResult<_> {
+            let reserve = reserve.unwrap_or_default();
+            match credited {
+                true => Ok(reserve.checked_add(reward.amount)?),
+                false => Ok(reserve.saturating_sub(reward.amount)),
+            }
+        })?;
+    }
+
+    Ok(())
+}
+
+// Settle the rewards earned by the NFT delegation to
+// the (validator, denom) since the last checkpoint.
+pub fn settle_rewards(
+    storage: &mut dyn Storage,
+    token_id: &str,
+    validator: &str,
+    denom: &str,
+) -> StdResult<Vec<Coin>> {
+    let mut checkpoint = match REWARD_CHECKPOINTS.may_load(storage, (token_id, validator, denom))? {
+        Some(checkpoint) => checkpoint,
+        None => return Ok(vec![]),
+    };
+    let index = REWARD_INDEXES
+        .may_load(storage, (validator, denom))?
+        .unwrap_or_default();
+    let rewards = index.rewards_since(&checkpoint.rewards_per_share, checkpoint.shares);
+
+    checkpoint.rewards_per_share = index.rewards_per_share;
+    REWARD_CHECKPOINTS.save(storage, (token_id, validator, denom), &checkpoint)?;
+    reserve_rewards(storage, &rewards, false)?;
+
+    Ok(rewards)
+}
+
+// Rewards the NFT delegation to the (validator, denom) would
+// settle if the unclaimed rewards of the hub were claimed now
+pub fn pending_rewards(
+    storage: &dyn Storage,
+    token_id: &str,
+    validator: &str,
+    denom: &str,
+    unclaimed: &[Coin],
+) -> StdResult<Vec<Coin>> {
+    let checkpoint = match REWARD_CHECKPOINTS.may_load(storage, (token_id, validator, denom))? {
+        Some(checkpoint) => checkpoint,
+        None => return Ok(vec![]),
+    };
+    let mut index = REWARD_INDEXES
+        .may_load(storage, (validator, denom))?
+        .unwrap_or_default();
+    index.add_rewards(unclaimed);
+
+    Ok(index.rewards_since(&checkpoint.rewards_per_share, checkpoint.shares))
+}
+
+// Add the coins to the list merging the ones with the same denom
+pub fn merge_coins(coins: &mut Vec<Coin>, others: Vec<Coin>) {
+    for other in others {
+        match coins.iter_mut().find(|coin| coin.denom == other.denom) {
+            Some(coin) => coin.amount += other.amount,
+            None => coins.push(other),
+        }
+    }
+}
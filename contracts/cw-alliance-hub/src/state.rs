@@ -1,8 +1,8 @@
 use std::{fmt, str::FromStr};
 
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::Addr;
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, Coin, Decimal, Empty, Timestamp, Uint128};
+use cw_storage_plus::{Item, Map};
 
 // Contain the list of nfts minted by the contract
 // Where the key is the address of the nft and the
@@ -15,23 +15,334 @@ pub struct Cfg {
     pub minted_nfts: u64,
     pub unbonding_seconds: u64,
     pub nft_contract_addr: Option<Addr>,
+    pub validator_strategy: ValidatorStrategy,
+    pub split_mode: SplitMode,
+    pub max_validators_per_denom: u64,
+    pub validators_allowlist: Option<Vec<String>>,
+    pub refund_unsupported_denoms: bool,
+    pub redeemed_nft: RedeemedNft,
+    pub protocol_fee: Option<ProtocolFee>,
 }
 
 impl Cfg {
-    pub fn new(unbonding_seconds: u64) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        unbonding_seconds: u64,
+        validator_strategy: ValidatorStrategy,
+        split_mode: SplitMode,
+        max_validators_per_denom: u64,
+        validators_allowlist: Option<Vec<String>>,
+        refund_unsupported_denoms: bool,
+        redeemed_nft: RedeemedNft,
+        protocol_fee: Option<ProtocolFee>,
+    ) -> Self {
         Cfg {
             minted_nfts: 0,
             nft_contract_addr: None,
             unbonding_seconds,
+            validator_strategy,
+            split_mode,
+            max_validators_per_denom,
+            validators_allowlist,
+            refund_unsupported_denoms,
+            redeemed_nft,
+            protocol_fee,
         }
     }
 }
 
+// Strategy used by the hub to select the validators
+// the funds are delegated or redelegated to
+#[cw_serde]
+#[derive(Default)]
+pub enum ValidatorStrategy {
+    /// Pseudo random validator based on the block height
+    #[default]
+    PseudoRandom,
+    /// Random validator weighted by the voting power
+    /// of the bonded and not jailed validators
+    VotingPower,
+    /// Validator with the lowest commission
+    LowestCommission,
+    /// Iterate the validators using the stored cursor
+    RoundRobin,
+    /// Pseudo random validator from the curated list
+    Curated { validators: Vec<String> },
+}
+
+// How each deposited coin is divided between
+// the validators selected by the strategy
+#[cw_serde]
+#[derive(Default)]
+pub enum SplitMode {
+    /// The whole coin is delegated to a single validator
+    #[default]
+    Single,
+    /// The coin is divided evenly between the validators
+    Even,
+    /// The coin is divided by the voting power of the validators
+    Weighted,
+}
+
+// Operations paused by the owner of the hub, redeeming the
+// matured bonds cannot be paused so users can always exit
+pub const PAUSE_FLAGS: Item<PauseFlags> = Item::new("pause_flags");
+
+#[cw_serde]
+#[derive(Default)]
+pub struct PauseFlags {
+    /// MsgDelegate, both minting and topping up NFTs
+    pub deposits: bool,
+    /// MsgRedelegate
+    pub redelegations: bool,
+    /// MsgStartUnbonding and MsgStartPartialUnbonding
+    pub unbondings: bool,
+    /// MsgClaimRewards
+    pub claims: bool,
+}
+
+// What happens to the NFT once all its
+// delegations have been redeemed
+#[cw_serde]
+#[derive(Default)]
+pub enum RedeemedNft {
+    /// The NFT is kept by the owner but cannot be transferred
+    #[default]
+    Lock,
+    /// The NFT is burned
+    Burn,
+}
+
+// Fee taken by the hub and sent to the treasury
+#[cw_serde]
+pub struct ProtocolFee {
+    pub treasury: Addr,
+    /// Share of the claimed rewards
+    pub reward_fee: Decimal,
+    /// Share of the deposited funds
+    pub deposit_fee: Decimal,
+}
+
+// Position of the next validator to select
+// by the round robin validator strategy
+pub const VALIDATORS_CURSOR: Item<u64> = Item::new("validators_cursor");
+
+// Temporary state used while claiming rewards. It stores
+// the hub balance before the claim, the owner of the NFT
+// and the NFT delegations claimed in the same order as the
+// submessages so each reply can credit the claimed rewards
+// to the right reward index
+pub const CLAIM_REWARDS_TMP: Item<ClaimRewardsTmp> = Item::new("claim_rewards_tmp");
+
+#[cw_serde]
+pub struct ClaimRewardsTmp {
+    pub owner: Addr,
+    pub token_id: String,
+    pub balances: Vec<Coin>,
+    pub delegations: Vec<(String, Coin)>,
+}
+
+// NFTs which owners have enabled the auto compound
+// of the rewards by the permissionless MsgCompound
+pub const AUTO_COMPOUND: Map<&str, Empty> = Map::new("auto_compound");
+
+// Temporary state used while compounding rewards, it works as
+// CLAIM_REWARDS_TMP for delegations of different NFTs
+pub const COMPOUND_TMP: Item<CompoundTmp> = Item::new("compound_tmp");
+
+#[cw_serde]
+pub struct CompoundTmp {
+    pub balances: Vec<Coin>,
+    pub delegations: Vec<CompoundDelegation>,
+}
+
+#[cw_serde]
+pub struct CompoundDelegation {
+    pub owner: String,
+    pub token_id: String,
+    pub validator: String,
+    pub coin: Coin,
+}
+
+// Temporary state used while undelegating or redelegating. It
+// stores the records created with the estimated completion time
+// in the same order as the submessages so each reply can replace
+// the estimate with the completion time reported by the chain
+pub const COMPLETION_TIMES_TMP: Item<Vec<CompletionTimeTmp>> = Item::new("completion_times_tmp");
+
+#[cw_serde]
+pub struct CompletionTimeTmp {
+    pub token_id: String,
+    pub validator: String,
+    pub denom: String,
+    pub status: DisplayType,
+    pub completion_time: Timestamp,
+}
+
+// Reward index of each alliance delegation of the hub where
+// the key is the (validator, denom) tuple. It keeps the shares
+// of all the NFTs and the rewards earned by each share since
+// the first delegation. Each share is worth the balance delegated
+// by the hub divided by the total shares, which only changes
+// when the validator is slashed.
+pub const REWARD_INDEXES: Map<(&str, &str), RewardIndex> = Map::new("reward_indexes");
+
+// Shares of the NFT in the (validator, denom) delegation and
+// the reward index at the moment the NFT rewards were settled
+// for the last time, where the key is (token_id, validator, denom).
+pub const REWARD_CHECKPOINTS: Map<(&str, &str, &str), RewardCheckpoint> =
+    Map::new("reward_checkpoints");
+
+#[cw_serde]
+pub struct RewardPerShare {
+    pub denom: String,
+    pub amount: Decimal,
+}
+
+#[cw_serde]
+pub struct RewardCheckpoint {
+    pub shares: Uint128,
+    pub rewards_per_share: Vec<RewardPerShare>,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct RewardIndex {
+    pub total_shares: Uint128,
+    pub rewards_per_share: Vec<RewardPerShare>,
+}
+
+impl RewardIndex {
+    // Split the rewards between all the shares.
+    // Rewards received without delegations cannot be
+    // assigned to any NFT so they are ignored
+    pub fn add_rewards(&mut self, rewards: &[Coin]) {
+        if self.total_shares.is_zero() {
+            return;
+        }
+
+        for reward in rewards {
+            let amount = Decimal::from_ratio(reward.amount, self.total_shares);
+            match self
+                .rewards_per_share
+                .iter_mut()
+                .find(|rps| rps.denom == reward.denom)
+            {
+                Some(rps) => rps.amount += amount,
+                None => self.rewards_per_share.push(RewardPerShare {
+                    denom: reward.denom.clone(),
+                    amount,
+                }),
+            }
+        }
+    }
+
+    // Rewards earned by the shares since the checkpoint
+    pub fn rewards_since(&self, checkpoint: &[RewardPerShare], shares: Uint128) -> Vec<Coin> {
+        self.rewards_per_share
+            .iter()
+            .filter_map(|rps| {
+                let prev_amount = checkpoint
+                    .iter()
+                    .find(|prev| prev.denom == rps.denom)
+                    .map(|prev| prev.amount)
+                    .unwrap_or_default();
+                let reward = shares * (rps.amount - prev_amount);
+
+                if reward.is_zero() {
+                    return None;
+                }
+                Some(Coin {
+                    denom: rps.denom.clone(),
+                    amount: reward,
+                })
+            })
+            .collect()
+    }
+}
+
+// Unbondings of the hub that have not been redeemed yet, where the
+// key is the (denom, completion time in nanoseconds) tuple. Once they
+// mature each entry records the principal returned by the chain so
+// the NFTs are paid from their own unbonding and not from the funds
+// the hub holds for other unbondings or rewards.
+pub const UNBONDINGS: Map<(&str, u64), UnbondingEntry> = Map::new("unbondings");
+
+#[cw_serde]
+#[derive(Default)]
+pub struct UnbondingEntry {
+    /// Amount undelegated by the NFTs that has not been redeemed
+    pub amount: Uint128,
+    /// Part of the amount which completion time is an estimate
+    /// that has not been confirmed by the chain yet
+    pub estimated: Uint128,
+    /// Principal returned by the chain that has not been redeemed,
+    /// set once the unbonding has matured
+    pub returned: Option<Uint128>,
+}
+
+impl UnbondingEntry {
+    // The principal returned is final once the chain has confirmed the
+    // completion time or the whole amount has been returned, otherwise
+    // the estimated part can still be returned in a later block
+    pub fn is_settled(&self) -> bool {
+        match self.returned {
+            Some(returned) => self.estimated.is_zero() || returned >= self.amount,
+            None => false,
+        }
+    }
+}
+
+// Rewards credited to the reward indexes that have not been sent to
+// the NFT owners yet, for each denom. The hub holds them in its balance
+// so they are not taken as principal returned by the chain.
+pub const REWARD_RESERVES: Map<&str, Uint128> = Map::new("reward_reserves");
+
+// Rewards claimed from a (validator, denom) no NFT delegates to, for
+// each denom, which cannot be credited to any NFT. They are sent to the
+// treasury of the protocol fee, when there is none the hub keeps them
+// so they are not taken as principal returned by the chain either.
+pub const ORPHANED_REWARDS: Map<&str, Uint128> = Map::new("orphaned_rewards");
+
+// Delegations of each NFT where the key is the token_id. The hub keeps
+// its own typed records as the source of truth, the NFT metadata is
+// built from them only to display the delegations
+pub const DELEGATIONS: Map<&str, Vec<DelegationRecord>> = Map::new("delegations");
+
+// Progress of the migration of the delegations stored in the NFT
+// metadata by the versions before 0.2.0. It is only set while the
+// migration is pending, storing the last NFT migrated.
+pub const DELEGATIONS_MIGRATION: Item<DelegationsMigration> = Item::new("delegations_migration");
+
+#[cw_serde]
+pub struct DelegationsMigration {
+    pub start_after: Option<String>,
+    /// NFTs skipped because their redelegation destination
+    /// cannot be recovered, they wait for the owner to set it
+    pub unresolved: Vec<String>,
+    /// Time of the contract migration, the legacy unbondings
+    /// completed before it were already returned to the hub
+    pub started_at: Timestamp,
+}
+
+#[cw_serde]
+pub struct DelegationRecord {
+    pub validator: String,
+    // Shares of the reward index for the Delegated and Redelegating
+    // records, the amount undelegated for the rest of the records
+    pub coin: Coin,
+    pub status: DisplayType,
+    // Time the delegation was made or, for the Redelegating
+    // and Unbonding records, the time it will be completed
+    pub completion_time: Timestamp,
+}
+
 #[cw_serde]
 pub enum DisplayType {
     Unknown,
     Unbonded,
     Unbonding,
+    Claimable,
     Delegated,
     Redelegating,
 }
@@ -43,6 +354,7 @@ impl FromStr for DisplayType {
         match s {
             "Delegated" => Ok(DisplayType::Delegated),
             "Unbonding" => Ok(DisplayType::Unbonding),
+            "Claimable" => Ok(DisplayType::Claimable),
             "Redelegating" => Ok(DisplayType::Redelegating),
             "Unbonded" => Ok(DisplayType::Unbonded),
             "Unknown" => Ok(DisplayType::Unknown),
@@ -56,6 +368,7 @@ impl fmt::Display for DisplayType {
         match self {
             DisplayType::Delegated => write!(f, "Delegated"),
             DisplayType::Unbonding => write!(f, "Unbonding"),
+            DisplayType::Claimable => write!(f, "Claimable"),
             DisplayType::Redelegating => write!(f, "Redelegating"),
             DisplayType::Unbonded => write!(f, "Unbonded"),
             DisplayType::Unknown => write!(f, "Unknown"),
@@ -68,6 +381,7 @@ impl From<String> for DisplayType {
         match s.as_str() {
             "Delegated" => DisplayType::Delegated,
             "Unbonding" => DisplayType::Unbonding,
+            "Claimable" => DisplayType::Claimable,
             "Redelegating" => DisplayType::Redelegating,
             "Unbonded" => DisplayType::Unbonded,
             "Unknown" => DisplayType::Unknown,
@@ -75,3 +389,32 @@ impl From<String> for DisplayType {
         }
     }
 }
+
+// Temporary state used while delegating, undelegating or redelegating.
+// x/alliance pays the pending rewards of the hub delegations moved by
+// these messages, so it stores the hub balance before them and, in the
+// same order as the submessages, the shares each one moves so each
+// reply credits the paid rewards before the reward indexes change.
+// Splitting and merging NFTs claim the rewards the same way before
+// moving the shares between the NFTs.
+pub const PAID_REWARDS_TMP: Item<PaidRewardsTmp> = Item::new("paid_rewards_tmp");
+
+#[cw_serde]
+pub struct PaidRewardsTmp {
+    pub owner: Addr,
+    pub balances: Vec<Coin>,
+    pub moves: Vec<SharesMove>,
+}
+
+#[cw_serde]
+pub struct SharesMove {
+    /// Delegation which rewards are paid by the submessage
+    pub validator: String,
+    pub denom: String,
+    /// Coins taken from the hub balance by the submessage
+    pub spent: Vec<Coin>,
+    /// Shares moved once the rewards are credited
+    /// as (token_id, validator, shares)
+    pub removed: Vec<(String, String, Coin)>,
+    pub added: Vec<(String, String, Coin)>,
+}
@@ -0,0 +1,87 @@
+use cosmwasm_std::Timestamp;
+
+use crate::{
+    state::{DelegationRecord, DisplayType},
+    ContractError,
+};
+
+// Transitions allowed between the statuses of the delegation records:
+//
+// - Delegated -> Unbonding when the delegation is undelegated,
+// - Delegated -> Redelegating when the delegation is redelegated,
+// - Redelegating -> Delegated once the redelegation has completed,
+// - Unbonding -> Claimable once the unbonding has matured,
+// - Claimable -> Unbonded when the unbonded tokens are redeemed.
+//
+// The completion time is the time the current status completes,
+// which is only checked by the transitions that wait for the chain.
+// The chain completes them at the end of the block so they are only
+// completed for the hub from the next block on.
+pub fn transition(
+    from: &DisplayType,
+    to: &DisplayType,
+    completion_time: Timestamp,
+    block_time: Timestamp,
+) -> Result<DisplayType, ContractError> {
+    match (from, to) {
+        (DisplayType::Delegated, DisplayType::Unbonding)
+        | (DisplayType::Delegated, DisplayType::Redelegating)
+        | (DisplayType::Claimable, DisplayType::Unbonded) => Ok(to.clone()),
+        (DisplayType::Redelegating, DisplayType::Delegated)
+        | (DisplayType::Unbonding, DisplayType::Claimable) => {
+            if completion_time >= block_time {
+                return Err(ContractError::StatusNotCompleted(
+                    from.to_string(),
+                    completion_time.seconds(),
+                ));
+            }
+
+            Ok(to.clone())
+        }
+        _ => Err(ContractError::IllegalTransition(
+            from.to_string(),
+            to.to_string(),
+        )),
+    }
+}
+
+impl DelegationRecord {
+    // Move the record to the new status, where the completion
+    // time is the time the new status has been or will be reached
+    pub fn transition(
+        &mut self,
+        to: DisplayType,
+        completion_time: Timestamp,
+        block_time: Timestamp,
+    ) -> Result<(), ContractError> {
+        self.status = transition(&self.status, &to, self.completion_time, block_time)?;
+        self.completion_time = completion_time;
+
+        Ok(())
+    }
+}
+
+// Move the redelegations which completion time has been reached back
+// to Delegated and the matured unbondings to Claimable, returning if
+// any record has been promoted so the NFT metadata can be updated
+pub fn refresh(
+    records: &mut [DelegationRecord],
+    block_time: Timestamp,
+) -> Result<bool, ContractError> {
+    let mut promoted = false;
+
+    for record in records
+        .iter_mut()
+        .filter(|record| record.completion_time < block_time)
+    {
+        let to = match record.status {
+            DisplayType::Redelegating => DisplayType::Delegated,
+            DisplayType::Unbonding => DisplayType::Claimable,
+            _ => continue,
+        };
+        record.transition(to, record.completion_time, block_time)?;
+        promoted = true;
+    }
+
+    Ok(promoted)
+}
@@ -0,0 +1,225 @@
+use crate::entry_points::{execute::execute, query::query};
+use crate::msg::{ExecuteMsg, QueryMsg, UpdateConfigMsg};
+use crate::state::{RedeemedNft, ValidatorStrategy, CFG};
+use crate::{tests::utils::chain_with_contract, ContractError};
+use cosmwasm_std::{from_binary, testing::mock_info, Addr, Attribute, Event};
+use cw_ownable::{Action, Ownership, OwnershipError};
+
+const VALIDATOR: &str = "terravaloper1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5ef6r50";
+const VALIDATOR2: &str = "terravaloper1z5tpwxqergd3c8g7ruszzg3rysjjvfegwvwdue";
+
+#[test]
+fn test_update_config() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract();
+    let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg {
+        validator_strategy: Some(ValidatorStrategy::Curated {
+            validators: vec![String::from(VALIDATOR)],
+        }),
+        validators_allowlist: Some(vec![String::from(VALIDATOR)]),
+        redeemed_nft: Some(RedeemedNft::Burn),
+        ..Default::default()
+    });
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN only the updated fields change
+    let cfg = CFG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(
+        cfg.validator_strategy,
+        ValidatorStrategy::Curated {
+            validators: vec![String::from(VALIDATOR)]
+        }
+    );
+    assert_eq!(
+        cfg.validators_allowlist,
+        Some(vec![String::from(VALIDATOR)])
+    );
+    assert_eq!(cfg.redeemed_nft, RedeemedNft::Burn);
+    assert_eq!(cfg.unbonding_seconds, 100);
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "update_config"),
+            Attribute::new("sender", "creator")
+        ]
+    );
+    assert_eq!(
+        res.events,
+        vec![
+            Event::new("update_config")
+                .add_attribute("field", "validator_strategy")
+                .add_attribute("old_value", "\"pseudo_random\"")
+                .add_attribute(
+                    "new_value",
+                    "{\"curated\":{\"validators\":[\"terravaloper1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5ef6r50\"]}}"
+                ),
+            Event::new("update_config")
+                .add_attribute("field", "validators_allowlist")
+                .add_attribute("old_value", "null")
+                .add_attribute("new_value", "[\"terravaloper1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5ef6r50\"]"),
+            Event::new("update_config")
+                .add_attribute("field", "redeemed_nft")
+                .add_attribute("old_value", "\"lock\"")
+                .add_attribute("new_value", "\"burn\""),
+        ]
+    );
+}
+
+#[test]
+fn test_update_config_removes_allowlist() {
+    // GIVEN a hub with an allowlist
+    let (mut deps, env, info) = chain_with_contract();
+    CFG.update(
+        deps.as_mut().storage,
+        |mut cfg| -> Result<_, ContractError> {
+            cfg.validators_allowlist = Some(vec![String::from("validator1")]);
+            Ok(cfg)
+        },
+    )
+    .unwrap();
+    let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg {
+        validators_allowlist: Some(vec![]),
+        ..Default::default()
+    });
+
+    // WHEN
+    execute(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN any active validator is allowed
+    let cfg = CFG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.validators_allowlist, None);
+}
+
+#[test]
+fn test_update_config_with_invalid_validators() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract();
+    let update_config = |validator_strategy, validators_allowlist| {
+        ExecuteMsg::UpdateConfig(UpdateConfigMsg {
+            validator_strategy,
+            validators_allowlist,
+            ..Default::default()
+        })
+    };
+    let cases = vec![
+        (
+            update_config(
+                Some(ValidatorStrategy::Curated { validators: vec![] }),
+                None,
+            ),
+            ContractError::NoCuratedValidators {},
+        ),
+        (
+            update_config(
+                Some(ValidatorStrategy::Curated {
+                    validators: vec![String::from(VALIDATOR), String::from(VALIDATOR)],
+                }),
+                None,
+            ),
+            ContractError::RepeatedValidator(String::from(VALIDATOR)),
+        ),
+        (
+            update_config(None, Some(vec![String::from("validator1")])),
+            ContractError::InvalidValidatorAddress(String::from("validator1")),
+        ),
+        (
+            // An account address is not an operator address
+            update_config(
+                None,
+                Some(vec![String::from(
+                    "terra1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5exk7yu",
+                )]),
+            ),
+            ContractError::InvalidValidatorAddress(String::from(
+                "terra1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5exk7yu",
+            )),
+        ),
+        (
+            // The checksum of the address is wrong
+            update_config(
+                None,
+                Some(vec![String::from(
+                    "terravaloper1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5ef6r5q",
+                )]),
+            ),
+            ContractError::InvalidValidatorAddress(String::from(
+                "terravaloper1qypqxpq9qcrsszg2pvxq6rs0zqg3yyc5ef6r5q",
+            )),
+        ),
+        (
+            update_config(
+                None,
+                Some(vec![String::from(VALIDATOR2), String::from(VALIDATOR2)]),
+            ),
+            ContractError::RepeatedValidator(String::from(VALIDATOR2)),
+        ),
+    ];
+
+    for (msg, err) in cases {
+        // WHEN
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err();
+
+        // THEN the config is not updated
+        assert_eq!(res, err);
+    }
+    let cfg = CFG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.validator_strategy, ValidatorStrategy::PseudoRandom);
+    assert_eq!(cfg.validators_allowlist, None);
+}
+
+#[test]
+fn test_update_config_by_other_than_owner() {
+    // GIVEN
+    let (mut deps, env, _info) = chain_with_contract();
+    let info = mock_info("random", &[]);
+    let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg {
+        unbonding_seconds: Some(200),
+        ..Default::default()
+    });
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+
+    // THEN
+    assert_eq!(res, ContractError::Ownership(OwnershipError::NotOwner));
+}
+
+#[test]
+fn test_update_ownership() {
+    // GIVEN the owner transfers the ownership
+    let (mut deps, env, info) = chain_with_contract();
+    let msg = ExecuteMsg::UpdateOwnership(Action::TransferOwnership {
+        new_owner: String::from("new_owner"),
+        expiry: None,
+    });
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+    assert_eq!(
+        res.events,
+        vec![Event::new("update_ownership")
+            .add_attribute("old_owner", "creator")
+            .add_attribute("new_owner", "creator")
+            .add_attribute("old_pending_owner", "none")
+            .add_attribute("new_pending_owner", "new_owner")]
+    );
+
+    // WHEN the new owner accepts it
+    let info = mock_info("new_owner", &[]);
+    let msg = ExecuteMsg::UpdateOwnership(Action::AcceptOwnership {});
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // THEN
+    assert_eq!(
+        res.events,
+        vec![Event::new("update_ownership")
+            .add_attribute("old_owner", "creator")
+            .add_attribute("new_owner", "new_owner")
+            .add_attribute("old_pending_owner", "new_owner")
+            .add_attribute("new_pending_owner", "none")]
+    );
+    let ownership: Ownership<Addr> =
+        from_binary(&query(deps.as_ref(), env, QueryMsg::Ownership {}).unwrap()).unwrap();
+    assert_eq!(ownership.owner, Some(Addr::unchecked("new_owner")));
+    assert_eq!(ownership.pending_owner, None);
+}
@@ -1,8 +1,15 @@
 use crate::msg::ExecuteMsg;
+use crate::rewards;
+use crate::state::{RewardPerShare, ORPHANED_REWARDS, REWARD_INDEXES, REWARD_RESERVES};
 use crate::{
-    entry_points::execute::execute, tests::utils::chain_with_contract_delegation, ContractError,
+    entry_points::{execute::execute, reply::reply},
+    tests::utils::chain_with_contract_delegation,
+    ContractError,
+};
+use cosmwasm_std::{
+    coins, testing::mock_info, Attribute, BankMsg, Binary, Coin, CosmosMsg, Decimal, Reply,
+    Response, SubMsg, SubMsgResponse, Uint128,
 };
-use cosmwasm_std::{coins, testing::mock_info, Attribute, Binary, CosmosMsg, SubMsg};
 use terra_proto_rs::{alliance::alliance::MsgClaimDelegationRewards, traits::Message};
 
 #[test]
@@ -18,17 +25,20 @@ fn test_claim_rewards() {
 
     // THEN
     assert_eq!(res.messages.len(), 1);
-    let claim_rewards_sub_msg = SubMsg::new(CosmosMsg::Stargate {
-        type_url: String::from("/alliance.alliance.MsgClaimDelegationRewards"),
-        value: Binary::from(
-            MsgClaimDelegationRewards {
-                delegator_address: String::from("cosmos2contract"),
-                validator_address: String::from("validator1"),
-                denom: String::from("token"),
-            }
-            .encode_to_vec(),
-        ),
-    });
+    let claim_rewards_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgClaimDelegationRewards"),
+            value: Binary::from(
+                MsgClaimDelegationRewards {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator1"),
+                    denom: String::from("token"),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        6,
+    );
     assert_eq!(res.messages[0], claim_rewards_sub_msg);
     assert_eq!(
         res.attributes,
@@ -39,6 +49,143 @@ fn test_claim_rewards() {
     );
 }
 
+#[test]
+fn test_claim_rewards_reply() {
+    // GIVEN two NFTs delegating to the same validator and denom
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    rewards::add_delegation(
+        deps.as_mut().storage,
+        "0",
+        "validator1",
+        &Coin::new(100, "token"),
+    )
+    .unwrap();
+    rewards::add_delegation(
+        deps.as_mut().storage,
+        "1",
+        "validator1",
+        &Coin::new(300, "token"),
+    )
+    .unwrap();
+    deps.querier
+        .update_balance("cosmos2contract", coins(50, "uluna"));
+    let msg = ExecuteMsg::MsgClaimRewards {
+        token_id: String::from("0"),
+    };
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+    deps.querier
+        .update_balance("cosmos2contract", coins(130, "uluna"));
+
+    // WHEN
+    let reply_msg = Reply {
+        id: 6,
+        result: cosmwasm_std::SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }),
+    };
+    let res = reply(deps.as_mut(), env, reply_msg).unwrap();
+
+    // THEN only the share of the first NFT is sent
+    assert_eq!(
+        res,
+        Response::new()
+            .add_attribute("method", "claim_rewards_reply")
+            .add_attribute("recipient", "creator")
+            .add_message(BankMsg::Send {
+                to_address: String::from("creator"),
+                amount: coins(20, "uluna"),
+            })
+    );
+    let index = REWARD_INDEXES
+        .load(deps.as_ref().storage, ("validator1", "token"))
+        .unwrap();
+    assert_eq!(index.total_shares, Uint128::new(400));
+    assert_eq!(
+        index.rewards_per_share,
+        vec![RewardPerShare {
+            denom: String::from("uluna"),
+            amount: Decimal::percent(20),
+        }]
+    );
+
+    // AND the second NFT keeps its share until it claims
+    let rewards =
+        rewards::settle_rewards(deps.as_mut().storage, "1", "validator1", "token").unwrap();
+    assert_eq!(rewards, coins(60, "uluna"));
+}
+
+#[test]
+fn test_claim_rewards_reply_without_rewards() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    deps.querier
+        .update_balance("cosmos2contract", coins(50, "uluna"));
+    let msg = ExecuteMsg::MsgClaimRewards {
+        token_id: String::from("0"),
+    };
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // WHEN
+    let reply_msg = Reply {
+        id: 6,
+        result: cosmwasm_std::SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }),
+    };
+    let res = reply(deps.as_mut(), env, reply_msg).unwrap();
+
+    // THEN
+    assert_eq!(
+        res,
+        Response::new()
+            .add_attribute("method", "claim_rewards_reply")
+            .add_attribute("recipient", "creator")
+    );
+}
+
+#[test]
+fn test_claim_rewards_reply_without_shares() {
+    // GIVEN the hub claims the rewards of a delegation without shares
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    REWARD_INDEXES.remove(deps.as_mut().storage, ("validator1", "token"));
+    deps.querier
+        .update_balance("cosmos2contract", coins(50, "uluna"));
+    let msg = ExecuteMsg::MsgClaimRewards {
+        token_id: String::from("0"),
+    };
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+    deps.querier
+        .update_balance("cosmos2contract", coins(130, "uluna"));
+
+    // WHEN
+    let reply_msg = Reply {
+        id: 6,
+        result: cosmwasm_std::SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }),
+    };
+    let res = reply(deps.as_mut(), env, reply_msg).unwrap();
+
+    // THEN without a treasury the rewards are kept as orphaned
+    // so they are not taken as principal returned by the chain
+    assert_eq!(
+        res,
+        Response::new()
+            .add_attribute("method", "claim_rewards_reply")
+            .add_attribute("recipient", "creator")
+    );
+    assert_eq!(
+        ORPHANED_REWARDS
+            .load(deps.as_ref().storage, "uluna")
+            .unwrap(),
+        Uint128::new(80)
+    );
+    assert!(REWARD_RESERVES.is_empty(deps.as_ref().storage));
+}
+
 #[test]
 fn test_claim_rewards_with_no_access() {
     // GIVEN
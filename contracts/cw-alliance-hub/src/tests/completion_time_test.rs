@@ -0,0 +1,300 @@
+use crate::msg::{ExecuteMsg, UpdateConfigMsg};
+use crate::state::{DelegationRecord, DisplayType, DELEGATIONS, UNBONDINGS};
+use crate::{
+    entry_points::{
+        execute::{execute, Cw721ExecuteMsg},
+        reply::{parse_timestamp, reply},
+    },
+    tests::utils::chain_with_contract_delegation,
+};
+use cosmwasm_std::{
+    to_binary, Attribute, Binary, Coin, Event, Reply, SubMsg, SubMsgResponse, SubMsgResult,
+    Timestamp, Uint128, WasmMsg,
+};
+use cw721_progressive_metadata::state::{Metadata as CW721Metadata, Trait as CW721Trait};
+use terra_proto_rs::{cosmos::staking::v1beta1::MsgUndelegateResponse, traits::Message};
+
+fn completion_time_reply(events: Vec<Event>) -> Reply {
+    Reply {
+        id: 11,
+        result: SubMsgResult::Ok(SubMsgResponse { events, data: None }),
+    }
+}
+
+#[test]
+fn test_unbonding_time_from_staking_params() {
+    // GIVEN governance changed the unbonding time
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    deps.querier.set_unbonding_seconds(200);
+    let msg = ExecuteMsg::MsgStartUnbonding {
+        token_id: String::from("0"),
+    };
+
+    // WHEN
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // THEN the record completes with the unbonding time of the chain
+    let records = DELEGATIONS.load(deps.as_ref().storage, "0").unwrap();
+    assert_eq!(
+        records,
+        vec![DelegationRecord {
+            validator: String::from("validator1"),
+            coin: Coin::new(100, "token"),
+            status: DisplayType::Unbonding,
+            completion_time: env.block.time.plus_seconds(200),
+        }]
+    );
+}
+
+#[test]
+fn test_unbonding_time_from_config() {
+    // GIVEN the staking params cannot be parsed and the owner
+    // has updated the unbonding seconds of the config
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    deps.querier.set_unbonding_time("21 days");
+    let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg {
+        unbonding_seconds: Some(300),
+        ..Default::default()
+    });
+    execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+    let msg = ExecuteMsg::MsgStartUnbonding {
+        token_id: String::from("0"),
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // THEN the record completes with the unbonding seconds of the config
+    let records = DELEGATIONS.load(deps.as_ref().storage, "0").unwrap();
+    assert_eq!(records[0].completion_time, env.block.time.plus_seconds(300));
+    assert!(res
+        .attributes
+        .contains(&Attribute::new("unbonding_seconds_fallback", "300")));
+}
+
+#[test]
+fn test_unbonding_time_without_staking_params() {
+    // GIVEN the chain does not allow the staking params query
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    deps.querier.remove_staking_params();
+    let msg = ExecuteMsg::MsgRedelegate {
+        token_id: String::from("0"),
+        validators: None,
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // THEN the redelegation completes with the unbonding seconds of the config
+    let records = DELEGATIONS.load(deps.as_ref().storage, "0").unwrap();
+    assert_eq!(records[0].completion_time, env.block.time.plus_seconds(100));
+    assert!(res
+        .attributes
+        .contains(&Attribute::new("unbonding_seconds_fallback", "100")));
+}
+
+#[test]
+fn test_unbonding_time_from_staking_params_without_fallback() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::MsgStartUnbonding {
+        token_id: String::from("0"),
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN the fallback is not reported
+    assert!(res
+        .attributes
+        .iter()
+        .all(|attr| attr.key != "unbonding_seconds_fallback"));
+}
+
+#[test]
+fn test_unbonding_time_from_reply_data() {
+    // GIVEN the response of the undelegation has the completion time
+    // and the event reports a different one which is ignored
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::MsgStartUnbonding {
+        token_id: String::from("0"),
+    };
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+    let mut response = MsgUndelegateResponse {
+        completion_time: Some(Default::default()),
+    };
+    if let Some(completion_time) = response.completion_time.as_mut() {
+        completion_time.seconds = 1_571_797_800;
+        completion_time.nanos = 250_000_000;
+    }
+    let data = response.encode_to_vec();
+    let msg = Reply {
+        id: 11,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![Event::new("alliance.alliance.UndelegateAllianceEvent")
+                .add_attribute("completion_time", "\"2019-10-23T02:30:00.5Z\"")],
+            data: Some(Binary::from(data)),
+        }),
+    };
+
+    // WHEN
+    reply(deps.as_mut(), env, msg).unwrap();
+
+    // THEN the record completes at the time of the response
+    let records = DELEGATIONS.load(deps.as_ref().storage, "0").unwrap();
+    assert_eq!(
+        records[0].completion_time,
+        Timestamp::from_nanos(1_571_797_800_250_000_000)
+    );
+}
+
+#[test]
+fn test_unbonding_time_from_reply_event() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::MsgStartUnbonding {
+        token_id: String::from("0"),
+    };
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+    let msg = completion_time_reply(vec![Event::new(
+        "alliance.alliance.UndelegateAllianceEvent",
+    )
+    .add_attribute("completion_time", "\"2019-10-23T02:30:00.5Z\"")]);
+
+    // WHEN
+    let res = reply(deps.as_mut(), env, msg).unwrap();
+
+    // THEN the record and the NFT complete at the time reported by the chain
+    let completion_time = Timestamp::from_nanos(1_571_797_800_500_000_000);
+    let records = DELEGATIONS.load(deps.as_ref().storage, "0").unwrap();
+    assert_eq!(records[0].completion_time, completion_time);
+    assert_eq!(
+        UNBONDINGS
+            .load(deps.as_ref().storage, ("token", completion_time.nanos()))
+            .unwrap()
+            .amount,
+        Uint128::new(100)
+    );
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::reply_always(
+            WasmMsg::Execute {
+                contract_addr: String::from("terra..."),
+                msg: to_binary(&Cw721ExecuteMsg::UpdateExtension {
+                    token_id: String::from("0"),
+                    extension: Some(CW721Metadata {
+                        name: Some(String::from("Alliance NFT #0")),
+                        attributes: Some(vec![CW721Trait {
+                            display_type: String::from("Unbonding"),
+                            trait_type: String::from("validator1"),
+                            timestamp: completion_time,
+                            value: String::from("100@token"),
+                        }]),
+                        ..Default::default()
+                    }),
+                })
+                .unwrap(),
+                funds: vec![],
+            },
+            10,
+        )]
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("method", "completion_time_reply"),
+            Attribute::new("completion_time", completion_time.to_string())
+        ]
+    );
+}
+
+#[test]
+fn test_redelegation_time_from_reply_event() {
+    // GIVEN the source validator is not bonded so the redelegation completes at once
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::MsgRedelegate {
+        token_id: String::from("0"),
+        validators: None,
+    };
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+    let msg = completion_time_reply(vec![Event::new(
+        "alliance.alliance.RedelegateAllianceEvent",
+    )
+    .add_attribute("completion_time", "\"2019-10-23T02:23:39.879305533Z\"")]);
+
+    // WHEN
+    reply(deps.as_mut(), env.clone(), msg).unwrap();
+
+    // THEN the redelegation completes at the block time
+    let records = DELEGATIONS.load(deps.as_ref().storage, "0").unwrap();
+    assert_eq!(
+        records,
+        vec![DelegationRecord {
+            validator: String::from("validator"),
+            coin: Coin::new(100, "token"),
+            status: DisplayType::Redelegating,
+            completion_time: env.block.time,
+        }]
+    );
+}
+
+#[test]
+fn test_parse_timestamp() {
+    // GIVEN the timestamps of the events, WHEN parsed, THEN
+    // the fractions of a second and the UTC offsets are applied
+    assert_eq!(
+        parse_timestamp("\"2019-10-23T02:30:00Z\""),
+        Some(Timestamp::from_seconds(1_571_797_800))
+    );
+    assert_eq!(
+        parse_timestamp("\"2019-10-23T02:30:00.000000001Z\""),
+        Some(Timestamp::from_nanos(1_571_797_800_000_000_001))
+    );
+    assert_eq!(
+        parse_timestamp("2019-10-23T04:30:00.5+02:00"),
+        Some(Timestamp::from_nanos(1_571_797_800_500_000_000))
+    );
+    assert_eq!(
+        parse_timestamp("2019-10-22T21:00:00-05:30"),
+        Some(Timestamp::from_seconds(1_571_797_800))
+    );
+
+    // AND the leap days are only valid in the leap years
+    assert_eq!(
+        parse_timestamp("2024-02-29T00:00:00Z"),
+        Some(Timestamp::from_seconds(1_709_164_800))
+    );
+    assert_eq!(
+        parse_timestamp("2000-02-29T00:00:00Z"),
+        Some(Timestamp::from_seconds(951_782_400))
+    );
+    assert_eq!(parse_timestamp("2023-02-29T00:00:00Z"), None);
+    assert_eq!(parse_timestamp("2100-02-29T00:00:00Z"), None);
+
+    // AND the malformed timestamps are rejected
+    assert_eq!(parse_timestamp("2019-10-23T02:30:00"), None);
+    assert_eq!(parse_timestamp("2019-10-23T02:30:00.Z"), None);
+    assert_eq!(parse_timestamp("2019-10-23T02:30:00.0000000001Z"), None);
+    assert_eq!(parse_timestamp("2019-13-23T02:30:00Z"), None);
+    assert_eq!(parse_timestamp("2019-10-23T24:30:00Z"), None);
+    assert_eq!(parse_timestamp("2019-10-23T02:30:00+2:00"), None);
+    assert_eq!(parse_timestamp("1969-12-31T23:59:59Z"), None);
+}
+
+#[test]
+fn test_completion_time_without_reply_event() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::MsgStartUnbonding {
+        token_id: String::from("0"),
+    };
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // WHEN the chain does not report the completion time
+    let res = reply(deps.as_mut(), env.clone(), completion_time_reply(vec![])).unwrap();
+
+    // THEN the estimate from the staking params is kept
+    let records = DELEGATIONS.load(deps.as_ref().storage, "0").unwrap();
+    assert_eq!(records[0].completion_time, env.block.time.plus_seconds(100));
+    assert_eq!(res.messages, vec![]);
+}
@@ -0,0 +1,350 @@
+use crate::msg::{ExecuteMsg, QueryMsg};
+use crate::rewards;
+use crate::state::{
+    DelegationRecord, DisplayType, PauseFlags, AUTO_COMPOUND, COMPOUND_TMP, DELEGATIONS,
+    PAUSE_FLAGS,
+};
+use crate::{
+    entry_points::{
+        execute::{execute, Cw721ExecuteMsg},
+        query::query,
+        reply::reply,
+    },
+    tests::utils::{chain_with_contract_delegation, mirror_reward_indexes},
+    ContractError,
+};
+use cosmwasm_std::{
+    coins, from_binary, testing::mock_info, to_binary, Attribute, BankMsg, Binary, Coin, CosmosMsg,
+    Empty, Reply, SubMsg, SubMsgResponse, Timestamp, WasmMsg,
+};
+use cw721_progressive_metadata::state::{Metadata as CW721Metadata, Trait as CW721Trait};
+use terra_proto_rs::{
+    alliance::alliance::{MsgClaimDelegationRewards, MsgDelegate},
+    cosmos::base::v1beta1::Coin as CosmosNativeCoin,
+    traits::Message,
+};
+
+#[test]
+fn test_set_auto_compound() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::SetAutoCompound {
+        token_id: String::from("0"),
+        enabled: true,
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // THEN
+    let enabled: bool = from_binary(
+        &query(
+            deps.as_ref(),
+            env,
+            QueryMsg::GetAutoCompound {
+                token_id: String::from("0"),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    assert!(enabled);
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "set_auto_compound"),
+            Attribute::new("sender", "creator"),
+            Attribute::new("token_id", "0"),
+            Attribute::new("enabled", "true")
+        ]
+    );
+}
+
+#[test]
+fn test_set_auto_compound_with_no_access() {
+    // GIVEN
+    let (mut deps, env, _) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::SetAutoCompound {
+        token_id: String::from("0"),
+        enabled: true,
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, mock_info("stranger", &[]), msg);
+
+    // THEN
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::UnauthorizedNFTOwnere(String::from("creator"), String::from("stranger"))
+    );
+}
+
+#[test]
+fn test_compound() {
+    // GIVEN an NFT with auto compound enabled
+    let (mut deps, env, _) = chain_with_contract_delegation(String::from("terra..."));
+    AUTO_COMPOUND
+        .save(deps.as_mut().storage, "0", &Empty {})
+        .unwrap();
+    let msg = ExecuteMsg::Compound {
+        token_ids: vec![String::from("0")],
+    };
+
+    // WHEN anyone compounds it
+    let res = execute(deps.as_mut(), env, mock_info("keeper", &[]), msg).unwrap();
+
+    // THEN the rewards of the delegated records are claimed
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::reply_on_success(
+            CosmosMsg::Stargate {
+                type_url: String::from("/alliance.alliance.MsgClaimDelegationRewards"),
+                value: Binary::from(
+                    MsgClaimDelegationRewards {
+                        delegator_address: String::from("cosmos2contract"),
+                        validator_address: String::from("validator1"),
+                        denom: String::from("token"),
+                    }
+                    .encode_to_vec(),
+                ),
+            },
+            12,
+        )]
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "compound"),
+            Attribute::new("token_ids", "0")
+        ]
+    );
+}
+
+#[test]
+fn test_compound_matured_redelegation() {
+    // GIVEN an NFT with auto compound enabled which redelegation has matured
+    let (mut deps, env, _) = chain_with_contract_delegation(String::from("terra..."));
+    AUTO_COMPOUND
+        .save(deps.as_mut().storage, "0", &Empty {})
+        .unwrap();
+    let mut records = DELEGATIONS.load(deps.as_ref().storage, "0").unwrap();
+    records[0].status = DisplayType::Redelegating;
+    records[0].completion_time = env.block.time.minus_seconds(1);
+    DELEGATIONS
+        .save(deps.as_mut().storage, "0", &records)
+        .unwrap();
+    let msg = ExecuteMsg::Compound {
+        token_ids: vec![String::from("0")],
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), mock_info("keeper", &[]), msg).unwrap();
+
+    // THEN the record is promoted to Delegated before claiming its rewards
+    let records = DELEGATIONS.load(deps.as_ref().storage, "0").unwrap();
+    assert_eq!(records[0].status, DisplayType::Delegated);
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        res.messages[0],
+        SubMsg::reply_always(
+            WasmMsg::Execute {
+                contract_addr: String::from("terra..."),
+                msg: to_binary(&Cw721ExecuteMsg::UpdateExtension {
+                    token_id: String::from("0"),
+                    extension: Some(CW721Metadata {
+                        attributes: Some(vec![CW721Trait {
+                            display_type: String::from("Delegated"),
+                            trait_type: String::from("validator1"),
+                            timestamp: env.block.time.minus_seconds(1),
+                            value: String::from("100@token"),
+                        }]),
+                        name: Some(String::from("Alliance NFT #0")),
+                        ..Default::default()
+                    }),
+                })
+                .unwrap(),
+                funds: vec![],
+            },
+            10,
+        )
+    );
+    assert_eq!(res.messages[1].id, 12);
+}
+
+#[test]
+fn test_compound_disabled() {
+    // GIVEN an NFT without auto compound
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::Compound {
+        token_ids: vec![String::from("0")],
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg);
+
+    // THEN
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::AutoCompoundDisabled(String::from("0"))
+    );
+}
+
+#[test]
+fn test_compound_paused() {
+    // GIVEN the claims are paused
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    AUTO_COMPOUND
+        .save(deps.as_mut().storage, "0", &Empty {})
+        .unwrap();
+    PAUSE_FLAGS
+        .save(
+            deps.as_mut().storage,
+            &PauseFlags {
+                claims: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let msg = ExecuteMsg::Compound {
+        token_ids: vec![String::from("0")],
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg);
+
+    // THEN
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Paused(String::from("claims"))
+    );
+}
+
+#[test]
+fn test_compound_reply() {
+    // GIVEN an NFT with auto compound enabled
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    AUTO_COMPOUND
+        .save(deps.as_mut().storage, "0", &Empty {})
+        .unwrap();
+    rewards::add_delegation(
+        deps.as_mut().storage,
+        "0",
+        "validator1",
+        &Coin::new(100, "token"),
+    )
+    .unwrap();
+    mirror_reward_indexes(&mut deps);
+    let msg = ExecuteMsg::Compound {
+        token_ids: vec![String::from("0")],
+    };
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+    deps.querier.update_balance(
+        "cosmos2contract",
+        vec![Coin::new(80, "stoken"), Coin::new(40, "uluna")],
+    );
+
+    // WHEN the rewards are claimed
+    let reply_msg = Reply {
+        id: 12,
+        result: cosmwasm_std::SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }),
+    };
+    let res = reply(deps.as_mut(), env.clone(), reply_msg).unwrap();
+
+    // THEN the alliance assets are delegated to the same validator
+    let records = vec![
+        DelegationRecord {
+            validator: String::from("validator1"),
+            coin: Coin::new(100, "token"),
+            status: DisplayType::Delegated,
+            completion_time: Timestamp::from_seconds(100),
+        },
+        DelegationRecord {
+            validator: String::from("validator1"),
+            coin: Coin::new(80, "stoken"),
+            status: DisplayType::Delegated,
+            completion_time: env.block.time,
+        },
+    ];
+    assert_eq!(
+        DELEGATIONS.load(deps.as_ref().storage, "0").unwrap(),
+        records
+    );
+    assert_eq!(res.messages.len(), 3);
+    assert_eq!(
+        res.messages[0],
+        SubMsg::new(CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgDelegate"),
+            value: Binary::from(
+                MsgDelegate {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator1"),
+                    amount: Some(CosmosNativeCoin {
+                        denom: String::from("stoken"),
+                        amount: String::from("80"),
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        })
+    );
+
+    // AND the NFT metadata grows with the compounded rewards
+    assert_eq!(
+        res.messages[1],
+        SubMsg::reply_always(
+            WasmMsg::Execute {
+                contract_addr: String::from("terra..."),
+                msg: to_binary(&Cw721ExecuteMsg::UpdateExtension {
+                    token_id: String::from("0"),
+                    extension: Some(CW721Metadata {
+                        name: Some(String::from("Alliance NFT #0")),
+                        attributes: Some(vec![
+                            CW721Trait {
+                                display_type: String::from("Delegated"),
+                                trait_type: String::from("validator1"),
+                                timestamp: Timestamp::from_seconds(100),
+                                value: String::from("100@token"),
+                            },
+                            CW721Trait {
+                                display_type: String::from("Delegated"),
+                                trait_type: String::from("validator1"),
+                                timestamp: env.block.time,
+                                value: String::from("80@stoken"),
+                            }
+                        ]),
+                        ..Default::default()
+                    }),
+                })
+                .unwrap(),
+                funds: vec![],
+            },
+            10,
+        )
+    );
+
+    // AND the rewards that are not alliance assets are sent to the owner
+    assert_eq!(
+        res.messages[2],
+        SubMsg::new(BankMsg::Send {
+            to_address: String::from("creator"),
+            amount: coins(40, "uluna"),
+        })
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("method", "compound_reply"),
+            Attribute::new("token_id", "0"),
+            Attribute::new("compounded", "80stoken")
+        ]
+    );
+    let tmp = COMPOUND_TMP.load(deps.as_ref().storage).unwrap();
+    assert!(tmp.delegations.is_empty());
+    assert_eq!(
+        tmp.balances,
+        vec![Coin::new(0, "stoken"), Coin::new(0, "uluna")]
+    );
+}
@@ -1,5 +1,7 @@
-use crate::msg::ExecuteMsg;
-use crate::tests::utils::chain_with_contract;
+use crate::msg::{ExecuteMsg, ValidatorWeight};
+use crate::rewards;
+use crate::state::{SplitMode, CFG};
+use crate::tests::utils::{chain_with_contract, chain_with_contract_delegation, reply_success};
 use crate::{
     entry_points::{
         execute::{execute, Cw721ExecuteMsg},
@@ -8,8 +10,8 @@ use crate::{
     ContractError,
 };
 use cosmwasm_std::{
-    testing::mock_info, to_binary, Attribute, Binary, Coin, CosmosMsg, Reply, Response, StdError,
-    SubMsg, SubMsgResponse, WasmMsg,
+    coins, testing::mock_info, to_binary, Attribute, BankMsg, Binary, Coin, CosmosMsg, Reply,
+    Response, StdError, SubMsg, SubMsgResponse, Timestamp, Uint128, WasmMsg,
 };
 use cw721_progressive_metadata::state::{Metadata as CW721Metadata, Trait as CW721Trait};
 use terra_proto_rs::{
@@ -21,7 +23,10 @@ use terra_proto_rs::{
 fn test_delegate() {
     // GIVEN
     let (mut deps, env, info) = chain_with_contract();
-    let msg = ExecuteMsg::MsgDelegate {};
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: None,
+        validators: None,
+    };
 
     // WHEN
     let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
@@ -54,20 +59,23 @@ fn test_delegate() {
     );
     assert_eq!(res.messages[0], instantiate_sub_msg);
 
-    let delegate_sub_msg = SubMsg::new(CosmosMsg::Stargate {
-        type_url: String::from("/alliance.alliance.MsgDelegate"),
-        value: Binary::from(
-            MsgDelegate {
-                delegator_address: String::from("cosmos2contract"),
-                validator_address: String::from("validator"),
-                amount: Some(CosmosNativeCoin {
-                    denom: String::from("token"),
-                    amount: String::from("100"),
-                }),
-            }
-            .encode_to_vec(),
-        ),
-    });
+    let delegate_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgDelegate"),
+            value: Binary::from(
+                MsgDelegate {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator"),
+                    amount: Some(CosmosNativeCoin {
+                        denom: String::from("token"),
+                        amount: String::from("100"),
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        14,
+    );
     assert_eq!(res.messages[1], delegate_sub_msg);
 
     assert_eq!(
@@ -101,9 +109,12 @@ fn test_delegate_multiple_tokens() {
     let (mut deps, env, _) = chain_with_contract();
     let info = mock_info(
         "creator",
-        &vec![Coin::new(100, "token"), Coin::new(100, "stoken")],
+        &[Coin::new(100, "token"), Coin::new(100, "stoken")],
     );
-    let msg = ExecuteMsg::MsgDelegate {};
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: None,
+        validators: None,
+    };
 
     // WHEN
     let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
@@ -144,36 +155,42 @@ fn test_delegate_multiple_tokens() {
     );
     assert_eq!(res.messages[0], instantiate_sub_msg);
 
-    let delegate_sub_msg = SubMsg::new(CosmosMsg::Stargate {
-        type_url: String::from("/alliance.alliance.MsgDelegate"),
-        value: Binary::from(
-            MsgDelegate {
-                delegator_address: String::from("cosmos2contract"),
-                validator_address: String::from("validator"),
-                amount: Some(CosmosNativeCoin {
-                    denom: String::from("token"),
-                    amount: String::from("100"),
-                }),
-            }
-            .encode_to_vec(),
-        ),
-    });
+    let delegate_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgDelegate"),
+            value: Binary::from(
+                MsgDelegate {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator"),
+                    amount: Some(CosmosNativeCoin {
+                        denom: String::from("token"),
+                        amount: String::from("100"),
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        14,
+    );
     assert_eq!(res.messages[1], delegate_sub_msg);
 
-    let delegate_sub_msg_2 = SubMsg::new(CosmosMsg::Stargate {
-        type_url: String::from("/alliance.alliance.MsgDelegate"),
-        value: Binary::from(
-            MsgDelegate {
-                delegator_address: String::from("cosmos2contract"),
-                validator_address: String::from("validator1"),
-                amount: Some(CosmosNativeCoin {
-                    denom: String::from("stoken"),
-                    amount: String::from("100"),
-                }),
-            }
-            .encode_to_vec(),
-        ),
-    });
+    let delegate_sub_msg_2 = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgDelegate"),
+            value: Binary::from(
+                MsgDelegate {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator1"),
+                    amount: Some(CosmosNativeCoin {
+                        denom: String::from("stoken"),
+                        amount: String::from("100"),
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        14,
+    );
     assert_eq!(res.messages[2], delegate_sub_msg_2);
 
     assert_eq!(
@@ -205,7 +222,10 @@ fn test_delegate_multiple_tokens() {
 fn test_delegate_reply_error() {
     // GIVEN
     let (mut deps, env, info) = chain_with_contract();
-    let msg = ExecuteMsg::MsgDelegate {};
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: None,
+        validators: None,
+    };
 
     // WHEN
     let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
@@ -238,20 +258,23 @@ fn test_delegate_reply_error() {
     );
     assert_eq!(res.messages[0], instantiate_sub_msg);
 
-    let delegate_sub_msg = SubMsg::new(CosmosMsg::Stargate {
-        type_url: String::from("/alliance.alliance.MsgDelegate"),
-        value: Binary::from(
-            MsgDelegate {
-                delegator_address: String::from("cosmos2contract"),
-                validator_address: String::from("validator"),
-                amount: Some(CosmosNativeCoin {
-                    denom: String::from("token"),
-                    amount: String::from("100"),
-                }),
-            }
-            .encode_to_vec(),
-        ),
-    });
+    let delegate_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgDelegate"),
+            value: Binary::from(
+                MsgDelegate {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator"),
+                    amount: Some(CosmosNativeCoin {
+                        denom: String::from("token"),
+                        amount: String::from("100"),
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        14,
+    );
     assert_eq!(res.messages[1], delegate_sub_msg);
 
     assert_eq!(
@@ -278,10 +301,13 @@ fn test_delegate_reply_error() {
 fn test_delegate_no_funds() {
     // GIVEN
     let (mut deps, env, _info) = chain_with_contract();
-    let info = mock_info("creator", &vec![]);
-    let info2 = mock_info("creator", &vec![Coin::new(0, "token")]);
+    let info = mock_info("creator", &[]);
+    let info2 = mock_info("creator", &[Coin::new(0, "token")]);
 
-    let msg = ExecuteMsg::MsgDelegate {};
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: None,
+        validators: None,
+    };
 
     // WHEN
     let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
@@ -291,3 +317,415 @@ fn test_delegate_no_funds() {
     assert_eq!(res, ContractError::NoFundsReceived {});
     assert_eq!(res2, ContractError::NoFundsReceived {});
 }
+
+#[test]
+fn test_top_up_delegation() {
+    // GIVEN an NFT delegating 100 token to validator1
+    let (mut deps, env, _) = chain_with_contract_delegation(String::from("terra..."));
+    let info = mock_info(
+        "creator",
+        &[Coin::new(50, "token"), Coin::new(10, "stoken")],
+    );
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: Some(String::from("0")),
+        validators: None,
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // THEN the token is merged in the existing trait and
+    // the new denom is appended to the NFT metadata
+    assert_eq!(3, res.messages.len());
+    let update_metadata = SubMsg::reply_always(
+        WasmMsg::Execute {
+            contract_addr: String::from("terra..."),
+            msg: to_binary(&Cw721ExecuteMsg::UpdateExtension {
+                token_id: String::from("0"),
+                extension: Some(CW721Metadata {
+                    name: Some(String::from("Alliance NFT #0")),
+                    attributes: Some(vec![
+                        CW721Trait {
+                            display_type: String::from("Delegated"),
+                            trait_type: String::from("validator1"),
+                            timestamp: Timestamp::from_seconds(100),
+                            value: String::from("150@token"),
+                        },
+                        CW721Trait {
+                            display_type: String::from("Delegated"),
+                            trait_type: String::from("validator"),
+                            timestamp: env.block.time,
+                            value: String::from("10@stoken"),
+                        },
+                    ]),
+                    ..Default::default()
+                }),
+            })
+            .unwrap(),
+            funds: vec![],
+        },
+        7,
+    );
+    assert_eq!(res.messages[0], update_metadata);
+
+    let delegate_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgDelegate"),
+            value: Binary::from(
+                MsgDelegate {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator1"),
+                    amount: Some(CosmosNativeCoin {
+                        denom: String::from("token"),
+                        amount: String::from("50"),
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        14,
+    );
+    assert_eq!(res.messages[1], delegate_sub_msg);
+    assert_eq!(
+        vec![
+            Attribute::new("action", "delegate"),
+            Attribute::new("sender", "creator"),
+            Attribute::new("token_id", "0")
+        ],
+        res.attributes
+    );
+
+    // REPLY
+    let reply_msg = Reply {
+        id: 7,
+        result: cosmwasm_std::SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }),
+    };
+    let reply_res = reply(deps.as_mut(), env, reply_msg).unwrap();
+    assert_eq!(
+        reply_res,
+        Response::new().add_attribute("method", "top_up_reply")
+    );
+}
+
+#[test]
+fn test_top_up_delegation_with_no_access() {
+    // GIVEN
+    let (mut deps, env, _) = chain_with_contract_delegation(String::from("terra..."));
+    let info = mock_info("random", &[Coin::new(50, "token")]);
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: Some(String::from("0")),
+        validators: None,
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+
+    // THEN
+    assert_eq!(
+        res,
+        ContractError::UnauthorizedNFTOwnere(String::from("creator"), String::from("random"))
+    );
+}
+
+#[test]
+fn test_top_up_unbonding_nft() {
+    // GIVEN an NFT that is unbonding
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra1..."));
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: Some(String::from("0")),
+        validators: None,
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+
+    // THEN
+    assert_eq!(res, ContractError::TopUpImpossible(String::from("0")));
+}
+
+#[test]
+fn test_delegate_split_between_validators() {
+    // GIVEN a hub that splits each coin between two validators
+    let (mut deps, env, _) = chain_with_contract();
+    CFG.update(
+        deps.as_mut().storage,
+        |mut cfg| -> Result<_, ContractError> {
+            cfg.split_mode = SplitMode::Even;
+            cfg.max_validators_per_denom = 2;
+            Ok(cfg)
+        },
+    )
+    .unwrap();
+    let info = mock_info("creator", &[Coin::new(101, "token")]);
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: None,
+        validators: None,
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // THEN the NFT records one entry per validator
+    assert_eq!(3, res.messages.len());
+    let mint_sub_msg = SubMsg::reply_always(
+        WasmMsg::Execute {
+            contract_addr: String::from("terra..."),
+            msg: to_binary(&Cw721ExecuteMsg::Mint {
+                token_id: String::from("0"),
+                owner: String::from("creator"),
+                token_uri: None,
+                extension: Some(CW721Metadata {
+                    name: Some(String::from("Alliance NFT #0")),
+                    attributes: Some(vec![
+                        CW721Trait {
+                            display_type: String::from("Delegated"),
+                            trait_type: String::from("validator"),
+                            timestamp: env.block.time,
+                            value: String::from("51@token"),
+                        },
+                        CW721Trait {
+                            display_type: String::from("Delegated"),
+                            trait_type: String::from("validator1"),
+                            timestamp: env.block.time,
+                            value: String::from("50@token"),
+                        },
+                    ]),
+                    ..Default::default()
+                }),
+            })
+            .unwrap(),
+            funds: vec![],
+        },
+        2,
+    );
+    assert_eq!(res.messages[0], mint_sub_msg);
+}
+
+#[test]
+fn test_delegate_to_user_validators() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract();
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: None,
+        validators: Some(vec![
+            ValidatorWeight {
+                address: String::from("validator2"),
+                weight: Uint128::new(1),
+            },
+            ValidatorWeight {
+                address: String::from("validator3"),
+                weight: Uint128::new(3),
+            },
+        ]),
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+    // THEN the funds are divided by the weights chosen by the user
+    assert_eq!(3, res.messages.len());
+    let mint_sub_msg = SubMsg::reply_always(
+        WasmMsg::Execute {
+            contract_addr: String::from("terra..."),
+            msg: to_binary(&Cw721ExecuteMsg::Mint {
+                token_id: String::from("0"),
+                owner: String::from("creator"),
+                token_uri: None,
+                extension: Some(CW721Metadata {
+                    name: Some(String::from("Alliance NFT #0")),
+                    attributes: Some(vec![
+                        CW721Trait {
+                            display_type: String::from("Delegated"),
+                            trait_type: String::from("validator2"),
+                            timestamp: env.block.time,
+                            value: String::from("25@token"),
+                        },
+                        CW721Trait {
+                            display_type: String::from("Delegated"),
+                            trait_type: String::from("validator3"),
+                            timestamp: env.block.time,
+                            value: String::from("75@token"),
+                        },
+                    ]),
+                    ..Default::default()
+                }),
+            })
+            .unwrap(),
+            funds: vec![],
+        },
+        2,
+    );
+    assert_eq!(res.messages[0], mint_sub_msg);
+
+    let delegate_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgDelegate"),
+            value: Binary::from(
+                MsgDelegate {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator3"),
+                    amount: Some(CosmosNativeCoin {
+                        denom: String::from("token"),
+                        amount: String::from("75"),
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        14,
+    );
+    assert_eq!(res.messages[2], delegate_sub_msg);
+}
+
+#[test]
+fn test_delegate_to_not_allowed_validators() {
+    // GIVEN the hub only allows to delegate to validator2
+    let (mut deps, env, info) = chain_with_contract();
+    let mut cfg = CFG.load(&deps.storage).unwrap();
+    cfg.validators_allowlist = Some(vec![String::from("validator2")]);
+    CFG.save(&mut deps.storage, &cfg).unwrap();
+
+    // WHEN delegating to a validator out of the allowlist or not active
+    let errors = ["validator3", "validator9"].map(|validator| {
+        let msg = ExecuteMsg::MsgDelegate {
+            token_id: None,
+            validators: Some(vec![ValidatorWeight {
+                address: String::from(validator),
+                weight: Uint128::new(1),
+            }]),
+        };
+        execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap_err()
+    });
+
+    // THEN
+    assert_eq!(
+        errors,
+        [
+            ContractError::ValidatorNotAllowed(String::from("validator3")),
+            ContractError::ValidatorNotAllowed(String::from("validator9")),
+        ]
+    );
+
+    // WHEN the weights are invalid
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: None,
+        validators: Some(vec![ValidatorWeight {
+            address: String::from("validator2"),
+            weight: Uint128::zero(),
+        }]),
+    };
+    let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+
+    // THEN
+    assert_eq!(res, ContractError::InvalidValidatorWeights {});
+}
+
+#[test]
+fn test_delegate_with_overflowing_weights() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract();
+
+    // WHEN the sum of the weights overflows
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: None,
+        validators: Some(vec![
+            ValidatorWeight {
+                address: String::from("validator2"),
+                weight: Uint128::MAX,
+            },
+            ValidatorWeight {
+                address: String::from("validator3"),
+                weight: Uint128::MAX,
+            },
+        ]),
+    };
+    let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+
+    // THEN
+    assert_eq!(res, ContractError::ValidatorWeightsOverflow {});
+}
+
+#[test]
+fn test_delegate_unsupported_denom() {
+    // GIVEN
+    let (mut deps, env, _) = chain_with_contract();
+    let info = mock_info(
+        "creator",
+        &[Coin::new(100, "token"), Coin::new(100, "uluna")],
+    );
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: None,
+        validators: None,
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+
+    // THEN
+    assert_eq!(res, ContractError::UnsupportedDenom(String::from("uluna")));
+}
+
+#[test]
+fn test_delegate_refund_unsupported_denom() {
+    // GIVEN the hub refunds the denoms that are not alliance assets
+    let (mut deps, env, _) = chain_with_contract();
+    let mut cfg = CFG.load(&deps.storage).unwrap();
+    cfg.refund_unsupported_denoms = true;
+    CFG.save(&mut deps.storage, &cfg).unwrap();
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: None,
+        validators: None,
+    };
+
+    // WHEN only unsupported denoms are deposited
+    let info = mock_info("creator", &[Coin::new(100, "uluna")]);
+    let res = execute(deps.as_mut(), env.clone(), info, msg.clone()).unwrap_err();
+
+    // THEN there is nothing to delegate
+    assert_eq!(res, ContractError::UnsupportedDenom(String::from("uluna")));
+
+    // WHEN the deposit also has alliance assets
+    let info = mock_info(
+        "creator",
+        &[Coin::new(100, "token"), Coin::new(100, "uluna")],
+    );
+    let res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN the alliance assets are delegated and the rest refunded
+    assert_eq!(3, res.messages.len());
+    assert_eq!(
+        res.messages[2],
+        SubMsg::new(BankMsg::Send {
+            to_address: String::from("creator"),
+            amount: vec![Coin::new(100, "uluna")],
+        })
+    );
+}
+
+#[test]
+fn test_delegate_credits_paid_rewards() {
+    // GIVEN the hub delegating 100 token of the NFT 0 to validator
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    deps.querier
+        .update_balance("cosmos2contract", coins(100, "token"));
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: None,
+        validators: None,
+    };
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // WHEN x/alliance pays 20 uluna of pending rewards while delegating
+    deps.querier
+        .update_balance("cosmos2contract", coins(20, "uluna"));
+    reply_success(&mut deps, &env, 14);
+
+    // THEN the rewards are earned by the NFT that was already delegating
+    let pending = |token_id| {
+        rewards::pending_rewards(&deps.storage, token_id, "validator", "token", &[]).unwrap()
+    };
+    assert_eq!(pending("0"), coins(20, "uluna"));
+    assert_eq!(pending("1"), vec![]);
+}
@@ -0,0 +1,229 @@
+use crate::msg::{ExecuteMsg, UpdateConfigMsg};
+use crate::rewards;
+use crate::state::{ProtocolFee, CFG, CLAIM_REWARDS_TMP, REWARD_INDEXES, REWARD_RESERVES};
+use crate::{
+    entry_points::{execute::execute, reply::reply},
+    tests::utils::{chain_with_contract, chain_with_contract_delegation, AllianceDeps},
+    ContractError,
+};
+use cosmwasm_std::{
+    coins, Addr, Attribute, BankMsg, Binary, Coin, CosmosMsg, Decimal, Reply, Response, SubMsg,
+    SubMsgResponse,
+};
+use terra_proto_rs::{
+    alliance::alliance::MsgDelegate, cosmos::base::v1beta1::Coin as CosmosNativeCoin,
+    traits::Message,
+};
+
+fn protocol_fee(reward_fee: u64, deposit_fee: u64) -> ProtocolFee {
+    ProtocolFee {
+        treasury: Addr::unchecked("treasury"),
+        reward_fee: Decimal::percent(reward_fee),
+        deposit_fee: Decimal::percent(deposit_fee),
+    }
+}
+
+fn set_protocol_fee(deps: &mut AllianceDeps, protocol_fee: ProtocolFee) {
+    CFG.update(
+        deps.as_mut().storage,
+        |mut cfg| -> Result<_, ContractError> {
+            cfg.protocol_fee = Some(protocol_fee);
+            Ok(cfg)
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_delegate_with_deposit_fee() {
+    // GIVEN a hub taking 5% of the deposits
+    let (mut deps, env, info) = chain_with_contract();
+    set_protocol_fee(&mut deps, protocol_fee(10, 5));
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: None,
+        validators: None,
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN the rest of the deposit is delegated
+    assert_eq!(3, res.messages.len());
+    let delegate_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgDelegate"),
+            value: Binary::from(
+                MsgDelegate {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator"),
+                    amount: Some(CosmosNativeCoin {
+                        denom: String::from("token"),
+                        amount: String::from("95"),
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        14,
+    );
+    assert_eq!(res.messages[1], delegate_sub_msg);
+
+    // AND the fee is sent to the treasury
+    assert_eq!(
+        res.messages[2],
+        SubMsg::new(BankMsg::Send {
+            to_address: String::from("treasury"),
+            amount: coins(5, "token"),
+        })
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "delegate"),
+            Attribute::new("sender", "creator"),
+            Attribute::new("deposit_fee", "5token")
+        ]
+    );
+}
+
+#[test]
+fn test_claim_rewards_reply_with_reward_fee() {
+    // GIVEN a hub taking 10% of the rewards and two NFTs
+    // delegating to the same validator and denom
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    set_protocol_fee(&mut deps, protocol_fee(10, 5));
+    rewards::add_delegation(
+        deps.as_mut().storage,
+        "0",
+        "validator1",
+        &Coin::new(100, "token"),
+    )
+    .unwrap();
+    rewards::add_delegation(
+        deps.as_mut().storage,
+        "1",
+        "validator1",
+        &Coin::new(300, "token"),
+    )
+    .unwrap();
+    deps.querier
+        .update_balance("cosmos2contract", coins(50, "uluna"));
+    let msg = ExecuteMsg::MsgClaimRewards {
+        token_id: String::from("0"),
+    };
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+    deps.querier
+        .update_balance("cosmos2contract", coins(130, "uluna"));
+
+    // WHEN
+    let reply_msg = Reply {
+        id: 6,
+        result: cosmwasm_std::SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }),
+    };
+    let res = reply(deps.as_mut(), env, reply_msg).unwrap();
+
+    // THEN the fee is sent to the treasury and the
+    // share of the rest of the rewards to the NFT owner
+    assert_eq!(
+        res,
+        Response::new()
+            .add_attribute("method", "claim_rewards_reply")
+            .add_attribute("recipient", "creator")
+            .add_attribute("reward_fee", "8uluna")
+            .add_message(BankMsg::Send {
+                to_address: String::from("treasury"),
+                amount: coins(8, "uluna"),
+            })
+            .add_message(BankMsg::Send {
+                to_address: String::from("creator"),
+                amount: coins(18, "uluna"),
+            })
+    );
+
+    // AND the sent coins are not part of the next balance snapshot
+    let tmp = CLAIM_REWARDS_TMP.load(deps.as_ref().storage).unwrap();
+    assert_eq!(tmp.balances, coins(104, "uluna"));
+}
+
+#[test]
+fn test_update_config_protocol_fee_too_high() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract();
+    let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg {
+        protocol_fee: Some(protocol_fee(11, 5)),
+        ..Default::default()
+    });
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg);
+
+    // THEN
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::ProtocolFeeTooHigh(String::from("reward"), Decimal::percent(10))
+    );
+}
+
+#[test]
+fn test_update_config_removes_protocol_fee() {
+    // GIVEN a hub with a protocol fee
+    let (mut deps, env, info) = chain_with_contract();
+    set_protocol_fee(&mut deps, protocol_fee(10, 5));
+    let msg = ExecuteMsg::UpdateConfig(UpdateConfigMsg {
+        protocol_fee: Some(protocol_fee(0, 0)),
+        ..Default::default()
+    });
+
+    // WHEN
+    execute(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN
+    let cfg = CFG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.protocol_fee, None);
+}
+
+#[test]
+fn test_claim_rewards_reply_without_shares() {
+    // GIVEN a hub taking 10% of the rewards which claims
+    // the rewards of a delegation without shares
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    set_protocol_fee(&mut deps, protocol_fee(10, 5));
+    REWARD_INDEXES.remove(deps.as_mut().storage, ("validator1", "token"));
+    deps.querier
+        .update_balance("cosmos2contract", coins(50, "uluna"));
+    let msg = ExecuteMsg::MsgClaimRewards {
+        token_id: String::from("0"),
+    };
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+    deps.querier
+        .update_balance("cosmos2contract", coins(130, "uluna"));
+
+    // WHEN
+    let reply_msg = Reply {
+        id: 6,
+        result: cosmwasm_std::SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }),
+    };
+    let res = reply(deps.as_mut(), env, reply_msg).unwrap();
+
+    // THEN the rewards are sent to the treasury without taking the fee
+    assert_eq!(
+        res,
+        Response::new()
+            .add_attribute("method", "claim_rewards_reply")
+            .add_attribute("recipient", "creator")
+            .add_attribute("orphaned_rewards", "80uluna")
+            .add_message(BankMsg::Send {
+                to_address: String::from("treasury"),
+                amount: coins(80, "uluna"),
+            })
+    );
+    let tmp = CLAIM_REWARDS_TMP.load(deps.as_ref().storage).unwrap();
+    assert_eq!(tmp.balances, coins(50, "uluna"));
+    assert!(REWARD_RESERVES.is_empty(deps.as_ref().storage));
+}
@@ -1,7 +1,9 @@
 use crate::entry_points::instantiate::instantiate;
 use crate::entry_points::reply::reply;
-use crate::state::CFG;
+use crate::msg::InstantiateMsg;
+use crate::state::{ValidatorStrategy, CFG};
 use crate::tests::utils::{default_chain, inst_msg};
+use crate::ContractError;
 use cosmwasm_std::{from_binary, Addr, CosmosMsg, Event, Reply, StdError, SubMsgResponse, WasmMsg};
 use cw2::{get_contract_version, ContractVersion};
 use cw721_progressive_metadata::InstantiateMsg as Cw721InstantiateMsg;
@@ -49,16 +51,61 @@ fn test_instantiate() {
     assert_eq!(cfg.unbonding_seconds, 100);
     assert_eq!(cfg.minted_nfts, 0);
     assert_eq!(cfg.nft_contract_addr, None);
+    assert_eq!(cfg.validator_strategy, ValidatorStrategy::PseudoRandom);
+    assert_eq!(
+        cw_ownable::get_ownership(deps.as_ref().storage)
+            .unwrap()
+            .owner,
+        Some(Addr::unchecked("creator"))
+    );
 
     assert_eq!(
         get_contract_version(deps.as_ref().storage).unwrap(),
         ContractVersion {
             contract: String::from("crates.io:cw-alliance-hub"),
-            version: String::from("0.1.0")
+            version: String::from("0.2.0")
         }
     );
 }
 
+#[test]
+fn test_instantiate_with_invalid_curated_validators() {
+    // GIVEN
+    let (mut deps, env, info) = default_chain();
+    let msg = InstantiateMsg {
+        validator_strategy: Some(ValidatorStrategy::Curated {
+            validators: vec![String::from("validator1")],
+        }),
+        ..inst_msg()
+    };
+
+    // WHEN
+    let res = instantiate(deps.as_mut(), env, info, msg).unwrap_err();
+
+    // THEN
+    assert_eq!(
+        res,
+        ContractError::InvalidValidatorAddress(String::from("validator1"))
+    );
+}
+
+#[test]
+fn test_instantiate_with_empty_allowlist() {
+    // GIVEN
+    let (mut deps, env, info) = default_chain();
+    let msg = InstantiateMsg {
+        validators_allowlist: Some(vec![]),
+        ..inst_msg()
+    };
+
+    // WHEN
+    instantiate(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN any active validator is allowed
+    let cfg = CFG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.validators_allowlist, None);
+}
+
 #[test]
 fn test_instantiate_reply() {
     // GIVEN
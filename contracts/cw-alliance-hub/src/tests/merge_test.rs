@@ -0,0 +1,165 @@
+use crate::msg::ExecuteMsg;
+use crate::{
+    entry_points::execute::{execute, Cw721ExecuteMsg},
+    rewards,
+    state::{DELEGATIONS, REWARD_CHECKPOINTS},
+    tests::utils::{
+        chain_with_contract_delegation, mirror_reward_indexes, reply_success, set_delegations,
+    },
+    ContractError,
+};
+use cosmwasm_std::{
+    coins, testing::mock_info, to_binary, Attribute, BankMsg, Binary, Coin, CosmosMsg, SubMsg,
+    Timestamp, Uint128, WasmMsg,
+};
+use cw721_progressive_metadata::state::{Metadata as CW721Metadata, Trait as CW721Trait};
+use terra_proto_rs::{alliance::alliance::MsgClaimDelegationRewards, traits::Message};
+
+fn merge_msg(token_ids: &[&str]) -> ExecuteMsg {
+    ExecuteMsg::MsgMerge {
+        token_ids: token_ids.iter().map(|id| id.to_string()).collect(),
+    }
+}
+
+#[test]
+fn test_merge() {
+    // GIVEN two NFTs delegating 100 token to validator1
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let records = DELEGATIONS.load(&deps.storage, "0").unwrap();
+    set_delegations(&mut deps, "1", records);
+    for token_id in ["0", "1"] {
+        rewards::add_delegation(
+            deps.as_mut().storage,
+            token_id,
+            "validator1",
+            &Coin::new(100, "token"),
+        )
+        .unwrap();
+    }
+    mirror_reward_indexes(&mut deps);
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info, merge_msg(&["0", "1"])).unwrap();
+
+    // THEN the delegations are summed in the first NFT and the second one is burnt
+    assert_eq!(res.messages.len(), 3);
+    let update_metadata = SubMsg::reply_always(
+        WasmMsg::Execute {
+            contract_addr: String::from("terra..."),
+            msg: to_binary(&Cw721ExecuteMsg::UpdateExtension {
+                token_id: String::from("0"),
+                extension: Some(CW721Metadata {
+                    name: Some(String::from("Alliance NFT #0")),
+                    attributes: Some(vec![CW721Trait {
+                        display_type: String::from("Delegated"),
+                        trait_type: String::from("validator1"),
+                        timestamp: Timestamp::from_seconds(100),
+                        value: String::from("200@token"),
+                    }]),
+                    ..Default::default()
+                }),
+            })
+            .unwrap(),
+            funds: vec![],
+        },
+        9,
+    );
+    assert_eq!(res.messages[0], update_metadata);
+
+    let burn_msg = SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: String::from("terra..."),
+        msg: to_binary(&Cw721ExecuteMsg::MinterBurn {
+            token_id: String::from("1"),
+        })
+        .unwrap(),
+        funds: vec![],
+    }));
+    assert_eq!(res.messages[1], burn_msg);
+    assert!(DELEGATIONS.may_load(&deps.storage, "1").unwrap().is_none());
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "merge"),
+            Attribute::new("sender", "creator"),
+            Attribute::new("token_id", "0")
+        ]
+    );
+
+    // AND the rewards of the merged delegations are claimed before moving the shares
+    let claim_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgClaimDelegationRewards"),
+            value: Binary::from(
+                MsgClaimDelegationRewards {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator1"),
+                    denom: String::from("token"),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        14,
+    );
+    assert_eq!(res.messages[2], claim_sub_msg);
+
+    // WHEN the claim pays 20 uluna of rewards earned before the merge
+    deps.querier
+        .update_balance("cosmos2contract", coins(20, "uluna"));
+    let res = reply_success(&mut deps, &env, 14);
+
+    // THEN both NFTs settle their share and the merged NFT holds all the shares
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: String::from("creator"),
+            amount: coins(20, "uluna"),
+        })]
+    );
+    let checkpoint = REWARD_CHECKPOINTS
+        .load(&deps.storage, ("0", "validator1", "token"))
+        .unwrap();
+    assert_eq!(checkpoint.shares, Uint128::new(200));
+    assert!(!REWARD_CHECKPOINTS.has(&deps.storage, ("1", "validator1", "token")));
+}
+
+#[test]
+fn test_merge_invalid_token_ids() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+
+    // WHEN
+    let single = execute(deps.as_mut(), env.clone(), info.clone(), merge_msg(&["0"])).unwrap_err();
+    let repeated = execute(deps.as_mut(), env, info, merge_msg(&["0", "0"])).unwrap_err();
+
+    // THEN
+    assert_eq!(single, ContractError::MergeImpossible(String::from("0")));
+    assert_eq!(repeated, ContractError::MergeImpossible(String::from("0")));
+}
+
+#[test]
+fn test_merge_unbonding_nft() {
+    // GIVEN NFTs that are unbonding
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra1..."));
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, merge_msg(&["0", "1"])).unwrap_err();
+
+    // THEN
+    assert_eq!(res, ContractError::MergeImpossible(String::from("0")));
+}
+
+#[test]
+fn test_merge_with_no_access() {
+    // GIVEN
+    let (mut deps, env, _) = chain_with_contract_delegation(String::from("terra..."));
+    let info = mock_info("random", &[]);
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, merge_msg(&["0", "1"])).unwrap_err();
+
+    // THEN
+    assert_eq!(
+        res,
+        ContractError::UnauthorizedNFTOwnere(String::from("creator"), String::from("random"))
+    );
+}
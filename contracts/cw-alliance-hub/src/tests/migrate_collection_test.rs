@@ -0,0 +1,136 @@
+use crate::entry_points::{execute::execute, instantiate::instantiate, reply::reply};
+use crate::msg::ExecuteMsg;
+use crate::{
+    tests::utils::{chain_with_contract, default_chain, inst_msg},
+    ContractError,
+};
+use cosmwasm_std::{
+    testing::mock_info, to_binary, Attribute, Empty, Event, Reply, ReplyOn, StdError, SubMsg,
+    SubMsgResponse, SubMsgResult, WasmMsg,
+};
+use cw_ownable::OwnershipError;
+
+#[test]
+fn test_migrate_collection() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract();
+    let msg = ExecuteMsg::MigrateCollection {
+        new_code_id: 2,
+        msg: to_binary(&Empty {}).unwrap(),
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN the hub migrates the NFT contract and replies always
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "migrate_collection"),
+            Attribute::new("sender", "creator"),
+            Attribute::new("nft_contract_address", "terra..."),
+            Attribute::new("new_code_id", "2"),
+        ]
+    );
+    assert_eq!(
+        res.messages,
+        vec![SubMsg {
+            id: 13,
+            msg: WasmMsg::Migrate {
+                contract_addr: String::from("terra..."),
+                new_code_id: 2,
+                msg: to_binary(&Empty {}).unwrap(),
+            }
+            .into(),
+            gas_limit: None,
+            reply_on: ReplyOn::Always,
+        }]
+    );
+}
+
+#[test]
+fn test_migrate_collection_unauthorized() {
+    // GIVEN
+    let (mut deps, env, _) = chain_with_contract();
+    let msg = ExecuteMsg::MigrateCollection {
+        new_code_id: 2,
+        msg: to_binary(&Empty {}).unwrap(),
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, mock_info("stranger", &[]), msg);
+
+    // THEN
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Ownership(OwnershipError::NotOwner)
+    );
+}
+
+#[test]
+fn test_migrate_collection_without_nft_contract() {
+    // GIVEN a hub which has not received the instantiate reply
+    let (mut deps, env, info) = default_chain();
+    instantiate(deps.as_mut(), env.clone(), info.clone(), inst_msg()).unwrap();
+    let msg = ExecuteMsg::MigrateCollection {
+        new_code_id: 2,
+        msg: to_binary(&Empty {}).unwrap(),
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg);
+
+    // THEN
+    assert_eq!(res.unwrap_err(), ContractError::NoNftContractAddress {});
+}
+
+#[test]
+fn test_migrate_collection_reply() {
+    // GIVEN
+    let (mut deps, env, _) = chain_with_contract();
+    let msg = Reply {
+        id: 13,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            data: None,
+            events: vec![
+                Event::new("migrate").add_attribute("code_id", "2"),
+                Event::new("wasm")
+                    .add_attribute("action", "migrate")
+                    .add_attribute("migrated_from_version", "0.1.0"),
+            ],
+        }),
+    };
+
+    // WHEN
+    let res = reply(deps.as_mut(), env, msg).unwrap();
+
+    // THEN
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("method", "migrate_collection_reply"),
+            Attribute::new("migrated_from_version", "0.1.0"),
+        ]
+    );
+}
+
+#[test]
+fn test_migrate_collection_reply_fail() {
+    // GIVEN
+    let (mut deps, env, _) = chain_with_contract();
+    let msg = Reply {
+        id: 13,
+        result: SubMsgResult::Err(String::from("Something went wrong")),
+    };
+
+    // WHEN
+    let res = reply(deps.as_mut(), env, msg).unwrap_err();
+
+    // THEN
+    assert_eq!(
+        res,
+        StdError::generic_err(String::from(
+            "Error migrating nft contract: Something went wrong"
+        ))
+    );
+}
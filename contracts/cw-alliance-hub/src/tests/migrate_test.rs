@@ -0,0 +1,429 @@
+use crate::entry_points::constants::CONTRACT_NAME;
+use crate::entry_points::execute::execute;
+use crate::entry_points::migrate::{
+    migrate, migrate_cfg_v1, migrate_delegations_v1, CfgV1, CFG_V1,
+};
+use crate::msg::{ExecuteMsg, MigrateMsg, RedelegationDestination};
+use crate::state::{
+    DelegationRecord, DelegationsMigration, DisplayType, RedeemedNft, ValidatorStrategy, CFG,
+    DELEGATIONS, DELEGATIONS_MIGRATION, ORPHANED_REWARDS, REWARD_INDEXES, UNBONDINGS,
+};
+use crate::tests::utils::{chain_with_contract, AllianceDeps};
+use crate::ContractError;
+use cosmwasm_std::{coins, testing::mock_info, Addr, Attribute, Coin, Timestamp, Uint128};
+use cw2::{get_contract_version, set_contract_version};
+
+// Store the state written by the versions before 0.2.0
+fn chain_with_v1_contract(nft_contract_addr: &str) -> AllianceDeps {
+    let (mut deps, _, _) = chain_with_contract();
+    set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.1.0").unwrap();
+    CFG_V1
+        .save(
+            deps.as_mut().storage,
+            &CfgV1 {
+                minted_nfts: 1,
+                unbonding_seconds: 100,
+                nft_contract_addr: Some(Addr::unchecked(nft_contract_addr)),
+            },
+        )
+        .unwrap();
+
+    deps
+}
+
+fn migrate_msg(owner: Option<&str>) -> MigrateMsg {
+    MigrateMsg::Migrate {
+        owner: owner.map(String::from),
+        redelegations: None,
+    }
+}
+
+#[test]
+fn test_migrate_from_v1() {
+    // GIVEN
+    let mut deps = chain_with_v1_contract("terra...");
+    let (_, env, _) = chain_with_contract();
+
+    // WHEN
+    let res = migrate(deps.as_mut(), env, migrate_msg(Some("admin"))).unwrap();
+
+    // THEN the state is upgraded to the current version
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "migrate"),
+            Attribute::new("migrated_from", "0.1.0"),
+            Attribute::new("migrated_to", "0.2.0")
+        ]
+    );
+    let version = get_contract_version(deps.as_ref().storage).unwrap();
+    assert_eq!(version.version, "0.2.0");
+    let cfg = CFG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.minted_nfts, 1);
+    assert!(DELEGATIONS.has(deps.as_ref().storage, "0"));
+
+    // AND the collection fits in the first batch of the migration
+    assert!(DELEGATIONS_MIGRATION
+        .may_load(deps.as_ref().storage)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn test_migrate_cfg_v1() {
+    // GIVEN
+    let mut deps = chain_with_v1_contract("terra...");
+    let (_, env, _) = chain_with_contract();
+
+    // WHEN
+    migrate_cfg_v1(deps.as_mut(), &env, &migrate_msg(Some("admin"))).unwrap();
+
+    // THEN the new fields take the instantiate defaults
+    let cfg = CFG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.minted_nfts, 1);
+    assert_eq!(cfg.unbonding_seconds, 100);
+    assert_eq!(cfg.nft_contract_addr, Some(Addr::unchecked("terra...")));
+    assert_eq!(cfg.validator_strategy, ValidatorStrategy::PseudoRandom);
+    assert_eq!(cfg.max_validators_per_denom, 1);
+    assert_eq!(cfg.validators_allowlist, None);
+    assert!(!cfg.refund_unsupported_denoms);
+    assert_eq!(cfg.redeemed_nft, RedeemedNft::Lock);
+    assert_eq!(cfg.protocol_fee, None);
+
+    // AND the owner is set from the migrate message
+    let ownership = cw_ownable::get_ownership(deps.as_ref().storage).unwrap();
+    assert_eq!(ownership.owner, Some(Addr::unchecked("admin")));
+}
+
+#[test]
+fn test_migrate_cfg_v1_without_owner() {
+    // GIVEN
+    let mut deps = chain_with_v1_contract("terra...");
+    let (_, env, _) = chain_with_contract();
+
+    // WHEN
+    let res = migrate_cfg_v1(deps.as_mut(), &env, &migrate_msg(None));
+
+    // THEN
+    assert_eq!(res.unwrap_err(), ContractError::MigrationOwnerRequired {});
+}
+
+#[test]
+fn test_migrate_delegations_v1() {
+    // GIVEN an NFT delegating
+    let mut deps = chain_with_v1_contract("terra...");
+    let (_, env, _) = chain_with_contract();
+    migrate_cfg_v1(deps.as_mut(), &env, &migrate_msg(Some("admin"))).unwrap();
+
+    // WHEN
+    migrate_delegations_v1(deps.as_mut(), &env, &migrate_msg(None)).unwrap();
+
+    // THEN the records are rebuilt from the NFT metadata
+    assert_eq!(
+        DELEGATIONS.load(deps.as_ref().storage, "0").unwrap(),
+        vec![DelegationRecord {
+            validator: String::from("validator1"),
+            coin: Coin::new(100, "token"),
+            status: DisplayType::Delegated,
+            completion_time: Timestamp::from_seconds(100),
+        }]
+    );
+
+    // AND the delegations earn rewards
+    let index = REWARD_INDEXES
+        .load(deps.as_ref().storage, ("validator1", "token"))
+        .unwrap();
+    assert_eq!(index.total_shares, Uint128::new(100));
+}
+
+#[test]
+fn test_migrate_unbonding_delegations_v1() {
+    // GIVEN an NFT unbonding
+    let mut deps = chain_with_v1_contract("terra...unbonding");
+    let (_, env, _) = chain_with_contract();
+    migrate_cfg_v1(deps.as_mut(), &env, &migrate_msg(Some("admin"))).unwrap();
+
+    // WHEN
+    migrate_delegations_v1(deps.as_mut(), &env, &migrate_msg(None)).unwrap();
+
+    // THEN the unbonding is tracked by the ledger
+    assert_eq!(
+        DELEGATIONS.load(deps.as_ref().storage, "0").unwrap()[0].status,
+        DisplayType::Unbonding
+    );
+    let unbonding = UNBONDINGS
+        .load(
+            deps.as_ref().storage,
+            ("token", Timestamp::from_seconds(100).nanos()),
+        )
+        .unwrap();
+    assert_eq!(unbonding.amount, Uint128::new(100));
+}
+
+#[test]
+fn test_migrate_delegations_v1_keeps_legacy_balance_apart() {
+    // GIVEN an NFT which unbonding completed before the contract
+    // migration and a hub holding its principal and 50 tokens of
+    // rewards claimed by the versions before 0.2.0
+    let mut deps = chain_with_v1_contract("terra...unbonding");
+    let (_, env, _) = chain_with_contract();
+    migrate_cfg_v1(deps.as_mut(), &env, &migrate_msg(Some("admin"))).unwrap();
+    deps.querier
+        .update_balance("cosmos2contract", coins(150, "token"));
+
+    // WHEN
+    migrate_delegations_v1(deps.as_mut(), &env, &migrate_msg(None)).unwrap();
+
+    // THEN only the legacy rewards are kept apart from the principal
+    let orphaned = ORPHANED_REWARDS
+        .load(deps.as_ref().storage, "token")
+        .unwrap();
+    assert_eq!(orphaned, Uint128::new(50));
+    let migration = DELEGATIONS_MIGRATION.may_load(deps.as_ref().storage);
+    assert_eq!(migration.unwrap(), None);
+}
+
+#[test]
+fn test_migrate_redelegating_delegations_v1() {
+    // GIVEN an NFT redelegating from validator1, which the versions
+    // before 0.2.0 wrote as the validator of the trait
+    let mut deps = chain_with_v1_contract("terra...redelegating");
+    let (_, env, _) = chain_with_contract();
+    migrate_cfg_v1(deps.as_mut(), &env, &migrate_msg(Some("admin"))).unwrap();
+    deps.querier
+        .set_alliance_delegation("validator1", Coin::new(50, "token"));
+    deps.querier
+        .set_alliance_delegation("validator2", Coin::new(100, "token"));
+    deps.querier
+        .set_alliance_delegation("validator3", Coin::new(100, "stoken"));
+
+    // WHEN
+    migrate_delegations_v1(deps.as_mut(), &env, &migrate_msg(None)).unwrap();
+
+    // THEN the record is moved to the validator the hub redelegated to
+    assert_eq!(
+        DELEGATIONS.load(deps.as_ref().storage, "0").unwrap(),
+        vec![DelegationRecord {
+            validator: String::from("validator2"),
+            coin: Coin::new(100, "token"),
+            status: DisplayType::Redelegating,
+            completion_time: Timestamp::from_seconds(100),
+        }]
+    );
+    let index = REWARD_INDEXES
+        .load(deps.as_ref().storage, ("validator2", "token"))
+        .unwrap();
+    assert_eq!(index.total_shares, Uint128::new(100));
+    assert!(!REWARD_INDEXES.has(deps.as_ref().storage, ("validator1", "token")));
+}
+
+#[test]
+fn test_migrate_redelegating_delegations_v1_ambiguous() {
+    // GIVEN an NFT redelegating from validator1 and a hub
+    // delegating enough tokens to two other validators
+    let mut deps = chain_with_v1_contract("terra...redelegating");
+    let (_, env, _) = chain_with_contract();
+    migrate_cfg_v1(deps.as_mut(), &env, &migrate_msg(Some("admin"))).unwrap();
+    deps.querier
+        .set_alliance_delegation("validator2", Coin::new(100, "token"));
+    deps.querier
+        .set_alliance_delegation("validator3", Coin::new(150, "token"));
+
+    // WHEN
+    migrate_delegations_v1(deps.as_mut(), &env, &migrate_msg(None)).unwrap();
+
+    // THEN the NFT is kept unresolved instead of guessing the destination
+    assert!(!DELEGATIONS.has(deps.as_ref().storage, "0"));
+    let migration = DELEGATIONS_MIGRATION.load(deps.as_ref().storage).unwrap();
+    assert_eq!(migration.start_after, Some(String::from("0")));
+    assert_eq!(migration.unresolved, vec![String::from("0")]);
+
+    // WHEN the owner sets the destination
+    let msg = ExecuteMsg::MigrateDelegations {
+        limit: None,
+        redelegations: Some(vec![RedelegationDestination {
+            token_id: String::from("0"),
+            validator: String::from("validator3"),
+        }]),
+    };
+    let res = execute(deps.as_mut(), env, mock_info("admin", &[]), msg).unwrap();
+
+    // THEN the NFT is migrated to that validator and the migration completes
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "migrate_delegations"),
+            Attribute::new("sender", "admin"),
+            Attribute::new("migrated_nfts", "1"),
+            Attribute::new("unresolved_nfts", ""),
+            Attribute::new("completed", "true"),
+        ]
+    );
+    assert_eq!(
+        DELEGATIONS.load(deps.as_ref().storage, "0").unwrap()[0].validator,
+        "validator3"
+    );
+    let index = REWARD_INDEXES
+        .load(deps.as_ref().storage, ("validator3", "token"))
+        .unwrap();
+    assert_eq!(index.total_shares, Uint128::new(100));
+}
+
+#[test]
+fn test_migrate_delegations_in_batches() {
+    // GIVEN a collection of three NFTs delegating to validator1
+    // where the migration of the delegations is pending
+    let mut deps = chain_with_v1_contract("terra...collection");
+    let (_, env, _) = chain_with_contract();
+    migrate_cfg_v1(deps.as_mut(), &env, &migrate_msg(Some("admin"))).unwrap();
+    DELEGATIONS_MIGRATION
+        .save(
+            deps.as_mut().storage,
+            &DelegationsMigration {
+                start_after: None,
+                unresolved: vec![],
+                started_at: env.block.time,
+            },
+        )
+        .unwrap();
+    let info = mock_info("admin", &[]);
+    let msg = ExecuteMsg::MigrateDelegations {
+        limit: Some(2),
+        redelegations: None,
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+
+    // THEN only the first batch is migrated
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "migrate_delegations"),
+            Attribute::new("sender", "admin"),
+            Attribute::new("migrated_nfts", "2"),
+            Attribute::new("unresolved_nfts", ""),
+            Attribute::new("completed", "false"),
+        ]
+    );
+    assert!(DELEGATIONS.has(deps.as_ref().storage, "1"));
+    assert!(!DELEGATIONS.has(deps.as_ref().storage, "2"));
+
+    // AND the NFT operations wait for the migration to complete
+    let claim = ExecuteMsg::MsgClaimRewards {
+        token_id: String::from("0"),
+    };
+    let res = execute(deps.as_mut(), env.clone(), mock_info("creator", &[]), claim);
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::DelegationsMigrationPending {}
+    );
+
+    // AND only the NFTs already migrated can be refreshed and redeemed
+    let refresh = ExecuteMsg::Refresh {
+        token_ids: vec![String::from("0"), String::from("1")],
+    };
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        refresh,
+    );
+    assert!(res.is_ok());
+    let refresh = ExecuteMsg::Refresh {
+        token_ids: vec![String::from("1"), String::from("2")],
+    };
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        refresh,
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::DelegationsMigrationPending {}
+    );
+    let redeem = ExecuteMsg::MsgRedeemBond {
+        token_id: String::from("2"),
+    };
+    let res = execute(
+        deps.as_mut(),
+        env.clone(),
+        mock_info("creator", &[]),
+        redeem,
+    );
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::DelegationsMigrationPending {}
+    );
+
+    // WHEN migrating the next batch
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+
+    // THEN the migration completes
+    assert_eq!(res.attributes[2], Attribute::new("migrated_nfts", "1"));
+    assert_eq!(res.attributes[4], Attribute::new("completed", "true"));
+    let index = REWARD_INDEXES
+        .load(deps.as_ref().storage, ("validator1", "token"))
+        .unwrap();
+    assert_eq!(index.total_shares, Uint128::new(300));
+
+    // WHEN migrating once the migration has completed
+    let res = execute(deps.as_mut(), env, info, msg);
+
+    // THEN
+    assert_eq!(res.unwrap_err(), ContractError::NoDelegationsMigration {});
+}
+
+#[test]
+fn test_migrate_same_version() {
+    // GIVEN
+    let (mut deps, env, _) = chain_with_contract();
+    let cfg = CFG.load(deps.as_ref().storage).unwrap();
+
+    // WHEN
+    let res = migrate(deps.as_mut(), env, migrate_msg(None)).unwrap();
+
+    // THEN no step runs
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "migrate"),
+            Attribute::new("migrated_from", "0.2.0"),
+            Attribute::new("migrated_to", "0.2.0")
+        ]
+    );
+    assert_eq!(CFG.load(deps.as_ref().storage).unwrap(), cfg);
+}
+
+#[test]
+fn test_migrate_downgrade() {
+    // GIVEN
+    let (mut deps, env, _) = chain_with_contract();
+    set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "1.0.0").unwrap();
+
+    // WHEN
+    let res = migrate(deps.as_mut(), env, migrate_msg(None));
+
+    // THEN
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::MigrationDowngrade(String::from("1.0.0"), String::from("0.2.0"))
+    );
+}
+
+#[test]
+fn test_migrate_foreign_contract() {
+    // GIVEN
+    let (mut deps, env, _) = chain_with_contract();
+    set_contract_version(deps.as_mut().storage, "crates.io:cw20-base", "0.1.0").unwrap();
+
+    // WHEN
+    let res = migrate(deps.as_mut(), env, migrate_msg(None));
+
+    // THEN
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::InvalidContractName(String::from("crates.io:cw20-base"))
+    );
+}
@@ -1,7 +1,21 @@
+pub mod admin_test;
 pub mod claim_rewards_test;
+pub mod completion_time_test;
+pub mod compound_test;
 pub mod delegate_test;
+pub mod fees_test;
 pub mod instantiate_test;
+pub mod merge_test;
+pub mod migrate_collection_test;
+pub mod migrate_test;
+pub mod pause_test;
+pub mod query_test;
 pub mod redeem_bond_test;
 pub mod redelegate_test;
+pub mod refresh_test;
+pub mod simulate_test;
+pub mod split_test;
 pub mod start_unbonding_test;
+pub mod status_test;
 pub mod utils;
+pub mod validators_test;
@@ -0,0 +1,142 @@
+use crate::entry_points::{execute::execute, query::query};
+use crate::msg::{ExecuteMsg, QueryMsg};
+use crate::state::{PauseFlags, PAUSE_FLAGS};
+use crate::{
+    tests::utils::{chain_with_contract, chain_with_contract_delegation},
+    ContractError,
+};
+use cosmwasm_std::{coins, from_binary, testing::mock_info, Attribute, Event};
+use cw_ownable::OwnershipError;
+
+fn pause_all() -> PauseFlags {
+    PauseFlags {
+        deposits: true,
+        redelegations: true,
+        unbondings: true,
+        claims: true,
+    }
+}
+
+#[test]
+fn test_update_pause_flags() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract();
+    let msg = ExecuteMsg::UpdatePauseFlags(PauseFlags {
+        deposits: true,
+        ..Default::default()
+    });
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // THEN
+    let flags: PauseFlags =
+        from_binary(&query(deps.as_ref(), env, QueryMsg::GetPauseFlags {}).unwrap()).unwrap();
+    assert!(flags.deposits);
+    assert!(!flags.redelegations);
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "update_pause_flags"),
+            Attribute::new("sender", "creator")
+        ]
+    );
+    assert_eq!(
+        res.events,
+        vec![Event::new("update_pause_flags")
+            .add_attribute(
+                "old_value",
+                "{\"deposits\":false,\"redelegations\":false,\"unbondings\":false,\"claims\":false}"
+            )
+            .add_attribute(
+                "new_value",
+                "{\"deposits\":true,\"redelegations\":false,\"unbondings\":false,\"claims\":false}"
+            )]
+    );
+}
+
+#[test]
+fn test_update_pause_flags_unauthorized() {
+    // GIVEN
+    let (mut deps, env, _) = chain_with_contract();
+    let msg = ExecuteMsg::UpdatePauseFlags(pause_all());
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, mock_info("stranger", &[]), msg);
+
+    // THEN
+    assert_eq!(
+        res.unwrap_err(),
+        ContractError::Ownership(OwnershipError::NotOwner)
+    );
+}
+
+#[test]
+fn test_paused_operations() {
+    // GIVEN a hub with all the operations paused
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra...unbonding"));
+    PAUSE_FLAGS
+        .save(deps.as_mut().storage, &pause_all())
+        .unwrap();
+    let msgs = vec![
+        (
+            ExecuteMsg::MsgDelegate {
+                token_id: None,
+                validators: None,
+            },
+            "deposits",
+        ),
+        (
+            ExecuteMsg::MsgRedelegate {
+                token_id: String::from("0"),
+                validators: None,
+            },
+            "redelegations",
+        ),
+        (
+            ExecuteMsg::MsgStartUnbonding {
+                token_id: String::from("0"),
+            },
+            "unbondings",
+        ),
+        (
+            ExecuteMsg::MsgClaimRewards {
+                token_id: String::from("0"),
+            },
+            "claims",
+        ),
+    ];
+
+    for (msg, operations) in msgs {
+        // WHEN
+        let res = execute(deps.as_mut(), env.clone(), info.clone(), msg);
+
+        // THEN
+        assert_eq!(
+            res.unwrap_err(),
+            ContractError::Paused(String::from(operations))
+        );
+    }
+}
+
+#[test]
+fn test_redeem_bond_while_paused() {
+    // GIVEN a hub with all the operations paused
+    let (mut deps, mut env, info) =
+        chain_with_contract_delegation(String::from("terra...unbonding"));
+    PAUSE_FLAGS
+        .save(deps.as_mut().storage, &pause_all())
+        .unwrap();
+    deps.querier
+        .update_balance("cosmos2contract", coins(100, "token"));
+    env.block.time = env.block.time.plus_seconds(101);
+    let msg = ExecuteMsg::MsgRedeemBond {
+        token_id: String::from("0"),
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg);
+
+    // THEN users can still exit
+    assert!(res.is_ok());
+}
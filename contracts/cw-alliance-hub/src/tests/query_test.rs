@@ -0,0 +1,171 @@
+use crate::entry_points::query::query;
+use crate::msg::{
+    PendingRewardsResponse, PositionResponse, PositionsResponse, QueryMsg, TotalDelegatedResponse,
+    ValidatorDelegation,
+};
+use crate::rewards;
+use crate::state::{DelegationRecord, DisplayType};
+use crate::tests::utils::{chain_with_contract_delegation, mirror_reward_indexes};
+use cosmwasm_std::{coins, from_binary, Coin, Timestamp};
+
+#[test]
+fn test_position() {
+    // GIVEN an NFT which unbonding has matured
+    let (deps, env, _) = chain_with_contract_delegation(String::from("terra...unbonding"));
+
+    // WHEN
+    let res: PositionResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env,
+            QueryMsg::Position {
+                token_id: String::from("0"),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    // THEN the delegation is displayed as claimable
+    assert_eq!(
+        res,
+        PositionResponse {
+            token_id: String::from("0"),
+            owner: String::from("creator"),
+            delegations: vec![DelegationRecord {
+                validator: String::from("validator1"),
+                coin: Coin::new(100, "token"),
+                status: DisplayType::Claimable,
+                completion_time: Timestamp::from_seconds(100),
+            }],
+        }
+    );
+}
+
+#[test]
+fn test_positions_by_owner() {
+    // GIVEN
+    let (deps, env, _) = chain_with_contract_delegation(String::from("terra..."));
+
+    // WHEN
+    let owned: PositionsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::PositionsByOwner {
+                owner: String::from("creator"),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    let not_owned: PositionsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env,
+            QueryMsg::PositionsByOwner {
+                owner: String::from("stranger"),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    // THEN
+    assert_eq!(owned.positions.len(), 1);
+    assert_eq!(owned.positions[0].token_id, "0");
+    assert_eq!(
+        owned.positions[0].delegations,
+        vec![DelegationRecord {
+            validator: String::from("validator1"),
+            coin: Coin::new(100, "token"),
+            status: DisplayType::Delegated,
+            completion_time: Timestamp::from_seconds(100),
+        }]
+    );
+    assert!(not_owned.positions.is_empty());
+}
+
+#[test]
+fn test_pending_rewards() {
+    // GIVEN two NFTs delegating to the same validator and denom,
+    // some rewards already claimed and some not claimed yet
+    let (mut deps, env, _) = chain_with_contract_delegation(String::from("terra..."));
+    rewards::add_delegation(
+        deps.as_mut().storage,
+        "0",
+        "validator1",
+        &Coin::new(100, "token"),
+    )
+    .unwrap();
+    rewards::add_delegation(
+        deps.as_mut().storage,
+        "1",
+        "validator1",
+        &Coin::new(300, "token"),
+    )
+    .unwrap();
+    rewards::add_rewards(
+        deps.as_mut().storage,
+        None,
+        "validator1",
+        "token",
+        coins(40, "uluna"),
+    )
+    .unwrap();
+    deps.querier
+        .set_alliance_rewards("validator1", "token", coins(80, "uluna"));
+
+    // WHEN
+    let res: PendingRewardsResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env,
+            QueryMsg::PendingRewards {
+                token_id: String::from("0"),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    // THEN the NFT share of both rewards is pending
+    assert_eq!(res.rewards, coins(30, "uluna"));
+}
+
+#[test]
+fn test_total_delegated() {
+    // GIVEN
+    let (mut deps, env, _) = chain_with_contract_delegation(String::from("terra..."));
+    rewards::add_delegation(
+        deps.as_mut().storage,
+        "1",
+        "validator1",
+        &Coin::new(300, "token"),
+    )
+    .unwrap();
+    mirror_reward_indexes(&mut deps);
+
+    // WHEN
+    let res: TotalDelegatedResponse =
+        from_binary(&query(deps.as_ref(), env, QueryMsg::TotalDelegated {}).unwrap()).unwrap();
+
+    // THEN
+    assert_eq!(
+        res.delegations,
+        vec![
+            ValidatorDelegation {
+                validator: String::from("validator"),
+                coin: Coin::new(100, "token"),
+            },
+            ValidatorDelegation {
+                validator: String::from("validator1"),
+                coin: Coin::new(300, "token"),
+            },
+        ]
+    );
+}
@@ -1,11 +1,20 @@
 use crate::msg::ExecuteMsg;
+use crate::state::{
+    DelegationRecord, DisplayType, RedeemedNft, UnbondingEntry, CFG, DELEGATIONS, REWARD_RESERVES,
+    UNBONDINGS,
+};
 use crate::{
-    entry_points::execute::{execute, Cw721ExecuteMsg},
-    tests::utils::chain_with_contract_delegation,
+    entry_points::{
+        execute::{execute, Cw721ExecuteMsg},
+        reply::reply,
+    },
+    tests::utils::{chain_with_contract_delegation, set_delegations},
     ContractError,
 };
 use cosmwasm_std::{coins, testing::mock_info, to_binary, Attribute, SubMsg, WasmMsg};
-use cosmwasm_std::{BankMsg, Coin, Uint128};
+use cosmwasm_std::{
+    BankMsg, Coin, Event, Order, Reply, StdResult, SubMsgResponse, SubMsgResult, Timestamp, Uint128,
+};
 use cw721_progressive_metadata::state::{Metadata as CW721Metadata, Trait as CW721Trait};
 
 #[test]
@@ -13,6 +22,8 @@ fn test_redeem_bond() {
     // GIVEN
     let (mut deps, mut env, info) =
         chain_with_contract_delegation(String::from("terra...unbonding"));
+    deps.querier
+        .update_balance("cosmos2contract", coins(100, "token"));
     let msg = ExecuteMsg::MsgRedeemBond {
         token_id: String::from("0"),
     };
@@ -21,8 +32,8 @@ fn test_redeem_bond() {
     // WHEN
     let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
-    // THEN
-    assert_eq!(res.messages.len(), 2);
+    // THEN the NFT is locked once redeemed
+    assert_eq!(res.messages.len(), 3);
     let update_metadata = SubMsg::reply_always(
         WasmMsg::Execute {
             contract_addr: String::from("terra...unbonding"),
@@ -45,18 +56,28 @@ fn test_redeem_bond() {
         5,
     );
     assert_eq!(res.messages[0], update_metadata);
+    let lock_msg = SubMsg::new(WasmMsg::Execute {
+        contract_addr: String::from("terra...unbonding"),
+        msg: to_binary(&Cw721ExecuteMsg::Lock {
+            token_id: String::from("0"),
+        })
+        .unwrap(),
+        funds: vec![],
+    });
+    assert_eq!(res.messages[1], lock_msg);
 
     let send_msg = SubMsg::new(BankMsg::Send {
         to_address: String::from("creator"),
         amount: vec![Coin::new(Uint128::new(100).into(), String::from("token"))],
     });
 
-    assert_eq!(res.messages[1], send_msg);
+    assert_eq!(res.messages[2], send_msg);
     assert_eq!(
         res.attributes,
         vec![
             Attribute::new("action", "redeem_bond"),
-            Attribute::new("sender", "creator")
+            Attribute::new("sender", "creator"),
+            Attribute::new("redeemed_nft", "lock")
         ]
     );
 }
@@ -97,3 +118,428 @@ fn test_redeem_bond_with_no_access() {
         )
     );
 }
+
+#[test]
+fn test_redeem_bond_pro_rata() {
+    // GIVEN the hub received less than unbonded by the NFTs
+    let (mut deps, mut env, info) =
+        chain_with_contract_delegation(String::from("terra...unbonding"));
+    set_delegations(
+        &mut deps,
+        "1",
+        vec![DelegationRecord {
+            validator: String::from("validator1"),
+            coin: Coin::new(300, "token"),
+            status: DisplayType::Unbonding,
+            completion_time: Timestamp::from_seconds(100),
+        }],
+    );
+    UNBONDINGS
+        .save(
+            deps.as_mut().storage,
+            ("token", 100_000_000_000),
+            &UnbondingEntry {
+                amount: Uint128::new(400),
+                estimated: Uint128::zero(),
+                returned: None,
+            },
+        )
+        .unwrap();
+    deps.querier
+        .update_balance("cosmos2contract", coins(200, "token"));
+    let msg = ExecuteMsg::MsgRedeemBond {
+        token_id: String::from("0"),
+    };
+    env.block.time = env.block.time.plus_seconds(101);
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // THEN the NFT is paid its share of the received funds
+    assert_eq!(
+        res.messages[2],
+        SubMsg::new(BankMsg::Send {
+            to_address: String::from("creator"),
+            amount: coins(50, "token"),
+        })
+    );
+    assert_eq!(
+        DELEGATIONS.load(deps.as_ref().storage, "0").unwrap(),
+        vec![DelegationRecord {
+            validator: String::from("validator1"),
+            coin: Coin::new(50, "token"),
+            status: DisplayType::Unbonded,
+            completion_time: env.block.time,
+        }]
+    );
+    assert_eq!(
+        UNBONDINGS
+            .range(deps.as_ref().storage, None, None, Order::Ascending)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap(),
+        vec![(
+            ("token".to_string(), 100_000_000_000),
+            UnbondingEntry {
+                amount: Uint128::new(300),
+                estimated: Uint128::zero(),
+                returned: Some(Uint128::new(150)),
+            }
+        )]
+    );
+}
+
+#[test]
+fn test_redeem_bond_from_own_unbonding() {
+    // GIVEN the hub holds the principal of an unbonding settled for
+    // another NFT and rewards not sent yet besides the 60 token the
+    // chain returned for the slashed unbonding of the NFT, which
+    // completion time has been confirmed by the chain
+    let (mut deps, mut env, info) =
+        chain_with_contract_delegation(String::from("terra...unbonding"));
+    UNBONDINGS
+        .save(
+            deps.as_mut().storage,
+            ("token", 50_000_000_000),
+            &UnbondingEntry {
+                amount: Uint128::new(100),
+                estimated: Uint128::zero(),
+                returned: Some(Uint128::new(100)),
+            },
+        )
+        .unwrap();
+    UNBONDINGS
+        .save(
+            deps.as_mut().storage,
+            ("token", 100_000_000_000),
+            &UnbondingEntry {
+                amount: Uint128::new(100),
+                estimated: Uint128::zero(),
+                returned: None,
+            },
+        )
+        .unwrap();
+    REWARD_RESERVES
+        .save(deps.as_mut().storage, "token", &Uint128::new(20))
+        .unwrap();
+    deps.querier
+        .update_balance("cosmos2contract", coins(180, "token"));
+    let msg = ExecuteMsg::MsgRedeemBond {
+        token_id: String::from("0"),
+    };
+    env.block.time = env.block.time.plus_seconds(101);
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // THEN the NFT is only paid the principal returned for its unbonding
+    assert_eq!(
+        res.messages[2],
+        SubMsg::new(BankMsg::Send {
+            to_address: String::from("creator"),
+            amount: coins(60, "token"),
+        })
+    );
+    assert_eq!(
+        UNBONDINGS
+            .range(deps.as_ref().storage, None, None, Order::Ascending)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap(),
+        vec![(
+            ("token".to_string(), 50_000_000_000),
+            UnbondingEntry {
+                amount: Uint128::new(100),
+                estimated: Uint128::zero(),
+                returned: Some(Uint128::new(100)),
+            }
+        )]
+    );
+}
+
+#[test]
+fn test_redeem_bond_before_funds_are_returned() {
+    // GIVEN the hub has not received the unbonded funds
+    let (mut deps, mut env, info) =
+        chain_with_contract_delegation(String::from("terra...unbonding"));
+    let msg = ExecuteMsg::MsgRedeemBond {
+        token_id: String::from("0"),
+    };
+    env.block.time = env.block.time.plus_seconds(101);
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+
+    // THEN
+    assert_eq!(
+        res,
+        ContractError::UnbondedFundsNotReceived(String::from("token"))
+    );
+}
+
+#[test]
+fn test_redeem_bond_after_start_unbonding() {
+    // GIVEN an NFT unbonded by the hub
+    let (mut deps, mut env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::MsgStartUnbonding {
+        token_id: String::from("1"),
+    };
+    set_delegations(
+        &mut deps,
+        "1",
+        vec![DelegationRecord {
+            validator: String::from("validator1"),
+            coin: Coin::new(100, "token"),
+            status: DisplayType::Delegated,
+            completion_time: Timestamp::from_seconds(100),
+        }],
+    );
+    execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+    let completion_time = env.block.time.plus_seconds(100);
+    assert_eq!(
+        UNBONDINGS
+            .load(deps.as_ref().storage, ("token", completion_time.nanos()))
+            .unwrap()
+            .amount,
+        Uint128::new(100)
+    );
+
+    // WHEN the chain returned the funds at the end of the completion block
+    deps.querier
+        .update_balance("cosmos2contract", coins(100, "token"));
+    env.block.time = completion_time.plus_seconds(1);
+    let msg = ExecuteMsg::MsgRedeemBond {
+        token_id: String::from("1"),
+    };
+    let res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN the NFT is fully paid and the unbonding is removed
+    assert_eq!(
+        res.messages[2],
+        SubMsg::new(BankMsg::Send {
+            to_address: String::from("creator"),
+            amount: coins(100, "token"),
+        })
+    );
+    assert!(UNBONDINGS.is_empty(deps.as_ref().storage));
+}
+
+#[test]
+fn test_redeem_bond_with_funds_returned_in_two_steps() {
+    // GIVEN an NFT unbonded by the hub which completion
+    // time has not been confirmed by the chain
+    let (mut deps, mut env, info) = chain_with_contract_delegation(String::from("terra..."));
+    set_delegations(
+        &mut deps,
+        "1",
+        vec![DelegationRecord {
+            validator: String::from("validator1"),
+            coin: Coin::new(100, "token"),
+            status: DisplayType::Delegated,
+            completion_time: Timestamp::from_seconds(100),
+        }],
+    );
+    let msg = ExecuteMsg::MsgStartUnbonding {
+        token_id: String::from("1"),
+    };
+    execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+    let completion_time = env.block.time.plus_seconds(100);
+    env.block.time = completion_time.plus_seconds(1);
+
+    // WHEN only a part of the funds has been returned
+    deps.querier
+        .update_balance("cosmos2contract", coins(40, "token"));
+    let msg = ExecuteMsg::MsgRedeemBond {
+        token_id: String::from("1"),
+    };
+    let err = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap_err();
+
+    // THEN the NFT is not paid and the unbonding can be settled again
+    assert_eq!(
+        err,
+        ContractError::UnbondedFundsNotReceived(String::from("token"))
+    );
+
+    // WHEN the rest of the funds is returned in a later block
+    deps.querier
+        .update_balance("cosmos2contract", coins(100, "token"));
+    let res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN the NFT is fully paid
+    assert_eq!(
+        res.messages[2],
+        SubMsg::new(BankMsg::Send {
+            to_address: String::from("creator"),
+            amount: coins(100, "token"),
+        })
+    );
+    assert!(UNBONDINGS.is_empty(deps.as_ref().storage));
+}
+
+#[test]
+fn test_redeem_bond_after_confirmed_slashed_unbonding() {
+    // GIVEN an NFT unbonded by the hub which completion
+    // time has been confirmed by the chain
+    let (mut deps, mut env, info) = chain_with_contract_delegation(String::from("terra..."));
+    set_delegations(
+        &mut deps,
+        "1",
+        vec![DelegationRecord {
+            validator: String::from("validator1"),
+            coin: Coin::new(100, "token"),
+            status: DisplayType::Delegated,
+            completion_time: Timestamp::from_seconds(100),
+        }],
+    );
+    let msg = ExecuteMsg::MsgStartUnbonding {
+        token_id: String::from("1"),
+    };
+    execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+    let completion_time = env.block.time.plus_seconds(100);
+    let msg = Reply {
+        id: 11,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![Event::new("alliance.alliance.UndelegateAllianceEvent")
+                .add_attribute("completion_time", "\"2019-10-23T02:25:19.879305533Z\"")],
+            data: None,
+        }),
+    };
+    reply(deps.as_mut(), env.clone(), msg).unwrap();
+    assert_eq!(
+        UNBONDINGS
+            .load(deps.as_ref().storage, ("token", completion_time.nanos()))
+            .unwrap(),
+        UnbondingEntry {
+            amount: Uint128::new(100),
+            estimated: Uint128::zero(),
+            returned: None,
+        }
+    );
+
+    // WHEN the validator was slashed while unbonding
+    deps.querier
+        .update_balance("cosmos2contract", coins(40, "token"));
+    env.block.time = completion_time.plus_seconds(1);
+    let msg = ExecuteMsg::MsgRedeemBond {
+        token_id: String::from("1"),
+    };
+    let res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN the NFT is paid the funds returned by the chain
+    assert_eq!(
+        res.messages[2],
+        SubMsg::new(BankMsg::Send {
+            to_address: String::from("creator"),
+            amount: coins(40, "token"),
+        })
+    );
+    assert!(UNBONDINGS.is_empty(deps.as_ref().storage));
+}
+
+#[test]
+fn test_redeem_bond_burns_nft() {
+    // GIVEN the hub burns the redeemed NFTs
+    let (mut deps, mut env, info) =
+        chain_with_contract_delegation(String::from("terra...unbonding"));
+    CFG.update(deps.as_mut().storage, |mut cfg| -> StdResult<_> {
+        cfg.redeemed_nft = RedeemedNft::Burn;
+        Ok(cfg)
+    })
+    .unwrap();
+    deps.querier
+        .update_balance("cosmos2contract", coins(100, "token"));
+    let msg = ExecuteMsg::MsgRedeemBond {
+        token_id: String::from("0"),
+    };
+    env.block.time = env.block.time.plus_seconds(101);
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN the NFT is burned instead of updated
+    assert_eq!(
+        res.messages[0],
+        SubMsg::reply_always(
+            WasmMsg::Execute {
+                contract_addr: String::from("terra...unbonding"),
+                msg: to_binary(&Cw721ExecuteMsg::MinterBurn {
+                    token_id: String::from("0"),
+                })
+                .unwrap(),
+                funds: vec![],
+            },
+            5,
+        )
+    );
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "redeem_bond"),
+            Attribute::new("sender", "creator"),
+            Attribute::new("redeemed_nft", "burn")
+        ]
+    );
+}
+
+#[test]
+fn test_redeem_bond_keeps_nft_with_delegations() {
+    // GIVEN an NFT partially unbonded
+    let (mut deps, mut env, info) =
+        chain_with_contract_delegation(String::from("terra...unbonding"));
+    set_delegations(
+        &mut deps,
+        "0",
+        vec![
+            DelegationRecord {
+                validator: String::from("validator1"),
+                coin: Coin::new(100, "token"),
+                status: DisplayType::Unbonding,
+                completion_time: Timestamp::from_seconds(100),
+            },
+            DelegationRecord {
+                validator: String::from("validator"),
+                coin: Coin::new(100, "token"),
+                status: DisplayType::Delegated,
+                completion_time: Timestamp::from_seconds(100),
+            },
+        ],
+    );
+    deps.querier
+        .update_balance("cosmos2contract", coins(100, "token"));
+    let msg = ExecuteMsg::MsgRedeemBond {
+        token_id: String::from("0"),
+    };
+    env.block.time = env.block.time.plus_seconds(101);
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN the NFT is updated and can still be transferred
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "redeem_bond"),
+            Attribute::new("sender", "creator")
+        ]
+    );
+}
+
+#[test]
+fn test_redeem_bond_twice() {
+    // GIVEN a redeemed NFT
+    let (mut deps, mut env, info) =
+        chain_with_contract_delegation(String::from("terra...unbonding"));
+    deps.querier
+        .update_balance("cosmos2contract", coins(100, "token"));
+    let msg = ExecuteMsg::MsgRedeemBond {
+        token_id: String::from("0"),
+    };
+    env.block.time = env.block.time.plus_seconds(101);
+    execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+
+    // THEN
+    assert_eq!(res, ContractError::AlreadyRedeemed(String::from("0")));
+}
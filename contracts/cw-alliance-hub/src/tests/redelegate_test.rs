@@ -1,15 +1,17 @@
-use crate::msg::ExecuteMsg;
+use crate::msg::{ExecuteMsg, ValidatorWeight};
 use crate::{
     entry_points::execute::{execute, Cw721ExecuteMsg},
     tests::utils::chain_with_contract_delegation,
     ContractError,
 };
 use cosmwasm_std::{
-    coins, testing::mock_info, to_binary, Attribute, Binary, CosmosMsg, SubMsg, WasmMsg,
+    coins, testing::mock_info, to_binary, Attribute, Binary, Coin, CosmosMsg, SubMsg, Uint128,
+    WasmMsg,
 };
 use cw721_progressive_metadata::state::{Metadata as CW721Metadata, Trait as CW721Trait};
 use terra_proto_rs::{
-    alliance::alliance::MsgRedelegate, cosmos::base::v1beta1::Coin as CosmosNativeCoin,
+    alliance::alliance::{MsgClaimDelegationRewards, MsgRedelegate},
+    cosmos::base::v1beta1::Coin as CosmosNativeCoin,
     traits::Message,
 };
 
@@ -19,13 +21,14 @@ fn test_redelegate() {
     let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
     let msg = ExecuteMsg::MsgRedelegate {
         token_id: String::from("0"),
+        validators: None,
     };
 
     // WHEN
     let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
 
     // THEN
-    assert_eq!(res.messages.len(), 2);
+    assert_eq!(res.messages.len(), 3);
     let update_metadata = SubMsg::reply_always(
         WasmMsg::Execute {
             contract_addr: String::from("terra..."),
@@ -35,7 +38,7 @@ fn test_redelegate() {
                     name: Some(String::from("Alliance NFT #0")),
                     attributes: Some(vec![CW721Trait {
                         display_type: String::from("Redelegating"),
-                        trait_type: String::from("validator1"),
+                        trait_type: String::from("validator"),
                         timestamp: env.block.time.plus_seconds(100),
                         value: String::from("100@token"),
                     }]),
@@ -49,22 +52,43 @@ fn test_redelegate() {
     );
     assert_eq!(res.messages[0], update_metadata);
 
-    let redelegate_sub_msg = SubMsg::new(CosmosMsg::Stargate {
-        type_url: String::from("/alliance.alliance.MsgRedelegate"),
-        value: Binary::from(
-            MsgRedelegate {
-                delegator_address: String::from("cosmos2contract"),
-                validator_src_address: String::from("validator1"),
-                validator_dst_address: String::from("validator"),
-                amount: Some(CosmosNativeCoin {
+    // AND the hub already delegating to the destination validator
+    // claims its rewards before x/alliance pays them with the source ones
+    let claim_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgClaimDelegationRewards"),
+            value: Binary::from(
+                MsgClaimDelegationRewards {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator"),
                     denom: String::from("token"),
-                    amount: String::from("100"),
-                }),
-            }
-            .encode_to_vec(),
-        ),
-    });
-    assert_eq!(res.messages[1], redelegate_sub_msg);
+                }
+                .encode_to_vec(),
+            ),
+        },
+        14,
+    );
+    assert_eq!(res.messages[1], claim_sub_msg);
+
+    let redelegate_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgRedelegate"),
+            value: Binary::from(
+                MsgRedelegate {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_src_address: String::from("validator1"),
+                    validator_dst_address: String::from("validator"),
+                    amount: Some(CosmosNativeCoin {
+                        denom: String::from("token"),
+                        amount: String::from("100"),
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        11,
+    );
+    assert_eq!(res.messages[2], redelegate_sub_msg);
     assert_eq!(
         res.attributes,
         vec![
@@ -81,6 +105,7 @@ fn test_claim_rewards_with_no_access() {
     let info = mock_info("invalid_creator", &coins(100, "token"));
     let msg = ExecuteMsg::MsgRedelegate {
         token_id: String::from("0"),
+        validators: None,
     };
 
     // WHEN
@@ -95,3 +120,133 @@ fn test_claim_rewards_with_no_access() {
         )
     );
 }
+
+#[test]
+fn test_redelegate_to_user_validators() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::MsgRedelegate {
+        token_id: String::from("0"),
+        validators: Some(vec![
+            ValidatorWeight {
+                address: String::from("validator2"),
+                weight: Uint128::new(1),
+            },
+            ValidatorWeight {
+                address: String::from("validator3"),
+                weight: Uint128::new(1),
+            },
+        ]),
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // THEN the delegation is divided between the chosen validators
+    assert_eq!(res.messages.len(), 3);
+    let update_metadata = SubMsg::reply_always(
+        WasmMsg::Execute {
+            contract_addr: String::from("terra..."),
+            msg: to_binary(&Cw721ExecuteMsg::UpdateExtension {
+                token_id: String::from("0"),
+                extension: Some(CW721Metadata {
+                    name: Some(String::from("Alliance NFT #0")),
+                    attributes: Some(vec![
+                        CW721Trait {
+                            display_type: String::from("Redelegating"),
+                            trait_type: String::from("validator2"),
+                            timestamp: env.block.time.plus_seconds(100),
+                            value: String::from("50@token"),
+                        },
+                        CW721Trait {
+                            display_type: String::from("Redelegating"),
+                            trait_type: String::from("validator3"),
+                            timestamp: env.block.time.plus_seconds(100),
+                            value: String::from("50@token"),
+                        },
+                    ]),
+                    ..Default::default()
+                }),
+            })
+            .unwrap(),
+            funds: vec![],
+        },
+        3,
+    );
+    assert_eq!(res.messages[0], update_metadata);
+
+    let redelegate_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgRedelegate"),
+            value: Binary::from(
+                MsgRedelegate {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_src_address: String::from("validator1"),
+                    validator_dst_address: String::from("validator3"),
+                    amount: Some(CosmosNativeCoin {
+                        denom: String::from("token"),
+                        amount: String::from("50"),
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        11,
+    );
+    assert_eq!(res.messages[2], redelegate_sub_msg);
+}
+
+#[test]
+fn test_redelegate_to_the_same_validator() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::MsgRedelegate {
+        token_id: String::from("0"),
+        validators: Some(vec![ValidatorWeight {
+            address: String::from("validator1"),
+            weight: Uint128::new(1),
+        }]),
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+
+    // THEN
+    assert_eq!(
+        res,
+        ContractError::RedelegatingImpossible(String::from("0"))
+    );
+}
+
+#[test]
+fn test_redelegate_after_redelegation_completes() {
+    // GIVEN a redelegated NFT
+    let (mut deps, mut env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::MsgRedelegate {
+        token_id: String::from("0"),
+        validators: None,
+    };
+    execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap();
+    let completion_time = env.block.time.plus_seconds(100);
+
+    // WHEN redelegating before the redelegation completes
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), msg.clone()).unwrap_err();
+
+    // THEN
+    assert_eq!(
+        res,
+        ContractError::IllegalTransition(
+            String::from("Redelegating"),
+            String::from("Redelegating")
+        )
+    );
+
+    // WHEN redelegating once the redelegation has completed
+    deps.querier
+        .set_alliance_delegation("validator", Coin::new(200, "token"));
+    env.block.time = completion_time.plus_seconds(1);
+    let res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN
+    assert_eq!(res.messages.len(), 2);
+}
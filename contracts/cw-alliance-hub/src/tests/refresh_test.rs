@@ -0,0 +1,104 @@
+use crate::msg::ExecuteMsg;
+use crate::state::{DelegationRecord, DisplayType, DELEGATIONS};
+use crate::{
+    entry_points::execute::{execute, Cw721ExecuteMsg},
+    tests::utils::chain_with_contract_delegation,
+    ContractError,
+};
+use cosmwasm_std::{testing::mock_info, to_binary, Attribute, Coin, SubMsg, Timestamp, WasmMsg};
+use cw721_progressive_metadata::state::{Metadata as CW721Metadata, Trait as CW721Trait};
+
+#[test]
+fn test_refresh_matured_unbonding() {
+    // GIVEN a matured unbonding refreshed by a third party
+    let (mut deps, mut env, _info) =
+        chain_with_contract_delegation(String::from("terra...unbonding"));
+    let info = mock_info("anyone", &[]);
+    let msg = ExecuteMsg::Refresh {
+        token_ids: vec![String::from("0")],
+    };
+    env.block.time = env.block.time.plus_seconds(101);
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN the record and the NFT are Claimable
+    let record = DelegationRecord {
+        validator: String::from("validator1"),
+        coin: Coin::new(100, "token"),
+        status: DisplayType::Claimable,
+        completion_time: Timestamp::from_seconds(100),
+    };
+    assert_eq!(
+        DELEGATIONS.load(deps.as_ref().storage, "0").unwrap(),
+        vec![record]
+    );
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::reply_always(
+            WasmMsg::Execute {
+                contract_addr: String::from("terra...unbonding"),
+                msg: to_binary(&Cw721ExecuteMsg::UpdateExtension {
+                    token_id: String::from("0"),
+                    extension: Some(CW721Metadata {
+                        name: Some(String::from("Alliance NFT #0")),
+                        attributes: Some(vec![CW721Trait {
+                            display_type: String::from("Claimable"),
+                            trait_type: String::from("validator1"),
+                            timestamp: Timestamp::from_seconds(100),
+                            value: String::from("100@token"),
+                        }]),
+                        ..Default::default()
+                    }),
+                })
+                .unwrap(),
+                funds: vec![],
+            },
+            10,
+        )]
+    );
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "refresh"),
+            Attribute::new("token_ids", "0")
+        ]
+    );
+}
+
+#[test]
+fn test_refresh_without_changes() {
+    // GIVEN a delegated NFT
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::Refresh {
+        token_ids: vec![String::from("0")],
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap();
+
+    // THEN the NFT is not updated
+    assert_eq!(res.messages, vec![]);
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "refresh"),
+            Attribute::new("token_ids", "")
+        ]
+    );
+}
+
+#[test]
+fn test_refresh_unknown_token() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::Refresh {
+        token_ids: vec![String::from("1")],
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+
+    // THEN
+    assert_eq!(res, ContractError::NoDelegationsFound(String::from("1")));
+}
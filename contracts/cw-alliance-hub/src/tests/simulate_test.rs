@@ -0,0 +1,134 @@
+use crate::entry_points::{execute::execute, query::query};
+use crate::msg::{ExecuteMsg, QueryMsg, SimulateDelegateResponse, SimulateRedelegateResponse};
+use crate::state::{DelegationRecord, DisplayType, DELEGATIONS};
+use crate::tests::utils::{chain_with_contract, chain_with_contract_delegation};
+use cosmwasm_std::{coins, from_binary, Coin, StdError};
+use cw721_progressive_metadata::state::{Metadata as CW721Metadata, Trait as CW721Trait};
+
+#[test]
+fn test_simulate_delegate() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract();
+
+    // WHEN
+    let res: SimulateDelegateResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::SimulateDelegate {
+                funds: coins(100, "token"),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    // THEN the NFT that would be minted is returned
+    let delegations = vec![DelegationRecord {
+        validator: String::from("validator"),
+        coin: Coin::new(100, "token"),
+        status: DisplayType::Delegated,
+        completion_time: env.block.time,
+    }];
+    assert_eq!(
+        res,
+        SimulateDelegateResponse {
+            token_id: String::from("0"),
+            delegations: delegations.clone(),
+            metadata: CW721Metadata {
+                name: Some(String::from("Alliance NFT #0")),
+                attributes: Some(vec![CW721Trait {
+                    display_type: String::from("Delegated"),
+                    trait_type: String::from("validator"),
+                    timestamp: env.block.time,
+                    value: String::from("100@token"),
+                }]),
+                ..Default::default()
+            },
+            refunds: vec![],
+            deposit_fees: vec![],
+        }
+    );
+    assert!(!DELEGATIONS.has(deps.as_ref().storage, "0"));
+
+    // AND it matches the NFT minted by MsgDelegate
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: None,
+        validators: None,
+    };
+    execute(deps.as_mut(), env, info, msg).unwrap();
+    assert_eq!(
+        DELEGATIONS.load(deps.as_ref().storage, "0").unwrap(),
+        delegations
+    );
+}
+
+#[test]
+fn test_simulate_delegate_unsupported_denom() {
+    // GIVEN
+    let (deps, env, _) = chain_with_contract();
+
+    // WHEN
+    let res = query(
+        deps.as_ref(),
+        env,
+        QueryMsg::SimulateDelegate {
+            funds: coins(100, "uluna"),
+        },
+    );
+
+    // THEN the query fails as the execution would
+    assert_eq!(
+        res.unwrap_err(),
+        StdError::generic_err("Denom 'uluna' is not whitelisted as an alliance asset")
+    );
+}
+
+#[test]
+fn test_simulate_redelegate() {
+    // GIVEN
+    let (deps, env, _) = chain_with_contract_delegation(String::from("terra..."));
+
+    // WHEN
+    let res: SimulateRedelegateResponse = from_binary(
+        &query(
+            deps.as_ref(),
+            env.clone(),
+            QueryMsg::SimulateRedelegate {
+                token_id: String::from("0"),
+            },
+        )
+        .unwrap(),
+    )
+    .unwrap();
+
+    // THEN the NFT is redelegated to another validator
+    let completion_time = env.block.time.plus_seconds(100);
+    assert_eq!(
+        res,
+        SimulateRedelegateResponse {
+            delegations: vec![DelegationRecord {
+                validator: String::from("validator"),
+                coin: Coin::new(100, "token"),
+                status: DisplayType::Redelegating,
+                completion_time,
+            }],
+            metadata: CW721Metadata {
+                name: Some(String::from("Alliance NFT #0")),
+                attributes: Some(vec![CW721Trait {
+                    display_type: String::from("Redelegating"),
+                    trait_type: String::from("validator"),
+                    timestamp: completion_time,
+                    value: String::from("100@token"),
+                }]),
+                ..Default::default()
+            },
+        }
+    );
+
+    // AND nothing is saved
+    assert_eq!(
+        DELEGATIONS.load(deps.as_ref().storage, "0").unwrap()[0].status,
+        DisplayType::Delegated
+    );
+}
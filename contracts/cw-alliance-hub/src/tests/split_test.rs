@@ -0,0 +1,186 @@
+use crate::msg::{DelegationTarget, ExecuteMsg};
+use crate::rewards;
+use crate::state::REWARD_CHECKPOINTS;
+use crate::{
+    entry_points::execute::{execute, Cw721ExecuteMsg},
+    tests::utils::{chain_with_contract_delegation, mirror_reward_indexes, reply_success},
+    ContractError,
+};
+use cosmwasm_std::{
+    coins, to_binary, Attribute, BankMsg, Binary, Coin, CosmosMsg, SubMsg, Timestamp, Uint128,
+    WasmMsg,
+};
+use cw721_progressive_metadata::state::{Metadata as CW721Metadata, Trait as CW721Trait};
+use terra_proto_rs::{alliance::alliance::MsgClaimDelegationRewards, traits::Message};
+
+fn split_msg(amounts: &[u128]) -> ExecuteMsg {
+    ExecuteMsg::MsgSplit {
+        token_id: String::from("0"),
+        parts: amounts
+            .iter()
+            .map(|amount| {
+                vec![DelegationTarget {
+                    validator: Some(String::from("validator1")),
+                    amount: Coin::new(*amount, "token"),
+                }]
+            })
+            .collect(),
+    }
+}
+
+#[test]
+fn test_split() {
+    // GIVEN an NFT delegating 100 token to validator1
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    rewards::add_delegation(
+        deps.as_mut().storage,
+        "0",
+        "validator1",
+        &Coin::new(100, "token"),
+    )
+    .unwrap();
+    mirror_reward_indexes(&mut deps);
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info, split_msg(&[30, 20])).unwrap();
+
+    // THEN the original NFT is shrunk and the parts are minted
+    assert_eq!(res.messages.len(), 4);
+    let update_metadata = SubMsg::reply_always(
+        WasmMsg::Execute {
+            contract_addr: String::from("terra..."),
+            msg: to_binary(&Cw721ExecuteMsg::UpdateExtension {
+                token_id: String::from("0"),
+                extension: Some(CW721Metadata {
+                    name: Some(String::from("Alliance NFT #0")),
+                    attributes: Some(vec![CW721Trait {
+                        display_type: String::from("Delegated"),
+                        trait_type: String::from("validator1"),
+                        timestamp: Timestamp::from_seconds(100),
+                        value: String::from("50@token"),
+                    }]),
+                    ..Default::default()
+                }),
+            })
+            .unwrap(),
+            funds: vec![],
+        },
+        8,
+    );
+    assert_eq!(res.messages[0], update_metadata);
+
+    let mint_part = SubMsg::reply_always(
+        WasmMsg::Execute {
+            contract_addr: String::from("terra..."),
+            msg: to_binary(&Cw721ExecuteMsg::Mint {
+                token_id: String::from("2"),
+                owner: String::from("creator"),
+                token_uri: None,
+                extension: Some(CW721Metadata {
+                    name: Some(String::from("Alliance NFT #2")),
+                    attributes: Some(vec![CW721Trait {
+                        display_type: String::from("Delegated"),
+                        trait_type: String::from("validator1"),
+                        timestamp: env.block.time,
+                        value: String::from("20@token"),
+                    }]),
+                    ..Default::default()
+                }),
+            })
+            .unwrap(),
+            funds: vec![],
+        },
+        2,
+    );
+    assert_eq!(res.messages[2], mint_part);
+    assert_eq!(
+        res.attributes,
+        vec![
+            Attribute::new("action", "split"),
+            Attribute::new("sender", "creator"),
+            Attribute::new("token_id", "0")
+        ]
+    );
+
+    // AND the rewards of the split delegation are claimed before moving the shares
+    let claim_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgClaimDelegationRewards"),
+            value: Binary::from(
+                MsgClaimDelegationRewards {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator1"),
+                    denom: String::from("token"),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        14,
+    );
+    assert_eq!(res.messages[3], claim_sub_msg);
+
+    // WHEN the claim pays 10 uluna of rewards earned before the split
+    deps.querier
+        .update_balance("cosmos2contract", coins(10, "uluna"));
+    let res = reply_success(&mut deps, &env, 14);
+
+    // THEN they are sent to the owner of the original NFT
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: String::from("creator"),
+            amount: coins(10, "uluna"),
+        })]
+    );
+
+    // AND the reward index checkpoints follow the delegations
+    let checkpoint = REWARD_CHECKPOINTS
+        .load(&deps.storage, ("0", "validator1", "token"))
+        .unwrap();
+    assert_eq!(checkpoint.shares, Uint128::new(50));
+    let checkpoint = REWARD_CHECKPOINTS
+        .load(&deps.storage, ("1", "validator1", "token"))
+        .unwrap();
+    assert_eq!(checkpoint.shares, Uint128::new(30));
+    let pending = rewards::pending_rewards(&deps.storage, "1", "validator1", "token", &[]).unwrap();
+    assert_eq!(pending, vec![]);
+}
+
+#[test]
+fn test_split_whole_delegation() {
+    // GIVEN an NFT delegating 100 token to validator1
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, split_msg(&[60, 40])).unwrap_err();
+
+    // THEN
+    assert_eq!(res, ContractError::SplitImpossible(String::from("0")));
+}
+
+#[test]
+fn test_split_above_delegation() {
+    // GIVEN an NFT delegating 100 token to validator1
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, split_msg(&[60, 60])).unwrap_err();
+
+    // THEN
+    assert_eq!(
+        res,
+        ContractError::InsufficientDelegation(String::from("60token"), String::from("0"))
+    );
+}
+
+#[test]
+fn test_split_unbonding_nft() {
+    // GIVEN an NFT that is unbonding
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra1..."));
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, split_msg(&[30])).unwrap_err();
+
+    // THEN
+    assert_eq!(res, ContractError::SplitImpossible(String::from("0")));
+}
@@ -1,11 +1,16 @@
-use crate::msg::ExecuteMsg;
+use crate::msg::{DelegationTarget, ExecuteMsg, ValidatorWeight};
+use crate::rewards;
+use crate::state::{
+    DelegationRecord, DisplayType, DELEGATIONS, REWARD_CHECKPOINTS, REWARD_INDEXES,
+};
 use crate::{
     entry_points::execute::{execute, Cw721ExecuteMsg},
-    tests::utils::chain_with_contract_delegation,
+    tests::utils::{chain_with_contract_delegation, reply_success, set_delegations},
     ContractError,
 };
 use cosmwasm_std::{
-    coins, testing::mock_info, to_binary, Attribute, Binary, CosmosMsg, SubMsg, WasmMsg,
+    coins, testing::mock_info, to_binary, Attribute, BankMsg, Binary, Coin, CosmosMsg, SubMsg,
+    Timestamp, Uint128, WasmMsg,
 };
 use cw721_progressive_metadata::state::{Metadata as CW721Metadata, Trait as CW721Trait};
 use terra_proto_rs::alliance::alliance::MsgUndelegate;
@@ -47,20 +52,23 @@ fn test_start_unbonding() {
     );
     assert_eq!(res.messages[0], update_metadata);
 
-    let redelegate_sub_msg = SubMsg::new(CosmosMsg::Stargate {
-        type_url: String::from("/alliance.alliance.MsgUndelegate"),
-        value: Binary::from(
-            MsgUndelegate {
-                delegator_address: String::from("cosmos2contract"),
-                validator_address: String::from("validator1"),
-                amount: Some(CosmosNativeCoin {
-                    denom: String::from("token"),
-                    amount: String::from("100"),
-                }),
-            }
-            .encode_to_vec(),
-        ),
-    });
+    let redelegate_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgUndelegate"),
+            value: Binary::from(
+                MsgUndelegate {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator1"),
+                    amount: Some(CosmosNativeCoin {
+                        denom: String::from("token"),
+                        amount: String::from("100"),
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        11,
+    );
     assert_eq!(res.messages[1], redelegate_sub_msg);
     assert_eq!(
         res.attributes,
@@ -71,6 +79,57 @@ fn test_start_unbonding() {
     );
 }
 
+#[test]
+fn test_start_unbonding_settles_rewards() {
+    // GIVEN
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    rewards::add_delegation(
+        deps.as_mut().storage,
+        "0",
+        "validator1",
+        &Coin::new(100, "token"),
+    )
+    .unwrap();
+    rewards::add_rewards(
+        deps.as_mut().storage,
+        None,
+        "validator1",
+        "token",
+        coins(50, "uluna"),
+    )
+    .unwrap();
+    deps.querier
+        .set_alliance_delegation("validator1", Coin::new(100, "token"));
+    deps.querier
+        .update_balance("cosmos2contract", coins(50, "uluna"));
+    let msg = ExecuteMsg::MsgStartUnbonding {
+        token_id: String::from("0"),
+    };
+    execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // WHEN x/alliance pays 30 uluna of pending rewards while undelegating
+    deps.querier
+        .update_balance("cosmos2contract", coins(80, "uluna"));
+    let res = reply_success(&mut deps, &env, 11);
+
+    // THEN the NFT receives the rewards credited before and the paid ones
+    assert_eq!(
+        res.messages,
+        vec![SubMsg::new(BankMsg::Send {
+            to_address: String::from("creator"),
+            amount: coins(80, "uluna"),
+        })]
+    );
+    let index = REWARD_INDEXES
+        .load(deps.as_ref().storage, ("validator1", "token"))
+        .unwrap();
+    assert_eq!(index.total_shares, Uint128::zero());
+    assert!(REWARD_CHECKPOINTS
+        .may_load(deps.as_ref().storage, ("0", "validator1", "token"))
+        .unwrap()
+        .is_none());
+}
+
 #[test]
 fn test_start_unbonding_with_no_access() {
     // GIVEN
@@ -92,3 +151,304 @@ fn test_start_unbonding_with_no_access() {
         )
     );
 }
+
+#[test]
+fn test_start_unbonding_after_slashing() {
+    // GIVEN an NFT that recorded 100 tokens on a validator slashed by 20%
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    rewards::add_delegation(
+        deps.as_mut().storage,
+        "0",
+        "validator1",
+        &Coin::new(100, "token"),
+    )
+    .unwrap();
+    deps.querier
+        .set_alliance_delegation("validator1", Coin::new(80, "token"));
+    let msg = ExecuteMsg::MsgStartUnbonding {
+        token_id: String::from("0"),
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // THEN only the slashed amount is undelegated and stored in the metadata
+    let update_metadata = SubMsg::reply_always(
+        WasmMsg::Execute {
+            contract_addr: String::from("terra..."),
+            msg: to_binary(&Cw721ExecuteMsg::UpdateExtension {
+                token_id: String::from("0"),
+                extension: Some(CW721Metadata {
+                    name: Some(String::from("Alliance NFT #0")),
+                    attributes: Some(vec![CW721Trait {
+                        display_type: String::from("Unbonding"),
+                        trait_type: String::from("validator1"),
+                        timestamp: env.block.time.plus_seconds(100),
+                        value: String::from("80@token"),
+                    }]),
+                    ..Default::default()
+                }),
+            })
+            .unwrap(),
+            funds: vec![],
+        },
+        4,
+    );
+    assert_eq!(res.messages[0], update_metadata);
+
+    let undelegate_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgUndelegate"),
+            value: Binary::from(
+                MsgUndelegate {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator1"),
+                    amount: Some(CosmosNativeCoin {
+                        denom: String::from("token"),
+                        amount: String::from("80"),
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        11,
+    );
+    assert_eq!(res.messages[1], undelegate_sub_msg);
+}
+
+fn undelegate_msg(amount: &str) -> SubMsg {
+    SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgUndelegate"),
+            value: Binary::from(
+                MsgUndelegate {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator1"),
+                    amount: Some(CosmosNativeCoin {
+                        denom: String::from("token"),
+                        amount: String::from(amount),
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        11,
+    )
+}
+
+#[test]
+fn test_start_unbonding_deposit_after_slashing() {
+    // GIVEN an NFT that recorded 100 tokens on a validator slashed by 20%
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    rewards::add_delegation(
+        deps.as_mut().storage,
+        "0",
+        "validator1",
+        &Coin::new(100, "token"),
+    )
+    .unwrap();
+    deps.querier
+        .set_alliance_delegation("validator1", Coin::new(80, "token"));
+
+    // AND a second NFT depositing 100 tokens to the same validator
+    let msg = ExecuteMsg::MsgDelegate {
+        token_id: None,
+        validators: Some(vec![ValidatorWeight {
+            address: String::from("validator1"),
+            weight: Uint128::one(),
+        }]),
+    };
+    execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+    reply_success(&mut deps, &env, 14);
+    deps.querier
+        .set_alliance_delegation("validator1", Coin::new(180, "token"));
+
+    // THEN the deposit is worth the shares of 100 slashed tokens
+    assert_eq!(
+        DELEGATIONS.load(&deps.storage, "1").unwrap(),
+        vec![DelegationRecord {
+            validator: String::from("validator1"),
+            coin: Coin::new(125, "token"),
+            status: DisplayType::Delegated,
+            completion_time: env.block.time,
+        }]
+    );
+
+    // WHEN the first NFT unbonds
+    let msg = ExecuteMsg::MsgStartUnbonding {
+        token_id: String::from("0"),
+    };
+    let res = execute(deps.as_mut(), env.clone(), info.clone(), msg).unwrap();
+
+    // THEN it only undelegates the slashed amount
+    assert_eq!(res.messages[1], undelegate_msg("80"));
+    reply_success(&mut deps, &env, 11);
+    deps.querier
+        .set_alliance_delegation("validator1", Coin::new(100, "token"));
+
+    // WHEN the second NFT unbonds
+    let msg = ExecuteMsg::MsgStartUnbonding {
+        token_id: String::from("1"),
+    };
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // THEN it undelegates its whole deposit
+    assert_eq!(res.messages[1], undelegate_msg("100"));
+    reply_success(&mut deps, &env, 11);
+    assert_eq!(
+        DELEGATIONS.load(&deps.storage, "1").unwrap(),
+        vec![DelegationRecord {
+            validator: String::from("validator1"),
+            coin: Coin::new(100, "token"),
+            status: DisplayType::Unbonding,
+            completion_time: env.block.time.plus_seconds(100),
+        }]
+    );
+    let index = REWARD_INDEXES
+        .load(&deps.storage, ("validator1", "token"))
+        .unwrap();
+    assert_eq!(index.total_shares, Uint128::zero());
+}
+
+#[test]
+fn test_start_partial_unbonding() {
+    // GIVEN an NFT delegating 100 token to validator1
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::MsgStartPartialUnbonding {
+        token_id: String::from("0"),
+        targets: vec![DelegationTarget {
+            validator: None,
+            amount: Coin::new(40, "token"),
+        }],
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // THEN the remainder is kept delegated and the
+    // unbonded part gets its own completion timestamp
+    assert_eq!(res.messages.len(), 2);
+    let update_metadata = SubMsg::reply_always(
+        WasmMsg::Execute {
+            contract_addr: String::from("terra..."),
+            msg: to_binary(&Cw721ExecuteMsg::UpdateExtension {
+                token_id: String::from("0"),
+                extension: Some(CW721Metadata {
+                    name: Some(String::from("Alliance NFT #0")),
+                    attributes: Some(vec![
+                        CW721Trait {
+                            display_type: String::from("Delegated"),
+                            trait_type: String::from("validator1"),
+                            timestamp: Timestamp::from_seconds(100),
+                            value: String::from("60@token"),
+                        },
+                        CW721Trait {
+                            display_type: String::from("Unbonding"),
+                            trait_type: String::from("validator1"),
+                            timestamp: env.block.time.plus_seconds(100),
+                            value: String::from("40@token"),
+                        },
+                    ]),
+                    ..Default::default()
+                }),
+            })
+            .unwrap(),
+            funds: vec![],
+        },
+        4,
+    );
+    assert_eq!(res.messages[0], update_metadata);
+
+    let undelegate_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgUndelegate"),
+            value: Binary::from(
+                MsgUndelegate {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator1"),
+                    amount: Some(CosmosNativeCoin {
+                        denom: String::from("token"),
+                        amount: String::from("40"),
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        11,
+    );
+    assert_eq!(res.messages[1], undelegate_sub_msg);
+}
+
+#[test]
+fn test_start_partial_unbonding_above_delegation() {
+    // GIVEN an NFT delegating 100 token to validator1
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    let msg = ExecuteMsg::MsgStartPartialUnbonding {
+        token_id: String::from("0"),
+        targets: vec![DelegationTarget {
+            validator: Some(String::from("validator1")),
+            amount: Coin::new(200, "token"),
+        }],
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env, info, msg).unwrap_err();
+
+    // THEN
+    assert_eq!(
+        res,
+        ContractError::InsufficientDelegation(String::from("200token"), String::from("0"))
+    );
+}
+
+#[test]
+fn test_start_unbonding_ignores_nft_metadata() {
+    // GIVEN the NFT metadata displays 100@token delegated to validator1
+    // while the hub recorded 50@token delegated to validator2
+    let (mut deps, env, info) = chain_with_contract_delegation(String::from("terra..."));
+    set_delegations(
+        &mut deps,
+        "0",
+        vec![DelegationRecord {
+            validator: String::from("validator2"),
+            coin: Coin::new(50, "token"),
+            status: DisplayType::Delegated,
+            completion_time: Timestamp::from_seconds(100),
+        }],
+    );
+    let msg = ExecuteMsg::MsgStartUnbonding {
+        token_id: String::from("0"),
+    };
+
+    // WHEN
+    let res = execute(deps.as_mut(), env.clone(), info, msg).unwrap();
+
+    // THEN the hub undelegates its own records
+    let undelegate_sub_msg = SubMsg::reply_on_success(
+        CosmosMsg::Stargate {
+            type_url: String::from("/alliance.alliance.MsgUndelegate"),
+            value: Binary::from(
+                MsgUndelegate {
+                    delegator_address: String::from("cosmos2contract"),
+                    validator_address: String::from("validator2"),
+                    amount: Some(CosmosNativeCoin {
+                        denom: String::from("token"),
+                        amount: String::from("50"),
+                    }),
+                }
+                .encode_to_vec(),
+            ),
+        },
+        11,
+    );
+    assert_eq!(res.messages[1], undelegate_sub_msg);
+    assert_eq!(
+        DELEGATIONS.load(&deps.storage, "0").unwrap(),
+        vec![DelegationRecord {
+            validator: String::from("validator2"),
+            coin: Coin::new(50, "token"),
+            status: DisplayType::Unbonding,
+            completion_time: env.block.time.plus_seconds(100),
+        }]
+    );
+}
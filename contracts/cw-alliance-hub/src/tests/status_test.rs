@@ -0,0 +1,176 @@
+use crate::state::{DelegationRecord, DisplayType};
+use crate::status::{refresh, transition};
+use crate::ContractError;
+use cosmwasm_std::{Coin, Timestamp};
+
+const STATUSES: [DisplayType; 6] = [
+    DisplayType::Unknown,
+    DisplayType::Unbonded,
+    DisplayType::Unbonding,
+    DisplayType::Claimable,
+    DisplayType::Delegated,
+    DisplayType::Redelegating,
+];
+
+fn record(status: DisplayType, completion_time: u64) -> DelegationRecord {
+    DelegationRecord {
+        validator: String::from("validator"),
+        coin: Coin::new(100, "token"),
+        status,
+        completion_time: Timestamp::from_seconds(completion_time),
+    }
+}
+
+#[test]
+fn test_all_transitions() {
+    // GIVEN the status completes at 100 and the block time is 200
+    let completion_time = Timestamp::from_seconds(100);
+    let block_time = Timestamp::from_seconds(200);
+
+    for from in STATUSES.iter() {
+        for to in STATUSES.iter() {
+            // WHEN
+            let res = transition(from, to, completion_time, block_time);
+
+            // THEN only the transitions of the state machine are allowed
+            match (from, to) {
+                (DisplayType::Delegated, DisplayType::Unbonding)
+                | (DisplayType::Delegated, DisplayType::Redelegating)
+                | (DisplayType::Redelegating, DisplayType::Delegated)
+                | (DisplayType::Unbonding, DisplayType::Claimable)
+                | (DisplayType::Claimable, DisplayType::Unbonded) => {
+                    assert_eq!(res, Ok(to.clone()))
+                }
+                _ => assert_eq!(
+                    res,
+                    Err(ContractError::IllegalTransition(
+                        from.to_string(),
+                        to.to_string()
+                    ))
+                ),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_transitions_before_completion() {
+    // GIVEN the status completes at 200 and the block time is 100
+    let completion_time = Timestamp::from_seconds(200);
+    let block_time = Timestamp::from_seconds(100);
+
+    // WHEN
+    let redelegating = transition(
+        &DisplayType::Redelegating,
+        &DisplayType::Delegated,
+        completion_time,
+        block_time,
+    );
+    let unbonding = transition(
+        &DisplayType::Unbonding,
+        &DisplayType::Claimable,
+        completion_time,
+        block_time,
+    );
+    let delegated = transition(
+        &DisplayType::Delegated,
+        &DisplayType::Unbonding,
+        completion_time,
+        block_time,
+    );
+
+    // THEN the transitions that wait for the chain fail
+    assert_eq!(
+        redelegating,
+        Err(ContractError::StatusNotCompleted(
+            String::from("Redelegating"),
+            200
+        ))
+    );
+    assert_eq!(
+        unbonding,
+        Err(ContractError::StatusNotCompleted(
+            String::from("Unbonding"),
+            200
+        ))
+    );
+    assert_eq!(delegated, Ok(DisplayType::Unbonding));
+}
+
+#[test]
+fn test_record_transition() {
+    // GIVEN
+    let mut delegation = record(DisplayType::Delegated, 100);
+
+    // WHEN
+    delegation
+        .transition(
+            DisplayType::Unbonding,
+            Timestamp::from_seconds(300),
+            Timestamp::from_seconds(200),
+        )
+        .unwrap();
+    let res = delegation.transition(
+        DisplayType::Claimable,
+        Timestamp::from_seconds(250),
+        Timestamp::from_seconds(250),
+    );
+
+    // THEN the record completes at the time of the new status
+    assert_eq!(delegation, record(DisplayType::Unbonding, 300));
+    assert_eq!(
+        res,
+        Err(ContractError::StatusNotCompleted(
+            String::from("Unbonding"),
+            300
+        ))
+    );
+}
+
+#[test]
+fn test_refresh() {
+    // GIVEN
+    let mut records = vec![
+        record(DisplayType::Redelegating, 100),
+        record(DisplayType::Redelegating, 300),
+        record(DisplayType::Unbonding, 100),
+        record(DisplayType::Unbonding, 300),
+    ];
+
+    // WHEN
+    let res = refresh(&mut records, Timestamp::from_seconds(200)).unwrap();
+
+    // THEN only the matured records are promoted
+    assert!(res);
+    assert_eq!(
+        records,
+        vec![
+            record(DisplayType::Delegated, 100),
+            record(DisplayType::Redelegating, 300),
+            record(DisplayType::Claimable, 100),
+            record(DisplayType::Unbonding, 300),
+        ]
+    );
+}
+
+#[test]
+fn test_refresh_without_matured_records() {
+    // GIVEN
+    let mut records = vec![
+        record(DisplayType::Delegated, 100),
+        record(DisplayType::Unbonding, 300),
+    ];
+
+    // WHEN
+    let res = refresh(&mut records, Timestamp::from_seconds(200)).unwrap();
+
+    // THEN nothing changes
+    assert!(!res);
+    assert_eq!(
+        records,
+        vec![
+            record(DisplayType::Delegated, 100),
+            record(DisplayType::Unbonding, 300),
+        ]
+    );
+}
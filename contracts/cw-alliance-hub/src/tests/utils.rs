@@ -1,16 +1,38 @@
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
 use cosmwasm_std::{
-    coins,
+    coins, from_binary, from_slice,
     testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage},
-    to_binary, Binary, ContractResult, Decimal, Empty, Env, Event, MessageInfo, OwnedDeps,
-    QuerierResult, Reply, SubMsgResponse, SystemResult, Timestamp, Validator, WasmQuery,
+    to_binary, Binary, Coin, ContractResult, Decimal, Empty, Env, Event, MessageInfo, Order,
+    OwnedDeps, Querier, QuerierResult, QueryRequest, Reply, Response, StdResult, SubMsgResponse,
+    SystemError, SystemResult, Timestamp, Uint128, Validator, WasmQuery,
+};
+use cw721::{AllNftInfoResponse, NftInfoResponse, OwnerOfResponse, TokensResponse};
+use serde::Serialize;
+use terra_proto_rs::{
+    alliance::alliance::{
+        QueryAllianceDelegationRequest, QueryAllianceDelegationRewardsRequest,
+        QueryAlliancesRequest,
+    },
+    cosmos::{base::query::v1beta1::PageRequest, staking::v1beta1::QueryValidatorsRequest},
+    traits::Message,
 };
-use cw721::{AllNftInfoResponse, NftInfoResponse, OwnerOfResponse};
 
 use crate::{
     entry_points::{execute::execute, instantiate::instantiate, reply::reply},
     msg::{CW721Collection, ExecuteMsg, InstantiateMsg},
+    state::{DelegationRecord, DisplayType, DELEGATIONS, REWARD_INDEXES},
+};
+use cw721_progressive_metadata::{
+    state::{Metadata as CW721Metadata, Trait as CW721Trait},
+    QueryMsg as CW721QueryEmpty,
 };
-use cw721_progressive_metadata::state::{Metadata as CW721Metadata, Trait as CW721Trait};
+
+type CW721QueryMsg = CW721QueryEmpty<CW721Metadata>;
 
 pub fn default_chain() -> (
     OwnedDeps<MockStorage, MockApi, MockQuerier, Empty>,
@@ -24,13 +46,316 @@ pub fn default_chain() -> (
     (deps, env, info)
 }
 
-pub fn chain_with_contract() -> (
-    OwnedDeps<MockStorage, MockApi, MockQuerier, Empty>,
-    Env,
-    MessageInfo,
-) {
-    let mut deps = mock_dependencies();
-    deps.querier = mock_querier();
+pub type AllianceDeps = OwnedDeps<MockStorage, MockApi, AllianceMockQuerier, Empty>;
+
+// Wraps the MockQuerier to answer the x/alliance and x/staking
+// stargate queries with the data set by the tests
+pub struct AllianceMockQuerier {
+    base: MockQuerier,
+    delegations: HashMap<(String, String), Coin>,
+    rewards: HashMap<(String, String), Vec<Coin>>,
+    bonded_validators: Vec<StakingValidator>,
+    alliances: Vec<String>,
+    unbonding_time: Option<String>,
+}
+
+impl AllianceMockQuerier {
+    pub fn set_bonded_validators(&mut self, validators: &[(&str, u128, bool)]) {
+        self.bonded_validators = validators
+            .iter()
+            .map(|(address, tokens, jailed)| StakingValidator {
+                operator_address: address.to_string(),
+                jailed: *jailed,
+                tokens: Uint128::new(*tokens),
+            })
+            .collect();
+    }
+
+    pub fn set_alliances(&mut self, denoms: &[&str]) {
+        self.alliances = denoms.iter().map(|denom| denom.to_string()).collect();
+    }
+
+    pub fn set_unbonding_seconds(&mut self, seconds: u64) {
+        self.unbonding_time = Some(format!("{}s", seconds));
+    }
+
+    pub fn set_unbonding_time(&mut self, unbonding_time: &str) {
+        self.unbonding_time = Some(unbonding_time.to_string());
+    }
+
+    // The chain does not allow the staking params query
+    pub fn remove_staking_params(&mut self) {
+        self.unbonding_time = None;
+    }
+
+    pub fn set_alliance_delegation(&mut self, validator: &str, balance: Coin) {
+        self.delegations
+            .insert((validator.to_string(), balance.denom.clone()), balance);
+    }
+
+    pub fn set_alliance_rewards(&mut self, validator: &str, denom: &str, rewards: Vec<Coin>) {
+        self.rewards
+            .insert((validator.to_string(), denom.to_string()), rewards);
+    }
+
+    fn handle_alliance_rewards(&self, data: &Binary) -> QuerierResult {
+        let req = match QueryAllianceDelegationRewardsRequest::decode(data.as_slice()) {
+            Ok(req) => req,
+            Err(err) => {
+                return SystemResult::Err(SystemError::InvalidRequest {
+                    error: err.to_string(),
+                    request: data.clone(),
+                })
+            }
+        };
+        let res = AllianceRewardsResponse {
+            rewards: self
+                .rewards
+                .get(&(req.validator_addr, req.denom))
+                .cloned()
+                .unwrap_or_default(),
+        };
+
+        SystemResult::Ok(ContractResult::Ok(to_binary(&res).unwrap()))
+    }
+
+    fn handle_alliance_delegation(&self, data: &Binary) -> QuerierResult {
+        let req = match QueryAllianceDelegationRequest::decode(data.as_slice()) {
+            Ok(req) => req,
+            Err(err) => {
+                return SystemResult::Err(SystemError::InvalidRequest {
+                    error: err.to_string(),
+                    request: data.clone(),
+                })
+            }
+        };
+        let balance = match self
+            .delegations
+            .get(&(req.validator_addr.clone(), req.denom.clone()))
+        {
+            Some(balance) => balance.clone(),
+            None => {
+                return SystemResult::Ok(ContractResult::Err(String::from(
+                    "alliance delegation not found",
+                )))
+            }
+        };
+        let res = AllianceDelegationResponse {
+            delegation: AllianceDelegation { balance },
+        };
+
+        SystemResult::Ok(ContractResult::Ok(to_binary(&res).unwrap()))
+    }
+}
+
+#[derive(Serialize)]
+struct PageResponse {
+    next_key: Option<Binary>,
+}
+
+// Page of the items starting at the offset written in the key of the
+// request, which the mock splits in pages of the given size so the
+// contract must follow the next keys to read all the items
+fn page<T: Clone>(
+    items: &[T],
+    pagination: Option<PageRequest>,
+    size: usize,
+) -> (Vec<T>, PageResponse) {
+    let offset = pagination
+        .map(|page| String::from_utf8(page.key).unwrap())
+        .filter(|key| !key.is_empty())
+        .map(|key| key.parse::<usize>().unwrap())
+        .unwrap_or_default();
+    let end = (offset + size).min(items.len());
+    let next_key = match end < items.len() {
+        true => Some(Binary::from(end.to_string().as_bytes())),
+        false => None,
+    };
+
+    (items[offset..end].to_vec(), PageResponse { next_key })
+}
+
+#[derive(Serialize)]
+struct StakingValidatorsResponse {
+    validators: Vec<StakingValidator>,
+    pagination: PageResponse,
+}
+
+#[derive(Clone, Serialize)]
+struct StakingValidator {
+    operator_address: String,
+    jailed: bool,
+    tokens: Uint128,
+}
+
+#[derive(Serialize)]
+struct StakingParamsResponse {
+    params: StakingParams,
+}
+
+#[derive(Serialize)]
+struct StakingParams {
+    unbonding_time: String,
+}
+
+#[derive(Serialize)]
+struct AlliancesResponse {
+    alliances: Vec<AllianceAsset>,
+    pagination: PageResponse,
+}
+
+#[derive(Serialize)]
+struct AllianceAsset {
+    denom: String,
+}
+
+#[derive(Serialize)]
+struct AllianceDelegationResponse {
+    delegation: AllianceDelegation,
+}
+
+#[derive(Serialize)]
+struct AllianceDelegation {
+    balance: Coin,
+}
+
+#[derive(Serialize)]
+struct AlliancesDelegationsResponse {
+    delegations: Vec<AlliancesDelegation>,
+}
+
+#[derive(Serialize)]
+struct AlliancesDelegation {
+    delegation: AlliancesDelegationInfo,
+    balance: Coin,
+}
+
+#[derive(Serialize)]
+struct AlliancesDelegationInfo {
+    validator_address: String,
+}
+
+#[derive(Serialize)]
+struct AllianceRewardsResponse {
+    rewards: Vec<Coin>,
+}
+
+impl Querier for AllianceMockQuerier {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        match from_slice::<QueryRequest<Empty>>(bin_request) {
+            Ok(QueryRequest::Stargate { path, data })
+                if path == "/alliance.alliance.Query/AllianceDelegation" =>
+            {
+                self.handle_alliance_delegation(&data)
+            }
+            Ok(QueryRequest::Stargate { path, .. })
+                if path == "/alliance.alliance.Query/AlliancesDelegation" =>
+            {
+                let mut delegations: Vec<AlliancesDelegation> = self
+                    .delegations
+                    .iter()
+                    .map(|((validator, _), balance)| AlliancesDelegation {
+                        delegation: AlliancesDelegationInfo {
+                            validator_address: validator.clone(),
+                        },
+                        balance: balance.clone(),
+                    })
+                    .collect();
+                delegations.sort_by(|a, b| {
+                    a.delegation
+                        .validator_address
+                        .cmp(&b.delegation.validator_address)
+                });
+                let res = AlliancesDelegationsResponse { delegations };
+
+                SystemResult::Ok(ContractResult::Ok(to_binary(&res).unwrap()))
+            }
+            Ok(QueryRequest::Stargate { path, data })
+                if path == "/alliance.alliance.Query/AllianceDelegationRewards" =>
+            {
+                self.handle_alliance_rewards(&data)
+            }
+            Ok(QueryRequest::Stargate { path, data })
+                if path == "/alliance.alliance.Query/Alliances" =>
+            {
+                let req = QueryAlliancesRequest::decode(data.as_slice()).unwrap();
+                let (alliances, pagination) = page(&self.alliances, req.pagination, 1);
+                let res = AlliancesResponse {
+                    alliances: alliances
+                        .into_iter()
+                        .map(|denom| AllianceAsset { denom })
+                        .collect(),
+                    pagination,
+                };
+
+                SystemResult::Ok(ContractResult::Ok(to_binary(&res).unwrap()))
+            }
+            Ok(QueryRequest::Stargate { path, .. })
+                if path == "/cosmos.staking.v1beta1.Query/Params" =>
+            {
+                let unbonding_time = match &self.unbonding_time {
+                    Some(unbonding_time) => unbonding_time.clone(),
+                    None => {
+                        return SystemResult::Err(SystemError::UnsupportedRequest {
+                            kind: format!("'{}' path is not allowed from the contract", path),
+                        })
+                    }
+                };
+                let res = StakingParamsResponse {
+                    params: StakingParams { unbonding_time },
+                };
+
+                SystemResult::Ok(ContractResult::Ok(to_binary(&res).unwrap()))
+            }
+            Ok(QueryRequest::Stargate { path, data })
+                if path == "/cosmos.staking.v1beta1.Query/Validators" =>
+            {
+                let req = QueryValidatorsRequest::decode(data.as_slice()).unwrap();
+                let (validators, pagination) = page(&self.bonded_validators, req.pagination, 2);
+                let res = StakingValidatorsResponse {
+                    validators,
+                    pagination,
+                };
+
+                SystemResult::Ok(ContractResult::Ok(to_binary(&res).unwrap()))
+            }
+            _ => self.base.raw_query(bin_request),
+        }
+    }
+}
+
+impl Deref for AllianceMockQuerier {
+    type Target = MockQuerier;
+
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl DerefMut for AllianceMockQuerier {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+fn alliance_dependencies() -> AllianceDeps {
+    OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier: AllianceMockQuerier {
+            base: mock_querier(),
+            delegations: HashMap::new(),
+            rewards: HashMap::new(),
+            bonded_validators: vec![],
+            alliances: vec![String::from("token"), String::from("stoken")],
+            unbonding_time: Some(String::from("100s")),
+        },
+        custom_query_type: PhantomData,
+    }
+}
+
+pub fn chain_with_contract() -> (AllianceDeps, Env, MessageInfo) {
+    let mut deps = alliance_dependencies();
     let env = mock_env();
     let info = mock_info("creator", &coins(100, "token"));
     instantiate(deps.as_mut(), env.clone(), info.clone(), inst_msg()).unwrap();
@@ -46,16 +371,9 @@ pub fn chain_with_contract() -> (
     (deps, env, info)
 }
 
-pub fn chain_with_contract_delegation(
-    contract_adress: String,
-) -> (
-    OwnedDeps<MockStorage, MockApi, MockQuerier, Empty>,
-    Env,
-    MessageInfo,
-) {
+pub fn chain_with_contract_delegation(contract_adress: String) -> (AllianceDeps, Env, MessageInfo) {
     // GIVEN the chain with data,
-    let mut deps = mock_dependencies();
-    deps.querier = mock_querier();
+    let mut deps = alliance_dependencies();
     let env = mock_env();
     let info = mock_info("creator", &coins(100, "token"));
 
@@ -65,9 +383,8 @@ pub fn chain_with_contract_delegation(
         id: 1,
         result: cosmwasm_std::SubMsgResult::Ok(SubMsgResponse {
             data: None,
-            events: vec![
-                Event::new("instantiate").add_attribute("_contract_address", contract_adress)
-            ],
+            events: vec![Event::new("instantiate")
+                .add_attribute("_contract_address", contract_adress.clone())],
         }),
     };
     reply(deps.as_mut(), env.clone(), msg).unwrap();
@@ -76,7 +393,10 @@ pub fn chain_with_contract_delegation(
         deps.as_mut(),
         env.clone(),
         info.clone(),
-        ExecuteMsg::MsgDelegate {},
+        ExecuteMsg::MsgDelegate {
+            token_id: None,
+            validators: None,
+        },
     )
     .unwrap();
     let reply_msg = Reply {
@@ -87,11 +407,63 @@ pub fn chain_with_contract_delegation(
         }),
     };
     reply(deps.as_mut(), env.clone(), reply_msg).unwrap();
+    reply_success(&mut deps, &env, 14);
+
+    // AND the delegations of the NFT match the metadata returned by the mock
+    let status = if contract_adress == "terra..." {
+        DisplayType::Delegated
+    } else {
+        DisplayType::Unbonding
+    };
+    set_delegations(
+        &mut deps,
+        "0",
+        vec![DelegationRecord {
+            validator: String::from("validator1"),
+            coin: Coin::new(100, "token"),
+            status,
+            completion_time: Timestamp::from_seconds(100),
+        }],
+    );
+
+    // AND the alliance delegations of the hub are worth its shares
+    mirror_reward_indexes(&mut deps);
 
     // Then return the chain
     (deps, env, info)
 }
 
+// Answers the alliance delegation queries with a balance equal to
+// the shares of each reward index, as if nothing had been slashed
+pub fn mirror_reward_indexes(deps: &mut AllianceDeps) {
+    let indexes = REWARD_INDEXES
+        .range(&deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()
+        .unwrap();
+    for ((validator, denom), index) in indexes {
+        deps.querier
+            .set_alliance_delegation(&validator, Coin::new(index.total_shares.u128(), denom));
+    }
+}
+
+// Reply of a submessage executed by the chain without events
+pub fn reply_success(deps: &mut AllianceDeps, env: &Env, id: u64) -> Response {
+    let msg = Reply {
+        id,
+        result: cosmwasm_std::SubMsgResult::Ok(SubMsgResponse {
+            events: vec![],
+            data: None,
+        }),
+    };
+    reply(deps.as_mut(), env.clone(), msg).unwrap()
+}
+
+pub fn set_delegations(deps: &mut AllianceDeps, token_id: &str, records: Vec<DelegationRecord>) {
+    DELEGATIONS
+        .save(deps.as_mut().storage, token_id, &records)
+        .unwrap();
+}
+
 fn mock_querier() -> MockQuerier {
     let mut querier = MockQuerier::new(&[]);
 
@@ -133,11 +505,40 @@ fn mock_querier() -> MockQuerier {
 
 fn handle_wasm_query(wq: &WasmQuery) -> SystemResult<ContractResult<Binary>> {
     match wq {
-        WasmQuery::Smart { contract_addr, .. } => {
-            let display_type = if contract_addr == "terra..." {
-                String::from("Delegated")
-            } else {
-                String::from("Unbonding")
+        WasmQuery::Smart { contract_addr, msg } => {
+            // The owner of the NFT minted by the tests holds only that NFT
+            if let Ok(CW721QueryMsg::Tokens { owner, .. }) = from_binary(msg) {
+                let tokens = match owner.as_str() {
+                    "creator" => vec![String::from("0")],
+                    _ => vec![],
+                };
+                return QuerierResult::Ok(ContractResult::Ok(
+                    to_binary(&TokensResponse { tokens }).unwrap(),
+                ));
+            }
+
+            // The collection of the migration tests holds three NFTs,
+            // the rest of the collections only the NFT minted by the tests
+            if let Ok(CW721QueryMsg::AllTokens { start_after, limit }) = from_binary(msg) {
+                let tokens = match contract_addr.as_str() {
+                    "terra...collection" => vec!["0", "1", "2"],
+                    _ => vec!["0"],
+                };
+                let tokens = tokens
+                    .into_iter()
+                    .map(String::from)
+                    .filter(|token_id| Some(token_id) > start_after.as_ref())
+                    .take(limit.unwrap_or(10) as usize)
+                    .collect();
+                return QuerierResult::Ok(ContractResult::Ok(
+                    to_binary(&TokensResponse { tokens }).unwrap(),
+                ));
+            }
+
+            let display_type = match contract_addr.as_str() {
+                "terra..." | "terra...collection" => String::from("Delegated"),
+                "terra...redelegating" => String::from("Redelegating"),
+                _ => String::from("Unbonding"),
             };
 
             QuerierResult::Ok(ContractResult::Ok(
@@ -169,11 +570,19 @@ fn handle_wasm_query(wq: &WasmQuery) -> SystemResult<ContractResult<Binary>> {
 
 pub fn inst_msg() -> InstantiateMsg {
     InstantiateMsg {
+        owner: None,
         cw721_code_id: 12345,
         cw721_unbonding_seconds: 100,
         cw721_collection: CW721Collection {
             name: String::from("Test Collection"),
             symbol: String::from("TST"),
         },
+        validator_strategy: None,
+        split_mode: None,
+        max_validators_per_denom: None,
+        validators_allowlist: None,
+        refund_unsupported_denoms: None,
+        redeemed_nft: None,
+        protocol_fee: None,
     }
 }
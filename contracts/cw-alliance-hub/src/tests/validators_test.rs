@@ -0,0 +1,224 @@
+use crate::state::{SplitMode, ValidatorStrategy, CFG, VALIDATORS_CURSOR};
+use crate::tests::utils::chain_with_contract;
+use crate::validators::{Candidate, ValidatorSelector};
+use crate::ContractError;
+use cosmwasm_std::{Coin, Decimal, Uint128};
+
+fn candidates() -> Vec<Candidate> {
+    vec![
+        Candidate {
+            address: String::from("validator"),
+            commission: Decimal::percent(10),
+            voting_power: Uint128::new(100),
+        },
+        Candidate {
+            address: String::from("validator1"),
+            commission: Decimal::percent(5),
+            voting_power: Uint128::new(0),
+        },
+        Candidate {
+            address: String::from("validator2"),
+            commission: Decimal::percent(20),
+            voting_power: Uint128::new(300),
+        },
+    ]
+}
+
+#[test]
+fn test_pseudo_random_strategy() {
+    // GIVEN
+    let mut selector =
+        ValidatorSelector::new(ValidatorStrategy::PseudoRandom, candidates(), 12345, 0).unwrap();
+
+    // WHEN
+    let selected = vec![selector.next(None).unwrap(), selector.next(None).unwrap()];
+
+    // THEN the selection is based on the block height
+    assert_eq!(selected, vec!["validator1", "validator"]);
+}
+
+#[test]
+fn test_lowest_commission_strategy() {
+    // GIVEN
+    let mut selector =
+        ValidatorSelector::new(ValidatorStrategy::LowestCommission, candidates(), 12345, 0)
+            .unwrap();
+
+    // WHEN
+    let selected = selector.next(None).unwrap();
+    let redelegation_dst = selector.next(Some("validator1")).unwrap();
+
+    // THEN
+    assert_eq!(selected, "validator1");
+    assert_eq!(redelegation_dst, "validator");
+}
+
+#[test]
+fn test_round_robin_strategy() {
+    // GIVEN the cursor stored by a previous execution
+    let (mut deps, env, _) = chain_with_contract();
+    CFG.update(
+        deps.as_mut().storage,
+        |mut cfg| -> Result<_, ContractError> {
+            cfg.validator_strategy = ValidatorStrategy::RoundRobin;
+            Ok(cfg)
+        },
+    )
+    .unwrap();
+    VALIDATORS_CURSOR.save(deps.as_mut().storage, &3).unwrap();
+
+    // WHEN
+    let mut selector = ValidatorSelector::load(deps.as_ref(), &env).unwrap();
+    let selected = vec![selector.next(None).unwrap(), selector.next(None).unwrap()];
+    selector.save(deps.as_mut().storage).unwrap();
+
+    // THEN the validators are iterated and the cursor is stored
+    assert_eq!(selected, vec!["validator3", "validator"]);
+    assert_eq!(VALIDATORS_CURSOR.load(&deps.storage).unwrap(), 5);
+}
+
+#[test]
+fn test_voting_power_strategy() {
+    // GIVEN a jailed validator and a validator without voting power,
+    // where the mock returns the validator with voting power in the
+    // second page of the bonded validators
+    let (mut deps, env, _) = chain_with_contract();
+    CFG.update(
+        deps.as_mut().storage,
+        |mut cfg| -> Result<_, ContractError> {
+            cfg.validator_strategy = ValidatorStrategy::VotingPower;
+            Ok(cfg)
+        },
+    )
+    .unwrap();
+    deps.querier.set_bonded_validators(&[
+        ("validator", 0, false),
+        ("validator1", 1000, true),
+        ("validator2", 300, false),
+    ]);
+
+    // WHEN
+    let mut selector = ValidatorSelector::load(deps.as_ref(), &env).unwrap();
+    let selected = (0..10)
+        .map(|_| selector.next(None).unwrap())
+        .collect::<Vec<_>>();
+
+    // THEN only the validator with voting power is selected
+    assert!(selected.iter().all(|val| val == "validator2"));
+}
+
+#[test]
+fn test_curated_strategy() {
+    // GIVEN a curated list with an inactive validator
+    let (mut deps, env, _) = chain_with_contract();
+    CFG.update(
+        deps.as_mut().storage,
+        |mut cfg| -> Result<_, ContractError> {
+            cfg.validator_strategy = ValidatorStrategy::Curated {
+                validators: vec![String::from("inactive"), String::from("validator2")],
+            };
+            Ok(cfg)
+        },
+    )
+    .unwrap();
+
+    // WHEN
+    let mut selector = ValidatorSelector::load(deps.as_ref(), &env).unwrap();
+
+    // THEN only the active curated validators are selected
+    assert_eq!(selector.next(None).unwrap(), "validator2");
+    assert_eq!(selector.next(None).unwrap(), "validator2");
+}
+
+#[test]
+fn test_strategy_without_validators() {
+    // GIVEN
+    let res = ValidatorSelector::new(ValidatorStrategy::RoundRobin, vec![], 12345, 0);
+
+    // THEN
+    assert_eq!(res.err(), Some(ContractError::NoValidatorsFound {}));
+}
+
+#[test]
+fn test_even_split() {
+    // GIVEN
+    let mut selector =
+        ValidatorSelector::new(ValidatorStrategy::RoundRobin, candidates(), 12345, 0)
+            .unwrap()
+            .with_split(SplitMode::Even, 3);
+
+    // WHEN
+    let split = selector.split(&Coin::new(100, "token")).unwrap();
+
+    // THEN the remainder goes to the first validator
+    assert_eq!(
+        split,
+        vec![
+            (String::from("validator"), Coin::new(34, "token")),
+            (String::from("validator1"), Coin::new(33, "token")),
+            (String::from("validator2"), Coin::new(33, "token")),
+        ]
+    );
+}
+
+#[test]
+fn test_weighted_split() {
+    // GIVEN
+    let mut selector =
+        ValidatorSelector::new(ValidatorStrategy::RoundRobin, candidates(), 12345, 0)
+            .unwrap()
+            .with_split(SplitMode::Weighted, 5);
+
+    // WHEN
+    let split = selector.split(&Coin::new(101, "token")).unwrap();
+
+    // THEN validators without voting power are skipped
+    assert_eq!(
+        split,
+        vec![
+            (String::from("validator"), Coin::new(26, "token")),
+            (String::from("validator2"), Coin::new(75, "token")),
+        ]
+    );
+}
+
+#[test]
+fn test_weighted_split_overflow() {
+    // GIVEN validators which voting power overflows when added
+    let candidates = candidates()
+        .into_iter()
+        .map(|candidate| Candidate {
+            voting_power: Uint128::MAX,
+            ..candidate
+        })
+        .collect();
+    let mut selector = ValidatorSelector::new(ValidatorStrategy::RoundRobin, candidates, 12345, 0)
+        .unwrap()
+        .with_split(SplitMode::Weighted, 3);
+
+    // WHEN
+    let res = selector.split(&Coin::new(100, "token"));
+
+    // THEN
+    assert_eq!(res.unwrap_err(), ContractError::ValidatorWeightsOverflow {});
+}
+
+#[test]
+fn test_voting_power_overflow() {
+    // GIVEN validators which voting power overflows when added
+    let candidates = candidates()
+        .into_iter()
+        .map(|candidate| Candidate {
+            voting_power: Uint128::MAX,
+            ..candidate
+        })
+        .collect();
+    let mut selector =
+        ValidatorSelector::new(ValidatorStrategy::VotingPower, candidates, 12345, 0).unwrap();
+
+    // WHEN
+    let res = selector.next(None);
+
+    // THEN
+    assert_eq!(res.unwrap_err(), ContractError::ValidatorWeightsOverflow {});
+}
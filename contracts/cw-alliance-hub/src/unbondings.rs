@@ -0,0 +1,163 @@
+use cosmwasm_std::{Coin, DepsMut, Env, Order, StdError, StdResult, Storage, Timestamp, Uint128};
+use cw_storage_plus::Bound;
+
+use crate::{
+    state::{UnbondingEntry, ORPHANED_REWARDS, REWARD_RESERVES, UNBONDINGS},
+    ContractError,
+};
+
+// Add the coin undelegated by the hub to the unbondings that
+// complete at the estimated completion time, until the chain
+// confirms it the principal returned can still be re-settled
+pub fn add_unbonding(
+    storage: &mut dyn Storage,
+    coin: &Coin,
+    completion_time: Timestamp,
+) -> StdResult<()> {
+    UNBONDINGS.update(
+        storage,
+        (&coin.denom, completion_time.nanos()),
+        |entry| -> StdResult<_> {
+            let mut entry = entry.unwrap_or_default();
+            entry.amount = entry.amount.checked_add(coin.amount)?;
+            entry.estimated = entry.estimated.checked_add(coin.amount)?;
+            Ok(entry)
+        },
+    )?;
+
+    Ok(())
+}
+
+// Remove the coin from the unbondings that complete at the
+// completion time, which have not matured yet. The records
+// unbonded before the unbondings were tracked are ignored.
+fn remove_unbonding(
+    storage: &mut dyn Storage,
+    coin: &Coin,
+    completion_time: Timestamp,
+) -> StdResult<()> {
+    let key = (coin.denom.as_str(), completion_time.nanos());
+    let mut entry = UNBONDINGS.may_load(storage, key)?.unwrap_or_default();
+    entry.amount = entry.amount.saturating_sub(coin.amount);
+    entry.estimated = entry.estimated.saturating_sub(coin.amount);
+
+    if entry.amount.is_zero() {
+        UNBONDINGS.remove(storage, key);
+        return Ok(());
+    }
+    UNBONDINGS.save(storage, key, &entry)
+}
+
+// Move the coin from the estimated completion time to the one
+// reported by the chain, which may be the same, confirming it
+pub fn confirm_unbonding(
+    storage: &mut dyn Storage,
+    coin: &Coin,
+    estimated: Timestamp,
+    completion_time: Timestamp,
+) -> StdResult<()> {
+    remove_unbonding(storage, coin, estimated)?;
+    UNBONDINGS.update(
+        storage,
+        (&coin.denom, completion_time.nanos()),
+        |entry| -> StdResult<_> {
+            let mut entry = entry.unwrap_or_default();
+            entry.amount = entry.amount.checked_add(coin.amount)?;
+            Ok(entry)
+        },
+    )?;
+
+    Ok(())
+}
+
+// Record the principal returned by the chain for the matured unbondings
+// of the denom that were not settled yet. It is taken from the part of
+// the hub balance that is not owed, which excludes the principal of the
+// unbondings settled before and the rewards not sent yet, and split pro
+// rata when the hub received less than undelegated (e.g. the validator
+// was slashed while unbonding). The chain returns the unbondings at the
+// end of the block so only the ones completed before the current block
+// are settled. The unbondings which completion time is an estimate are
+// settled again on each call until the hub received their whole amount,
+// as the chain may return them in a later block than estimated.
+pub fn settle_unbondings(deps: DepsMut, env: &Env, denom: &str) -> Result<(), ContractError> {
+    let matured = UNBONDINGS
+        .prefix(denom)
+        .range(
+            deps.storage,
+            None,
+            Some(Bound::exclusive(env.block.time.nanos())),
+            Order::Ascending,
+        )
+        .collect::<StdResult<Vec<(u64, UnbondingEntry)>>>()?;
+    let (settled, pending): (Vec<_>, Vec<_>) = matured
+        .into_iter()
+        .partition(|(_, entry)| entry.is_settled());
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let mut owed = REWARD_RESERVES
+        .may_load(deps.storage, denom)?
+        .unwrap_or_default()
+        .checked_add(
+            ORPHANED_REWARDS
+                .may_load(deps.storage, denom)?
+                .unwrap_or_default(),
+        )
+        .map_err(StdError::from)?;
+    for (_, entry) in settled.iter() {
+        owed = owed
+            .checked_add(entry.returned.unwrap_or_default())
+            .map_err(StdError::from)?;
+    }
+    let balance = deps.querier.query_balance(&env.contract.address, denom)?;
+    let received = balance.amount.saturating_sub(owed);
+    if received.is_zero() {
+        return Ok(());
+    }
+
+    let mut expected = Uint128::zero();
+    for (_, entry) in pending.iter() {
+        expected = expected.checked_add(entry.amount).map_err(StdError::from)?;
+    }
+    let received = received.min(expected);
+    for (completion_time, mut entry) in pending.into_iter() {
+        entry.returned = Some(entry.amount.multiply_ratio(received, expected));
+        UNBONDINGS.save(deps.storage, (denom, completion_time), &entry)?;
+    }
+
+    Ok(())
+}
+
+// Redeem the coin from the matured unbonding that completed at the
+// completion time, returning its share of the principal returned.
+// It fails until the principal returned for the unbonding is final.
+pub fn redeem_unbonding(
+    storage: &mut dyn Storage,
+    coin: &Coin,
+    completion_time: Timestamp,
+) -> Result<Coin, ContractError> {
+    let key = (coin.denom.as_str(), completion_time.nanos());
+    let mut entry = UNBONDINGS.may_load(storage, key)?.unwrap_or_default();
+    let returned = match entry.returned {
+        Some(returned) if !entry.amount.is_zero() && entry.is_settled() => returned,
+        _ => return Err(ContractError::UnbondedFundsNotReceived(coin.denom.clone())),
+    };
+
+    let amount = coin.amount.min(entry.amount);
+    let paid = returned.multiply_ratio(amount, entry.amount);
+    entry.amount -= amount;
+    entry.estimated = entry.estimated.min(entry.amount);
+    entry.returned = Some(returned - paid);
+    if entry.amount.is_zero() {
+        UNBONDINGS.remove(storage, key);
+    } else {
+        UNBONDINGS.save(storage, key, &entry)?;
+    }
+
+    Ok(Coin {
+        denom: coin.denom.clone(),
+        amount: paid,
+    })
+}
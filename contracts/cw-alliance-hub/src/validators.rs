@@ -0,0 +1,463 @@
+use cosmwasm_std::{Coin, Decimal, Deps, Env, StdError, StdResult, Storage, Uint128};
+
+use crate::entry_points::query;
+use crate::msg::ValidatorWeight;
+use crate::state::{SplitMode, ValidatorStrategy, CFG, VALIDATORS_CURSOR};
+use crate::ContractError;
+
+// Validator that can be selected by the validator strategies
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub address: String,
+    pub commission: Decimal,
+    pub voting_power: Uint128,
+}
+
+// Select the validators the funds are delegated to following
+// the strategy configured in the hub. The selector keeps the
+// state of the strategy between the selections of the same
+// execution so each coin can be sent to a different validator.
+pub struct ValidatorSelector {
+    strategy: ValidatorStrategy,
+    split_mode: SplitMode,
+    max_validators: u64,
+    candidates: Vec<Candidate>,
+    user_weights: Option<Vec<Uint128>>,
+    seed: u64,
+    remaining: u64,
+    cursor: u64,
+    selections: u64,
+}
+
+impl ValidatorSelector {
+    pub fn load(deps: Deps, env: &Env) -> Result<Self, ContractError> {
+        let cfg = CFG.load(deps.storage)?;
+        let mut candidates: Vec<Candidate> = match &cfg.validator_strategy {
+            ValidatorStrategy::VotingPower => query::bonded_validators(deps.querier)?
+                .into_iter()
+                .filter(|val| !val.jailed)
+                .map(|val| Candidate {
+                    address: val.operator_address,
+                    commission: Decimal::zero(),
+                    voting_power: val.tokens,
+                })
+                .collect(),
+            ValidatorStrategy::Curated { validators } => {
+                let active = query::all_validators(deps.querier)?;
+                validators
+                    .iter()
+                    .filter_map(|addr| active.iter().find(|val| &val.address == addr))
+                    .map(|val| Candidate {
+                        address: val.address.clone(),
+                        commission: val.commission,
+                        voting_power: Uint128::zero(),
+                    })
+                    .collect()
+            }
+            _ => query::all_validators(deps.querier)?
+                .into_iter()
+                .map(|val| Candidate {
+                    address: val.address,
+                    commission: val.commission,
+                    voting_power: Uint128::zero(),
+                })
+                .collect(),
+        };
+
+        // The weighted split needs the voting power of the validators
+        if cfg.split_mode == SplitMode::Weighted
+            && cfg.validator_strategy != ValidatorStrategy::VotingPower
+        {
+            let bonded = query::bonded_validators(deps.querier)?;
+            for candidate in candidates.iter_mut() {
+                if let Some(val) = bonded
+                    .iter()
+                    .find(|val| val.operator_address == candidate.address)
+                {
+                    candidate.voting_power = val.tokens;
+                }
+            }
+        }
+        let cursor = VALIDATORS_CURSOR
+            .may_load(deps.storage)?
+            .unwrap_or_default();
+
+        ValidatorSelector::new(cfg.validator_strategy, candidates, env.block.height, cursor)
+            .map(|selector| selector.with_split(cfg.split_mode, cfg.max_validators_per_denom))
+    }
+
+    pub fn new(
+        strategy: ValidatorStrategy,
+        candidates: Vec<Candidate>,
+        seed: u64,
+        cursor: u64,
+    ) -> Result<Self, ContractError> {
+        if candidates.is_empty() {
+            return Err(ContractError::NoValidatorsFound {});
+        }
+
+        Ok(ValidatorSelector {
+            strategy,
+            split_mode: SplitMode::Single,
+            max_validators: 1,
+            remaining: candidates.len() as u64,
+            candidates,
+            user_weights: None,
+            seed,
+            cursor,
+            selections: 0,
+        })
+    }
+
+    // Use the validators chosen by the user when there are any, checking
+    // they are active and allowed by the hub, otherwise fall back to the
+    // validators selected by the strategy configured in the hub
+    pub fn load_with_user_validators(
+        deps: Deps,
+        env: &Env,
+        validators: Option<Vec<ValidatorWeight>>,
+    ) -> Result<Self, ContractError> {
+        let validators = match validators {
+            Some(validators) if !validators.is_empty() => validators,
+            _ => return ValidatorSelector::load(deps, env),
+        };
+        let cfg = CFG.load(deps.storage)?;
+        let active = query::all_validators(deps.querier)?;
+
+        let mut candidates: Vec<Candidate> = vec![];
+        let mut weights = vec![];
+        for val in validators {
+            let allowed = match &cfg.validators_allowlist {
+                Some(allowlist) => allowlist.contains(&val.address),
+                None => true,
+            };
+            let active_val = match active.iter().find(|active| active.address == val.address) {
+                Some(active_val) if allowed => active_val,
+                _ => return Err(ContractError::ValidatorNotAllowed(val.address)),
+            };
+            if val.weight.is_zero() || candidates.iter().any(|c| c.address == val.address) {
+                return Err(ContractError::InvalidValidatorWeights {});
+            }
+
+            candidates.push(Candidate {
+                address: active_val.address.clone(),
+                commission: active_val.commission,
+                voting_power: Uint128::zero(),
+            });
+            weights.push(val.weight);
+        }
+
+        let mut selector =
+            ValidatorSelector::new(cfg.validator_strategy, candidates, env.block.height, 0)?;
+        selector.user_weights = Some(weights);
+
+        Ok(selector)
+    }
+
+    // Validators chosen by the user, if any
+    pub fn user_validators(&self) -> Option<Vec<String>> {
+        self.user_weights.as_ref()?;
+
+        Some(
+            self.candidates
+                .iter()
+                .map(|val| val.address.clone())
+                .collect(),
+        )
+    }
+
+    pub fn with_split(mut self, split_mode: SplitMode, max_validators: u64) -> Self {
+        self.split_mode = split_mode;
+        self.max_validators = max_validators;
+        self
+    }
+
+    // Divide the coin between the validators selected for it
+    // following the split mode or the weights chosen by the user
+    pub fn split(&mut self, coin: &Coin) -> Result<Vec<(String, Coin)>, ContractError> {
+        // The validators chosen by the user get the coin by their weights
+        if let Some(weights) = &self.user_weights {
+            let validators = self
+                .candidates
+                .iter()
+                .map(|val| val.address.clone())
+                .collect();
+
+            return divide(coin, validators, weights.clone());
+        }
+
+        let len = self.max_validators.min(self.candidates.len() as u64);
+        if self.split_mode == SplitMode::Single || len <= 1 {
+            return Ok(vec![(self.next(None)?, coin.clone())]);
+        }
+
+        // Select distinct validators, the strategies can return the
+        // same validator more than once so the attempts are bounded
+        let mut validators: Vec<String> = vec![];
+        for _ in 0..self.candidates.len() * 2 {
+            if validators.len() as u64 == len {
+                break;
+            }
+            let validator = self.next(None)?;
+            if !validators.contains(&validator) {
+                validators.push(validator);
+            }
+        }
+        for val in self.candidates.iter() {
+            if validators.len() as u64 == len {
+                break;
+            }
+            if !validators.contains(&val.address) {
+                validators.push(val.address.clone());
+            }
+        }
+
+        let weights = validators
+            .iter()
+            .map(|validator| match self.split_mode {
+                SplitMode::Weighted => self
+                    .candidates
+                    .iter()
+                    .find(|val| &val.address == validator)
+                    .map(|val| val.voting_power)
+                    .unwrap_or_default(),
+                _ => Uint128::one(),
+            })
+            .collect::<Vec<Uint128>>();
+
+        divide(coin, validators, weights)
+    }
+
+    // Select the next validator, the excluded validator is skipped
+    // when there are other candidates (e.g. the redelegation source)
+    pub fn next(&mut self, exclude: Option<&str>) -> Result<String, ContractError> {
+        let len = self.candidates.len();
+        let mut index = match self.strategy {
+            ValidatorStrategy::PseudoRandom | ValidatorStrategy::Curated { .. } => {
+                let index = get_pseudorandom(self.seed, self.remaining);
+
+                // Remove 1 of the index to generate a new
+                // pseudorandom index in the next iteration
+                if self.remaining > 1 {
+                    self.remaining -= 1
+                }
+
+                index as usize
+            }
+            ValidatorStrategy::VotingPower => self.weighted_index()?,
+            ValidatorStrategy::LowestCommission => self.lowest_commission_index(exclude),
+            ValidatorStrategy::RoundRobin => {
+                let index = self.cursor % len as u64;
+                self.cursor += 1;
+
+                index as usize
+            }
+        };
+        self.selections += 1;
+
+        if len > 1 && Some(self.candidates[index].address.as_str()) == exclude {
+            index = (index + 1) % len;
+        }
+
+        Ok(self.candidates[index].address.clone())
+    }
+
+    // Store the cursor of the round robin strategy
+    // so the next execution starts where this one ended
+    pub fn save(&self, storage: &mut dyn Storage) -> StdResult<()> {
+        match self.strategy {
+            ValidatorStrategy::RoundRobin => VALIDATORS_CURSOR.save(storage, &self.cursor),
+            _ => Ok(()),
+        }
+    }
+
+    fn weighted_index(&self) -> Result<usize, ContractError> {
+        let voting_powers = self
+            .candidates
+            .iter()
+            .map(|val| val.voting_power)
+            .collect::<Vec<Uint128>>();
+        let total = sum_weights(&voting_powers)?;
+        if total.is_zero() {
+            return Ok(0);
+        }
+
+        let seed = self.seed.wrapping_add(self.selections);
+        let random = ((mix(seed) as u128) << 64) | mix(seed.wrapping_add(1)) as u128;
+        let mut point = Uint128::new(random % total.u128());
+        for (index, val) in self.candidates.iter().enumerate() {
+            if point < val.voting_power {
+                return Ok(index);
+            }
+            point -= val.voting_power;
+        }
+
+        Ok(self.candidates.len() - 1)
+    }
+
+    fn lowest_commission_index(&self, exclude: Option<&str>) -> usize {
+        self.candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, val)| Some(val.address.as_str()) != exclude)
+            .min_by_key(|(_, val)| val.commission)
+            .map(|(index, _)| index)
+            .unwrap_or_default()
+    }
+}
+
+// Divide the coin between the validators by their weights, or evenly
+// when there are no weights, delegating the remainder of the division
+// to the first validator so the result is deterministic
+fn divide(
+    coin: &Coin,
+    validators: Vec<String>,
+    weights: Vec<Uint128>,
+) -> Result<Vec<(String, Coin)>, ContractError> {
+    let total = sum_weights(&weights)?;
+    let weights = match total.is_zero() {
+        true => vec![Uint128::one(); validators.len()],
+        false => weights,
+    };
+    let total = sum_weights(&weights)?;
+
+    let mut amounts = weights
+        .iter()
+        .map(|weight| coin.amount.multiply_ratio(*weight, total))
+        .collect::<Vec<Uint128>>();
+    let distributed = sum_weights(&amounts)?;
+    amounts[0] += coin
+        .amount
+        .checked_sub(distributed)
+        .map_err(StdError::from)?;
+
+    Ok(validators
+        .into_iter()
+        .zip(amounts)
+        .filter(|(_, amount)| !amount.is_zero())
+        .map(|(validator, amount)| {
+            (
+                validator,
+                Coin {
+                    denom: coin.denom.clone(),
+                    amount,
+                },
+            )
+        })
+        .collect())
+}
+
+// The weights are chosen by the users or taken from the voting
+// power of the validators so their sum is checked for overflows
+fn sum_weights(weights: &[Uint128]) -> Result<Uint128, ContractError> {
+    weights.iter().try_fold(Uint128::zero(), |total, weight| {
+        total
+            .checked_add(*weight)
+            .map_err(|_| ContractError::ValidatorWeightsOverflow {})
+    })
+}
+
+// Validate the validators of the curated strategy, which must
+// be at least one operator address without repetitions
+pub fn validate_strategy(strategy: ValidatorStrategy) -> Result<ValidatorStrategy, ContractError> {
+    if let ValidatorStrategy::Curated { validators } = &strategy {
+        if validators.is_empty() {
+            return Err(ContractError::NoCuratedValidators {});
+        }
+        validate_validators(validators)?;
+    }
+
+    Ok(strategy)
+}
+
+// Validate the operator addresses of the allowlist,
+// an empty allowlist allows any active validator
+pub fn validate_allowlist(
+    allowlist: Option<Vec<String>>,
+) -> Result<Option<Vec<String>>, ContractError> {
+    match allowlist {
+        Some(allowlist) if !allowlist.is_empty() => {
+            validate_validators(&allowlist)?;
+            Ok(Some(allowlist))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn validate_validators(validators: &[String]) -> Result<(), ContractError> {
+    for (index, validator) in validators.iter().enumerate() {
+        if !is_valoper_address(validator) {
+            return Err(ContractError::InvalidValidatorAddress(validator.clone()));
+        }
+        if validators[..index].contains(validator) {
+            return Err(ContractError::RepeatedValidator(validator.clone()));
+        }
+    }
+
+    Ok(())
+}
+
+// The operator addresses are lowercase bech32 addresses which
+// prefix ends with "valoper" (e.g. "terravaloper1..."). The api of
+// the contract only validates the addresses of the accounts so the
+// checksum of the address is verified here.
+fn is_valoper_address(address: &str) -> bool {
+    const CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+    const GENERATORS: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+    let (prefix, data) = match address.rsplit_once('1') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    if prefix.len() <= "valoper".len()
+        || !prefix.ends_with("valoper")
+        || !prefix.bytes().all(|byte| byte.is_ascii_lowercase())
+        || data.len() < 6
+        || address.len() > 90
+    {
+        return false;
+    }
+    let data = match data
+        .chars()
+        .map(|char| CHARSET.find(char).map(|value| value as u32))
+        .collect::<Option<Vec<u32>>>()
+    {
+        Some(data) => data,
+        None => return false,
+    };
+
+    let values = prefix
+        .bytes()
+        .map(|byte| byte as u32 >> 5)
+        .chain([0])
+        .chain(prefix.bytes().map(|byte| byte as u32 & 31))
+        .chain(data);
+    let mut checksum = 1u32;
+    for value in values {
+        let top = checksum >> 25;
+        checksum = (checksum & 0x1ffffff) << 5 ^ value;
+        for (index, generator) in GENERATORS.iter().enumerate() {
+            if (top >> index) & 1 == 1 {
+                checksum ^= generator;
+            }
+        }
+    }
+
+    checksum == 1
+}
+
+fn get_pseudorandom(block_height: u64, max: u64) -> u64 {
+    let seed: u64 = block_height % (max + 1);
+
+    seed % max
+}
+
+// Spread the bits of the seed (splitmix64) so consecutive
+// block heights select validators far from each other
+fn mix(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+
+    z ^ (z >> 31)
+}
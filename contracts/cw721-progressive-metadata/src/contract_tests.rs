@@ -298,6 +298,125 @@ fn burning() {
     assert!(tokens.tokens.is_empty());
 }
 
+#[test]
+fn burning_by_minter() {
+    let mut deps = mock_dependencies();
+    let contract = setup_contract(deps.as_mut());
+
+    let token_id = "petrify".to_string();
+    let mint_msg = ExecuteMsg::Mint {
+        token_id: token_id.clone(),
+        owner: "demeter".to_string(),
+        token_uri: None,
+        extension: None,
+    };
+
+    // mint some NFT to another owner
+    let minter = mock_info(MINTER, &[]);
+    let _ = contract
+        .execute(deps.as_mut(), mock_env(), minter.clone(), mint_msg)
+        .unwrap();
+
+    // the minter has no access to the NFT through the standard burn
+    let err = contract
+        .execute(
+            deps.as_mut(),
+            mock_env(),
+            minter.clone(),
+            ExecuteMsg::Burn {
+                token_id: token_id.clone(),
+            },
+        )
+        .unwrap_err();
+    assert_eq!(err, ContractError::Ownership(OwnershipError::NotOwner));
+
+    // random cannot burn it on behalf of the owner
+    let minter_burn_msg = ExecuteMsg::MinterBurn { token_id };
+    let err = contract
+        .execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("random", &[]),
+            minter_burn_msg.clone(),
+        )
+        .unwrap_err();
+    assert_eq!(err, ContractError::Ownership(OwnershipError::NotOwner));
+
+    // minter can burn it on behalf of the owner
+    let _ = contract
+        .execute(deps.as_mut(), mock_env(), minter, minter_burn_msg)
+        .unwrap();
+
+    let count = contract.num_tokens(deps.as_ref()).unwrap();
+    assert_eq!(0, count.count);
+}
+
+#[test]
+fn locking_nft() {
+    let mut deps = mock_dependencies();
+    let contract = setup_contract(deps.as_mut());
+
+    let token_id = "petrify".to_string();
+    let mint_msg = ExecuteMsg::Mint {
+        token_id: token_id.clone(),
+        owner: "demeter".to_string(),
+        token_uri: None,
+        extension: None,
+    };
+    let minter = mock_info(MINTER, &[]);
+    contract
+        .execute(deps.as_mut(), mock_env(), minter.clone(), mint_msg)
+        .unwrap();
+
+    // the owner cannot lock it
+    let owner = mock_info("demeter", &[]);
+    let lock_msg = ExecuteMsg::Lock {
+        token_id: token_id.clone(),
+    };
+    let err = contract
+        .execute(deps.as_mut(), mock_env(), owner.clone(), lock_msg.clone())
+        .unwrap_err();
+    assert_eq!(err, ContractError::Ownership(OwnershipError::NotOwner));
+
+    // the minter cannot lock unknown tokens
+    let err = contract
+        .execute(
+            deps.as_mut(),
+            mock_env(),
+            minter.clone(),
+            ExecuteMsg::Lock {
+                token_id: "unknown".to_string(),
+            },
+        )
+        .unwrap_err();
+    assert_eq!(err, ContractError::TokenIdNotFound {});
+
+    // the minter can lock it
+    contract
+        .execute(deps.as_mut(), mock_env(), minter, lock_msg)
+        .unwrap();
+
+    // and the owner cannot transfer or send it anymore
+    let transfer_msg = ExecuteMsg::TransferNft {
+        recipient: String::from("random"),
+        token_id: token_id.clone(),
+    };
+    let err = contract
+        .execute(deps.as_mut(), mock_env(), owner.clone(), transfer_msg)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Locked {});
+
+    let send_msg = ExecuteMsg::SendNft {
+        contract: String::from("another_contract"),
+        token_id,
+        msg: to_binary("You now have the melting power").unwrap(),
+    };
+    let err = contract
+        .execute(deps.as_mut(), mock_env(), owner, send_msg)
+        .unwrap_err();
+    assert_eq!(err, ContractError::Locked {});
+}
+
 #[test]
 fn transferring_nft() {
     let mut deps = mock_dependencies();
@@ -16,6 +16,9 @@ pub enum ContractError {
     #[error("token_id not found")]
     TokenIdNotFound {},
 
+    #[error("token_id is locked and cannot be transferred")]
+    Locked {},
+
     #[error("Cannot set approval that is already expired")]
     Expired {},
 
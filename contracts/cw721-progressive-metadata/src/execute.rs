@@ -73,6 +73,8 @@ where
                 msg,
             } => self.send_nft(deps, env, info, contract, token_id, msg),
             ExecuteMsg::Burn { token_id } => self.burn(deps, env, info, token_id),
+            ExecuteMsg::Lock { token_id } => self.lock(deps, info, token_id),
+            ExecuteMsg::MinterBurn { token_id } => self.minter_burn(deps, info, token_id),
             ExecuteMsg::UpdateOwnership(action) => Self::update_ownership(deps, env, info, action),
             ExecuteMsg::Extension { msg: _ } => Ok(Response::default()),
             ExecuteMsg::UpdateExtension {
@@ -157,6 +159,44 @@ where
             .add_attribute("token_id", token_id))
     }
 
+    pub fn lock(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        token_id: String,
+    ) -> Result<Response<C>, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        if !self.tokens.has(deps.storage, &token_id) {
+            return Err(ContractError::TokenIdNotFound {});
+        }
+        self.locked_tokens.save(deps.storage, &token_id, &true)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "lock")
+            .add_attribute("owner", info.sender)
+            .add_attribute("token_id", token_id))
+    }
+
+    pub fn minter_burn(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        token_id: String,
+    ) -> Result<Response<C>, ContractError> {
+        cw_ownable::assert_owner(deps.storage, &info.sender)?;
+
+        if !self.tokens.has(deps.storage, &token_id) {
+            return Err(ContractError::TokenIdNotFound {});
+        }
+        self._burn(deps, &token_id)?;
+
+        Ok(Response::new()
+            .add_attribute("action", "minter_burn")
+            .add_attribute("minter", info.sender)
+            .add_attribute("token_id", token_id))
+    }
+
     /// Migrates the contract from the previous version to the current
     /// version.
     pub fn migrate(deps: DepsMut, _env: Env) -> Result<Response<C>, ContractError> {
@@ -309,8 +349,7 @@ where
         let token = self.tokens.load(deps.storage, &token_id)?;
         self.check_can_send(deps.as_ref(), &env, &info, &token)?;
 
-        self.tokens.remove(deps.storage, &token_id)?;
-        self.decrement_tokens(deps.storage)?;
+        self._burn(deps, &token_id)?;
 
         Ok(Response::new()
             .add_attribute("action", "burn")
@@ -327,6 +366,14 @@ where
     E: CustomMsg,
     Q: CustomMsg,
 {
+    pub fn _burn(&self, deps: DepsMut, token_id: &str) -> Result<(), ContractError> {
+        self.tokens.remove(deps.storage, token_id)?;
+        self.locked_tokens.remove(deps.storage, token_id);
+        self.decrement_tokens(deps.storage)?;
+
+        Ok(())
+    }
+
     pub fn _transfer_nft(
         &self,
         deps: DepsMut,
@@ -336,6 +383,10 @@ where
         token_id: &str,
     ) -> Result<TokenInfo<T>, ContractError> {
         let mut token = self.tokens.load(deps.storage, token_id)?;
+        // locked tokens cannot be transferred by anyone
+        if self.locked_tokens.has(deps.storage, token_id) {
+            return Err(ContractError::Locked {});
+        }
         // ensure we have permissions
         self.check_can_send(deps.as_ref(), env, info, &token)?;
         // set owner and remove existing approvals
@@ -67,6 +67,12 @@ pub enum ExecuteMsg<Extension, E> {
     /// Burn an NFT the sender has access to
     Burn { token_id: String },
 
+    /// Make an NFT non-transferable forever, can only be called by the minter
+    Lock { token_id: String },
+
+    /// Burn an NFT on behalf of its owner, can only be called by the minter
+    MinterBurn { token_id: String },
+
     /// Extension msg
     Extension { msg: E },
 